@@ -1,3 +1,96 @@
 use aarya_v1;
 ALTER TABLE questions
-ADD COLUMN radio bit DEFAULT b'0';
\ No newline at end of file
+ADD COLUMN radio bit DEFAULT b'0';
+create table question_reports (
+    report_id int unsigned auto_increment primary key,
+    question_id int unsigned not null,
+    test_id int unsigned null,
+    student_id int unsigned not null,
+    report_reason enum('wrong_answer', 'ambiguous', 'typo', 'other') not null check (report_reason in ('wrong_answer', 'ambiguous', 'typo', 'other')),
+    report_comment varchar(1024) null,
+    -- 0: open, 1: resolved, 2: dismissed
+    report_state tinyint unsigned not null default 0,
+    report_timestamp timestamp default current_timestamp(),
+    constraint fk_question_reports_questions foreign key (question_id) references questions (question_id),
+    constraint fk_question_reports_tests foreign key (test_id) references tests (test_id),
+    constraint fk_question_reports_students foreign key (student_id) references students (student_id)
+) auto_increment = 1000;
+create index idx_question_reports_question on question_reports (question_id, report_state);
+create table question_revisions (
+    revision_id int unsigned auto_increment primary key,
+    -- no foreign key, revisions outlive deleted questions
+    question_id int unsigned not null,
+    action_type enum('insert', 'update', 'delete') not null check (action_type in ('insert', 'update', 'delete')),
+    editor varchar(255) not null,
+    revision_reason varchar(1024) null,
+    revision_timestamp timestamp default current_timestamp(),
+    course_id int unsigned not null,
+    chapter_id int unsigned not null,
+    topic_id int unsigned not null,
+    que_text varchar(2048) not null,
+    que_description varchar(2048) not null,
+    choices varchar(2048) collate utf8mb4_bin not null check (json_valid(`choices`)),
+    answers varchar(2048) collate utf8mb4_bin not null check (json_valid(`answers`)),
+    radio bit null default 0,
+    ans_explanation varchar(2048) not null,
+    ans_hint varchar(1024) not null,
+    difficulty tinyint not null,
+    diff_reason varchar(1024) not null,
+    que_hash varchar(2048) null
+) auto_increment = 1000;
+create index idx_question_revisions_question on question_revisions (question_id, revision_id);
+-- existing questions start their history with an insert revision
+insert into question_revisions (
+        question_id,
+        action_type,
+        editor,
+        revision_reason,
+        course_id,
+        chapter_id,
+        topic_id,
+        que_text,
+        que_description,
+        choices,
+        answers,
+        radio,
+        ans_explanation,
+        ans_hint,
+        difficulty,
+        diff_reason,
+        que_hash
+    )
+select question_id,
+    'insert',
+    'migration',
+    'initial revision',
+    course_id,
+    chapter_id,
+    topic_id,
+    que_text,
+    que_description,
+    choices,
+    answers,
+    radio,
+    ans_explanation,
+    ans_hint,
+    difficulty,
+    diff_reason,
+    que_hash
+from questions;
+alter table test_questions
+add column revision_id int unsigned null
+after question_id;
+update test_questions tq
+set tq.revision_id = (
+        select max(qr.revision_id)
+        from question_revisions qr
+        where qr.question_id = tq.question_id
+    );
+alter table test_questions
+modify column revision_id int unsigned not null;
+alter table test_questions
+add constraint fk_test_questions_revisions foreign key (revision_id) references question_revisions (revision_id);
+alter table chapters
+add column chapter_order int unsigned not null default 0;
+alter table topics
+add column topic_order int unsigned not null default 0;
@@ -14,6 +14,7 @@ create table chapters (
     chapter_name varchar(128),
     chapter_description varchar(512),
     chapter_name_hash varchar(2048) null,
+    chapter_order int unsigned not null default 0,
     constraint unique_chapter_name_hash unique (chapter_name_hash),
     constraint fk_chapters_courses foreign key (course_id) references courses (course_id)
 );
@@ -23,6 +24,7 @@ create table topics (
     chapter_id int unsigned,
     topic_name varchar(128),
     topic_description varchar(512),
+    topic_order int unsigned not null default 0,
     constraint fk_topics_courses foreign key (course_id) references courses (course_id),
     constraint fk_topics_chapters foreign key (chapter_id) references chapters (chapter_id)
 );
@@ -76,15 +78,56 @@ create table tests(
     constraint fk_tests_courses foreign key (course_id) references courses (course_id),
     constraint fk_tests_students foreign key (student_id) references students (student_id)
 ) auto_increment = 1000;
+create table question_revisions (
+    revision_id int unsigned auto_increment primary key,
+    -- no foreign key, revisions outlive deleted questions
+    question_id int unsigned not null,
+    action_type enum('insert', 'update', 'delete') not null check (action_type in ('insert', 'update', 'delete')),
+    editor varchar(255) not null,
+    revision_reason varchar(1024) null,
+    revision_timestamp timestamp default current_timestamp(),
+    course_id int unsigned not null,
+    chapter_id int unsigned not null,
+    topic_id int unsigned not null,
+    que_text varchar(2048) not null,
+    que_description varchar(2048) not null,
+    choices varchar(2048) collate utf8mb4_bin not null check (json_valid(`choices`)),
+    answers varchar(2048) collate utf8mb4_bin not null check (json_valid(`answers`)),
+    radio bit null default 0,
+    ans_explanation varchar(2048) not null,
+    ans_hint varchar(1024) not null,
+    difficulty tinyint not null,
+    diff_reason varchar(1024) not null,
+    que_hash varchar(2048) null
+) auto_increment = 1000;
+create index idx_question_revisions_question on question_revisions (question_id, revision_id);
 create table test_questions (
     id int unsigned auto_increment primary key,
     test_id int unsigned not null,
     question_id int unsigned not null,
+    -- revision of the question shown in the test
+    revision_id int unsigned not null,
     -- 0: not attempted, 1: attempted, 2: correct, 3: incorrect
     question_state tinyint not null default 0,
     constraint fk_test_questions_tests foreign key (test_id) references tests (test_id),
-    constraint fk_test_questions_questions foreign key (question_id) references questions (question_id)
+    constraint fk_test_questions_questions foreign key (question_id) references questions (question_id),
+    constraint fk_test_questions_revisions foreign key (revision_id) references question_revisions (revision_id)
+) auto_increment = 1000;
+create table question_reports (
+    report_id int unsigned auto_increment primary key,
+    question_id int unsigned not null,
+    test_id int unsigned null,
+    student_id int unsigned not null,
+    report_reason enum('wrong_answer', 'ambiguous', 'typo', 'other') not null check (report_reason in ('wrong_answer', 'ambiguous', 'typo', 'other')),
+    report_comment varchar(1024) null,
+    -- 0: open, 1: resolved, 2: dismissed
+    report_state tinyint unsigned not null default 0,
+    report_timestamp timestamp default current_timestamp(),
+    constraint fk_question_reports_questions foreign key (question_id) references questions (question_id),
+    constraint fk_question_reports_tests foreign key (test_id) references tests (test_id),
+    constraint fk_question_reports_students foreign key (student_id) references students (student_id)
 ) auto_increment = 1000;
+create index idx_question_reports_question on question_reports (question_id, report_state);
 create table students_history (
     history_id int unsigned auto_increment primary key,
     student_id int unsigned,
@@ -0,0 +1,33 @@
+$(function () {
+	$("#submit-button").on("click", function (event) {
+		var $this = $(this);
+		event.preventDefault();
+
+		var payload = {
+			email: $("#email").val(),
+			password: $("#password").val(),
+			role: $("#role").val(),
+			author: $("#authors").val() || null,
+		};
+
+		$("#errors").empty();
+		$.ajax({
+			url: $this.data("url"),
+			type: "POST",
+			contentType: "application/json",
+			data: JSON.stringify(payload),
+			success: function (data) {
+				console.log(data);
+				$("#errors").hide();
+				window.location = "/admin/accounts";
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = err.responseText;
+				$("#errors").append(errorMessage).show();
+			},
+		});
+	});
+});
\ No newline at end of file
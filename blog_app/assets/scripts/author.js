@@ -58,6 +58,11 @@ $(function () {
 			},
 			error: function (err) {
 				console.error("Upload failed:", err);
+				$("#photo-field-error").text(
+					err.responseJSON && err.responseJSON.error
+						? err.responseJSON.error
+						: "Upload failed."
+				);
 				toggle_form(false);
 				toggle_upload(true);
 			},
@@ -0,0 +1,61 @@
+$(function () {
+	function show_error(message) {
+		const errorMessage = document.createElement("p");
+		errorMessage.classList.add("text-sm", "p-1");
+		errorMessage.textContent = message;
+		$("#errors").append(errorMessage).show();
+	}
+
+	$("#bulk-action").on("change", function () {
+		$("#bulk-reassign").toggleClass("hidden", $(this).val() !== "delete");
+	});
+
+	$("#bulk-apply").on("click", function () {
+		var ids = $(".bulk-select:checked")
+			.map(function () {
+				return $(this).val();
+			})
+			.get();
+		if (!ids.length) {
+			return;
+		}
+
+		var action = $("#bulk-action").val();
+		if (action === "delete" && !confirm(`Delete ${ids.length} for good? This cannot be undone.`)) {
+			return;
+		}
+
+		var payload = { action: action, ids: ids };
+		var reassign_to = $("#reassign-to").val();
+		if (action === "delete" && reassign_to) {
+			payload.reassign_to = reassign_to;
+		}
+		console.log(payload);
+
+		$("#errors").empty().hide();
+		$.ajax({
+			url: $("#bulk-actions").data("url"),
+			type: "POST",
+			contentType: "application/json",
+			data: JSON.stringify(payload),
+			success: function (data) {
+				console.log(data);
+				if (!data.failed.length) {
+					window.location.reload();
+					return;
+				}
+				data.failed.forEach((failure) => {
+					var name = $(`.bulk-select[value='${failure.id}']`).data("name");
+					show_error(`${name}: ${failure.reason}`);
+				});
+				if (data.done) {
+					show_error(`${data.done} done, reload to see them.`);
+				}
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				show_error(err.responseText);
+			},
+		});
+	});
+});
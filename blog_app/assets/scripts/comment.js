@@ -0,0 +1,24 @@
+$(function () {
+	$(".moderate-button").on("click", function () {
+		var $this = $(this);
+
+		$("#errors").empty();
+		$.ajax({
+			url: $this.data("url"),
+			type: "POST",
+			contentType: "application/json",
+			data: JSON.stringify({ status: $this.data("status") }),
+			success: function (data) {
+				console.log(data);
+				$this.closest("div.rounded").remove();
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = err.responseText;
+				$("#errors").append(errorMessage).show();
+			},
+		});
+	});
+});
@@ -0,0 +1,54 @@
+$(function () {
+	function show_result(message, failed) {
+		$("#comment-result")
+			.text(message)
+			.toggleClass("text-yellow-300", failed)
+			.show();
+	}
+
+	function cancel_reply() {
+		$("#comment-parent-id").val("");
+		$("#replying-to").hide();
+	}
+
+	$(".reply-button").on("click", function () {
+		var $this = $(this);
+		$("#comment-parent-id").val($this.data("id"));
+		$("#replying-to-name").text($this.data("name"));
+		$("#replying-to").show();
+		$("#comment-name").trigger("focus");
+		$("#comment-form")[0].scrollIntoView({ behavior: "smooth" });
+	});
+
+	$("#cancel-reply").on("click", cancel_reply);
+
+	$("#comment-form").on("submit", function (event) {
+		var $form = $(this);
+		event.preventDefault();
+
+		var payload = {
+			post_id: $("#comment-post-id").val(),
+			parent_id: $("#comment-parent-id").val() || null,
+			name: $("#comment-name").val(),
+			email: $("#comment-email").val(),
+			body: $("#comment-body").val(),
+			website: $("#comment-website").val(),
+		};
+
+		$.ajax({
+			url: $form.data("url"),
+			type: "POST",
+			contentType: "application/json",
+			data: JSON.stringify(payload),
+			success: function (data) {
+				show_result(data, false);
+				$("#comment-body").val("");
+				cancel_reply();
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				show_result(err.responseText || "Error posting comment", true);
+			},
+		});
+	});
+});
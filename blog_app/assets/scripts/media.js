@@ -0,0 +1,25 @@
+$(function () {
+	$(".delete-button").on("click", function () {
+		var $this = $(this);
+		if (!window.confirm("Delete this file with all of its sizes? This cannot be undone.")) {
+			return;
+		}
+
+		$("#errors").empty();
+		$.ajax({
+			url: $this.data("url"),
+			type: "POST",
+			success: function (data) {
+				console.log(data);
+				window.location.reload();
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = err.responseText;
+				$("#errors").append(errorMessage).show();
+			},
+		});
+	});
+});
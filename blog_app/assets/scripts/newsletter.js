@@ -0,0 +1,28 @@
+$(function () {
+	$("#send-digest").on("click", function () {
+		var $this = $(this);
+		if (!window.confirm("Send the digest to every confirmed subscriber now?")) {
+			return;
+		}
+
+		$this.prop("disabled", true);
+		$("#errors").empty().hide();
+		$.ajax({
+			url: $this.data("url"),
+			type: "POST",
+			success: function (data) {
+				console.log(data);
+				$("#result").text(data).show();
+				$this.hide();
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = err.responseText;
+				$("#errors").append(errorMessage).show();
+				$this.prop("disabled", false);
+			},
+		});
+	});
+});
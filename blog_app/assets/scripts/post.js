@@ -18,16 +18,48 @@ $(function () {
 	let toggle_body_upload = function (toggle) {
 		$("#body-upload-button").prop("disabled", toggle);
 	};
-	let tagId = $("#tag-id");
-	let authorId = $("#author-id");
-	if (tagId) {
-		$("#tags").val(tagId.val());
-		console.log("Tag ID:", tagId.val());
-	}
-	if (authorId) {
-		$("#authors").val(authorId.val());
-		console.log("Author ID:", authorId.val());
+	// the upload apis answer failed uploads with a json error
+	let upload_error = function (err) {
+		return err.responseJSON && err.responseJSON.error
+			? err.responseJSON.error
+			: "Upload failed.";
+	};
+	// the saved ids come comma separated, the main author or tag first
+	let select_ids = function (ids, main, more) {
+		let values = (ids.val() || "").split(",").filter(Boolean);
+		if (values.length) {
+			$(main).val(values[0]);
+			$(more).val(values.slice(1));
+		}
+	};
+	// the main author or tag leads, the rest follow without repeating it
+	let selected_ids = function (main, more) {
+		let first = $(main).val();
+		let rest = ($(more).val() || []).filter((id) => id !== first);
+		return first ? [first].concat(rest) : rest;
+	};
+	select_ids($("#author-ids"), "#authors", "#co-authors");
+	select_ids($("#tag-ids"), "#tags", "#more-tags");
+	let statusValue = $("#status-value");
+	if (statusValue.length) {
+		$("#status").val(statusValue.val());
 	}
+	$("#preview-button").on("click", function () {
+		$.ajax({
+			url: $(this).data("url"),
+			type: "POST",
+			success: function (data) {
+				$("#preview-url").val(data.url).removeClass("hidden").trigger("select");
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = err.responseText;
+				$("#errors").append(errorMessage).show();
+			},
+		});
+	});
 	$("#photo-field").on("change", function () {
 		var file = this.files[0];
 		console.info("File selected:", file.name);
@@ -80,6 +112,7 @@ $(function () {
 			},
 			error: function (err) {
 				console.error("Upload failed:", err);
+				$("#photo-field-error").text(upload_error(err));
 				toggle_form(false);
 				toggle_upload(true);
 				toggle_more_upload(true);
@@ -153,6 +186,7 @@ $(function () {
 			},
 			error: function (err) {
 				console.error("Upload failed:", err);
+				$("#more-photos-field-error").text(upload_error(err));
 				toggle_form(false);
 				toggle_upload(true);
 				toggle_more_upload(true);
@@ -213,6 +247,7 @@ $(function () {
 			},
 			error: function (err) {
 				console.error("Upload failed:", err);
+				$("#body-field-error").text(upload_error(err));
 				toggle_form(false);
 				toggle_upload(true);
 				toggle_more_upload(true);
@@ -233,10 +268,12 @@ $(function () {
 			hero_image: $("#hero-image-url").val(),
 			publish_date: Date.parse($("#publish_date").val()) / 1000,
 			modified_date: Date.parse($("#modified_date").val()) / 1000,
-			author: $("#authors").val(),
-			tag: $("#tags").val(),
+			authors: selected_ids("#authors", "#co-authors"),
+			tags: selected_ids("#tags", "#more-tags"),
 			permalink: $("#permalink").val(),
 			description: $("#description").val(),
+			featured: $("#featured").is(":checked"),
+			status: $("#status").val(),
 		};
 		console.log(payload);
 
@@ -0,0 +1,65 @@
+import Ajv from "https://cdn.skypack.dev/ajv";
+
+$(function () {
+	function show_error(message) {
+		const errorMessage = document.createElement("p");
+		errorMessage.classList.add("text-sm", "p-1");
+		errorMessage.textContent = message;
+		$("#errors").append(errorMessage).show();
+	}
+
+	$("#submit-button").on("click", function (event) {
+		var $this = $(this);
+		event.preventDefault();
+
+		var payload = {
+			permalink: $("#permalink").val().trim().replace(/^\/?blogs\//, ""),
+			target: $("#target").val().trim(),
+		};
+		console.log(payload);
+
+		var schema = JSON.parse($("#schema").val());
+		const ajv = new Ajv({ allErrors: true });
+		const validate = ajv.compile(schema);
+		const valid = validate(payload);
+		$("#errors").empty();
+		if (!valid) {
+			validate.errors.forEach((error) => {
+				show_error(`${error.instancePath} ${error.message}`);
+			});
+		} else {
+			$.ajax({
+				url: $this.data("url"),
+				type: "POST",
+				contentType: "application/json",
+				data: JSON.stringify(payload),
+				success: function (data) {
+					console.log(data);
+					window.location.reload();
+				},
+				error: function (err) {
+					console.error(err.responseText);
+					show_error(err.responseText);
+				},
+			});
+		}
+	});
+
+	$(".delete-button").on("click", function () {
+		var $this = $(this);
+
+		$("#errors").empty();
+		$.ajax({
+			url: $this.data("url"),
+			type: "POST",
+			success: function (data) {
+				console.log(data);
+				$this.closest("tr").remove();
+			},
+			error: function (err) {
+				console.error(err.responseText);
+				show_error(err.responseText);
+			},
+		});
+	});
+});
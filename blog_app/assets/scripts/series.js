@@ -0,0 +1,92 @@
+import Ajv from "https://cdn.skypack.dev/ajv";
+
+$(function () {
+	function add_part(id) {
+		var title = $("#post-choices option").filter(function () {
+			return $(this).val() === id;
+		}).text();
+		if (!title || $("#series-posts li[data-id='" + id + "']").length) {
+			return;
+		}
+
+		var $item = $("<li>").attr("data-id", id).addClass("py-1");
+		$item.append($("<span>").text(title));
+		$item.append($("<button type='button' class='part-up ml-2'>").text("↑"));
+		$item.append($("<button type='button' class='part-down ml-1'>").text("↓"));
+		$item.append($("<button type='button' class='part-remove ml-1 text-red-500'>").text("✕"));
+		$("#series-posts").append($item);
+	}
+
+	$("#post-ids")
+		.val()
+		.split(",")
+		.filter((id) => id)
+		.forEach(add_part);
+
+	$("#add-part").on("click", function () {
+		add_part($("#post-choices").val());
+	});
+
+	$("#series-posts").on("click", ".part-up", function () {
+		var $item = $(this).closest("li");
+		$item.insertBefore($item.prev());
+	});
+
+	$("#series-posts").on("click", ".part-down", function () {
+		var $item = $(this).closest("li");
+		$item.insertAfter($item.next());
+	});
+
+	$("#series-posts").on("click", ".part-remove", function () {
+		$(this).closest("li").remove();
+	});
+
+	$("#submit-button").on("click", function (event) {
+		var $this = $(this);
+		event.preventDefault();
+
+		var payload = {
+			name: $("#name").val(),
+			description: $("#description").val(),
+			posts: $("#series-posts li")
+				.map(function () {
+					return $(this).data("id");
+				})
+				.get(),
+		};
+		console.log(payload);
+
+		var schema = JSON.parse($("#schema").val());
+		const ajv = new Ajv({ allErrors: true });
+		const validate = ajv.compile(schema);
+		const valid = validate(payload);
+		$("#errors").empty();
+		if (!valid) {
+			validate.errors.forEach((error) => {
+				const errorMessage = document.createElement("p");
+				errorMessage.classList.add("text-sm", "p-1");
+				errorMessage.textContent = `${error.instancePath} ${error.message}`;
+				$("#errors").append(errorMessage).show();
+			});
+		} else {
+			$.ajax({
+				url: $this.data("url"),
+				type: "POST",
+				contentType: "application/json",
+				data: JSON.stringify(payload),
+				success: function (data) {
+					console.log(data);
+					$("#errors").hide();
+					window.location = "/admin/series";
+				},
+				error: function (err) {
+					console.error(err.responseText);
+					const errorMessage = document.createElement("p");
+					errorMessage.classList.add("text-sm", "p-1");
+					errorMessage.textContent = err.responseText;
+					$("#errors").append(errorMessage).show();
+				},
+			});
+		}
+	});
+});
@@ -0,0 +1,45 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use log::{debug, error, info};
+use mongodb::Client;
+
+use crate::{
+    comments::{self, CommentError},
+    models::CommentRequestModel,
+    utils::{
+        json_ops::{self, JsonOpsResult},
+        request_ops,
+    },
+};
+
+/// a reader comment or reply, queued until an editor approves it
+#[post("/api/comments")]
+pub async fn post_comment(
+    req: HttpRequest,
+    mongoc: web::Data<Client>,
+    model: web::Json<CommentRequestModel>,
+) -> impl Responder {
+    debug!("{:?}", model);
+
+    if let JsonOpsResult::Error(e) = json_ops::validate_json_text(
+        "./assets/schema/comment-schema.json",
+        serde_json::to_string(&model).unwrap().as_str(),
+    ) {
+        error!("Failed to validate comment: {:?}", e);
+        return HttpResponse::BadRequest().body("Please fill in your name, email and comment");
+    }
+
+    let address = request_ops::client_address(&req);
+    match comments::submit(&mongoc, &model, comments::client_hash(&address)).await {
+        Ok(()) => {
+            info!("Comment received for post {}", model.post_id);
+            HttpResponse::Ok().body("Thanks! Your comment will appear once it is approved.")
+        }
+        Err(CommentError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(CommentError::RateLimited) => HttpResponse::TooManyRequests()
+            .body("You are commenting too fast, please wait a few minutes"),
+        Err(CommentError::Database(e)) => {
+            error!("Failed to save comment: {:?}", e);
+            HttpResponse::InternalServerError().body("Error saving comment")
+        }
+    }
+}
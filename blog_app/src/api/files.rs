@@ -1,11 +1,16 @@
-use std::{collections::HashMap, fs::File, io::Write};
+use std::collections::HashMap;
 
-use actix_multipart::{Field, Multipart, MultipartError};
-use actix_web::{post, HttpResponse, Responder};
+use actix_multipart::{Field, Multipart};
+use actix_web::{post, web, HttpResponse, Responder};
 use futures::StreamExt;
+use log::error;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use crate::utils::random_ops;
+use crate::{content::ContentStore, entities::result_types::EntityResult};
+
+/// largest markdown body accepted
+const MAX_MARKDOWN_BYTES: usize = 1024 * 1024;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FileResponse {
@@ -13,36 +18,49 @@ pub struct FileResponse {
 }
 
 #[post("/api/markdown")]
-pub async fn post_markdown(mut payload: Multipart) -> impl Responder {
+pub async fn post_markdown(
+    content: web::Data<dyn ContentStore>,
+    mut payload: Multipart,
+) -> impl Responder {
     let mut response = Vec::new();
 
     if let Some(item) = payload.next().await {
-        let unique_key = random_ops::generate_guid(16);
-
-        // Create the file path with the unique key and original extension
-        let file_path = format!("./assets/markdowns/{}.md", unique_key);
+        let markdown = match item {
+            Ok(mut field) => read_markdown(&mut field).await,
+            Err(e) => Err(format!("The upload could not be read: {}", e)),
+        };
+        let markdown = match markdown {
+            Ok(r) => r,
+            Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e })),
+        };
 
-        response.push(process_file(item, &file_path, &unique_key).await);
+        match content.write(&markdown).await {
+            EntityResult::Success(key) => response.push(FileResponse {
+                paths: HashMap::from([("markdown".to_string(), key)]),
+            }),
+            EntityResult::Error(e) => {
+                error!("Failed to store markdown: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(json!({ "error": "The markdown could not be saved" }));
+            }
+        }
     }
 
     HttpResponse::Ok().json(response)
 }
 
-async fn process_file(
-    item: Result<Field, MultipartError>,
-    file_path: &String,
-    unique_key: &String,
-) -> FileResponse {
-    let mut field = item.unwrap();
-    // Create a new file and write the contents
-    let mut f = File::create(file_path).unwrap();
+async fn read_markdown(field: &mut Field) -> Result<String, String> {
+    let mut bytes = Vec::new();
     while let Some(chunk) = field.next().await {
-        let data = chunk.unwrap();
-        f.write_all(&data).unwrap();
+        let chunk = chunk.map_err(|e| format!("The upload could not be read: {}", e))?;
+        if bytes.len() + chunk.len() > MAX_MARKDOWN_BYTES {
+            return Err(format!(
+                "Markdown files must be {} KB or smaller",
+                MAX_MARKDOWN_BYTES / 1024
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
     }
 
-    // Append the unique key and extension to the response
-    FileResponse {
-        paths: HashMap::from([("markdown".to_string(), unique_key.to_string())]),
-    }
+    String::from_utf8(bytes).map_err(|_| String::from("Markdown files must be UTF-8 text"))
 }
@@ -1,2 +1,4 @@
+pub mod comments;
 pub mod files;
 pub mod photos;
+pub mod search;
@@ -1,27 +1,98 @@
-use std::{collections::HashMap, ffi::OsStr, fs::File, io::Write, path::Path};
+use std::{collections::HashMap, fs::File, io::Cursor};
 
-use actix_multipart::{Field, Multipart, MultipartError};
-use actix_web::{post, HttpResponse, Responder};
+use actix_multipart::{Field, Multipart};
+use actix_web::{post, web, HttpResponse, Responder};
 use futures::StreamExt;
-use image::{imageops::FilterType, GenericImageView};
+use image::{
+    codecs::{avif::AvifEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, GenericImageView, ImageFormat, ImageReader, Limits,
+};
+use log::{error, info};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::utils::{
-    image_ops::{ImagePath, ImageSize},
+    environ::Environ,
+    image_ops::{self, ImagePath, ImageSize},
     random_ops,
 };
 
+/// largest upload accepted, checked while the body streams in
+const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+/// widest and tallest image decoded, anything bigger is refused before its pixels are allocated
+const MAX_DIMENSION: u32 = 8192;
+/// memory a single decode may take, a tiny file that inflates past this is a decompression bomb
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+/// ravif speed (1 slowest to 10 fastest) and quality used for avif copies
+const AVIF_SPEED: u8 = 8;
+const AVIF_QUALITY: u8 = 70;
+
 #[derive(Deserialize, Serialize)]
 pub struct PhotoResponse {
     pub paths: Option<HashMap<String, String>>,
 }
 
+#[derive(Debug)]
+pub enum PhotoError {
+    Malformed(String),
+    TooLarge,
+    UnsupportedType,
+    Undecodable(String),
+    Storage(String),
+}
+
+impl PhotoError {
+    /// the json error sent back for a failed upload, naming the file when it is known
+    pub fn response(&self, filename: Option<&str>) -> HttpResponse {
+        let (mut builder, message) = match self {
+            PhotoError::Malformed(_) => (
+                HttpResponse::BadRequest(),
+                String::from("The upload could not be read"),
+            ),
+            PhotoError::TooLarge => (
+                HttpResponse::PayloadTooLarge(),
+                format!(
+                    "Images must be {} MB or smaller",
+                    MAX_UPLOAD_BYTES / 1024 / 1024
+                ),
+            ),
+            PhotoError::UnsupportedType => (
+                HttpResponse::UnsupportedMediaType(),
+                String::from("Only JPEG, PNG and WebP images are accepted"),
+            ),
+            PhotoError::Undecodable(_) => (
+                HttpResponse::UnprocessableEntity(),
+                format!(
+                    "The image is damaged or larger than {0}x{0} pixels",
+                    MAX_DIMENSION
+                ),
+            ),
+            PhotoError::Storage(_) => (
+                HttpResponse::InternalServerError(),
+                String::from("The image could not be saved"),
+            ),
+        };
+        builder.json(json!({ "error": message, "file": filename }))
+    }
+}
+
 #[post("/api/photo")]
 pub async fn post_photo(mut payload: Multipart) -> impl Responder {
     let mut response = Vec::new();
 
     if let Some(item) = payload.next().await {
-        response.push(process_photo(item).await);
+        let mut field = match item {
+            Ok(r) => r,
+            Err(e) => return PhotoError::Malformed(e.to_string()).response(None),
+        };
+        match process_photo(&mut field).await {
+            Ok(r) => response.push(r),
+            Err(e) => {
+                error!("Failed to process photo: {:?}", e);
+                return e.response(filename(&field).as_deref());
+            }
+        }
     }
 
     HttpResponse::Ok().json(response)
@@ -31,64 +102,95 @@ pub async fn post_photo(mut payload: Multipart) -> impl Responder {
 pub async fn post_photos(mut payload: Multipart) -> impl Responder {
     let mut response = Vec::new();
     while let Some(item) = payload.next().await {
-        response.push(process_photo(item).await);
+        let mut field = match item {
+            Ok(r) => r,
+            Err(e) => return PhotoError::Malformed(e.to_string()).response(None),
+        };
+        match process_photo(&mut field).await {
+            Ok(r) => response.push(r),
+            Err(e) => {
+                error!("Failed to process photo: {:?}", e);
+                return e.response(filename(&field).as_deref());
+            }
+        }
     }
 
     // Return the response as JSON
     HttpResponse::Ok().json(response)
 }
 
-async fn process_photo(item: Result<Field, MultipartError>) -> PhotoResponse {
-    let mut field = item.unwrap();
-    let content_disposition = field.content_disposition().clone();
-    let filename = content_disposition.get_filename().unwrap();
-    let extension = Path::new(filename)
-        .extension()
-        .and_then(OsStr::to_str)
-        .unwrap_or("");
-
-    // Generate a unique key
-    let unique_key = random_ops::generate_guid(16);
-
-    // Create the file path with the unique key and original extension
-    let image_path = ImagePath::new(unique_key.to_string(), extension.to_string());
+fn filename(field: &Field) -> Option<String> {
+    field
+        .content_disposition()
+        .get_filename()
+        .map(|name| name.to_string())
+}
 
-    // Create a new file and write the contents
-    let mut f = File::create(image_path.original_path()).unwrap();
+async fn process_photo(field: &mut Field) -> Result<PhotoResponse, PhotoError> {
+    // the whole upload is buffered, bounded by MAX_UPLOAD_BYTES
+    let mut bytes = Vec::new();
     while let Some(chunk) = field.next().await {
-        let data = chunk.unwrap();
-        f.write_all(&data).unwrap();
+        let chunk = chunk.map_err(|e| PhotoError::Malformed(e.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_UPLOAD_BYTES {
+            return Err(PhotoError::TooLarge);
+        }
+        bytes.extend_from_slice(&chunk);
     }
 
-    generate_photo_sizes(&image_path);
+    // decoding and encoding are cpu bound, keep them off the async workers
+    let avif = Environ::default().image_avif;
+    let image_path = web::block(move || store_photo(&bytes, avif))
+        .await
+        .map_err(|e| PhotoError::Storage(e.to_string()))??;
 
-    // Append the unique key and extension to the response
-    PhotoResponse {
+    info!("Photo stored {}", image_path);
+
+    Ok(PhotoResponse {
         paths: Some(image_path.get_all_paths()),
-    }
+    })
 }
 
-fn generate_photo_sizes(image_path: &ImagePath) {
-    // Iterate over all image sizes and create resized photos
+/// checks the real type of the upload from its magic bytes, decodes it within limits and
+/// writes the original and every display size, in its own format and as webp (and avif).
+/// Re-encoding from pixels drops exif, gps and any other metadata the upload carried.
+fn store_photo(bytes: &[u8], avif: bool) -> Result<ImagePath, PhotoError> {
+    let format = image::guess_format(bytes).map_err(|_| PhotoError::UnsupportedType)?;
+    let extension = match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        _ => return Err(PhotoError::UnsupportedType),
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let img = reader
+        .decode()
+        .map_err(|e| PhotoError::Undecodable(e.to_string()))?;
+
+    let orientation = match format {
+        ImageFormat::Jpeg => image_ops::exif_orientation(bytes),
+        _ => 1,
+    };
+    let img = image_ops::apply_orientation(img, orientation);
+
+    let image_path = ImagePath::new(random_ops::generate_guid(16), extension.to_string());
+    save_photo(&image_path, &ImageSize::Original, &img, format, avif)?;
     for size in ImageSize::get_display_sizes() {
-        resize_photo(image_path, &size);
+        save_photo(&image_path, &size, &resize_photo(&img, &size), format, avif)?;
     }
+
+    Ok(image_path)
 }
 
-fn resize_photo(image_path: &ImagePath, size: &ImageSize) {
-    let path = image_path;
-    let original_path = path.original_path();
-    let resized_path = path.from(size);
+fn resize_photo(img: &DynamicImage, size: &ImageSize) -> DynamicImage {
     let (width, height) = size.dimensions();
 
-    // do not proceed if the resized image already exists
-    if Path::new(&resized_path).exists() {
-        return;
-    }
-
-    // Open the original image
-    let img = image::open(original_path).unwrap();
-
     // Calculate the new dimensions while maintaining the aspect ratio
     let (orig_width, orig_height) = img.dimensions();
     let aspect_ratio = orig_width as f32 / orig_height as f32;
@@ -99,14 +201,44 @@ fn resize_photo(image_path: &ImagePath, size: &ImageSize) {
     };
 
     // Resize the image
-    let resized_img = img.resize(new_width, new_height, FilterType::Lanczos3);
-
-    // Save the resized image to disk
-    let mut output = File::create(&resized_path).unwrap();
-    resized_img
-        .write_to(
-            &mut output,
-            image::ImageFormat::from_extension(image_path.extension.clone()).unwrap(),
-        )
-        .unwrap();
+    img.resize(new_width, new_height, FilterType::Lanczos3)
+}
+
+/// writes one size of the photo in the upload's format and the modern formats
+fn save_photo(
+    image_path: &ImagePath,
+    size: &ImageSize,
+    img: &DynamicImage,
+    format: ImageFormat,
+    avif: bool,
+) -> Result<(), PhotoError> {
+    let storage = |e: image::ImageError| PhotoError::Storage(e.to_string());
+    let create = |extension: &str| {
+        File::create(image_path.variant_path(size, extension))
+            .map_err(|e| PhotoError::Storage(e.to_string()))
+    };
+
+    img.save_with_format(image_path.from(size), format)
+        .map_err(storage)?;
+
+    // the webp and avif encoders take 8 bit rgb(a) only, e.g. not 16 bit pngs
+    let img = match img.color().has_alpha() {
+        true => DynamicImage::ImageRgba8(img.to_rgba8()),
+        false => DynamicImage::ImageRgb8(img.to_rgb8()),
+    };
+
+    // the image crate encodes webp lossless only
+    if format != ImageFormat::WebP {
+        img.write_with_encoder(WebPEncoder::new_lossless(create("webp")?))
+            .map_err(storage)?;
+    }
+    if avif {
+        img.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            create("avif")?,
+            AVIF_SPEED,
+            AVIF_QUALITY,
+        ))
+        .map_err(storage)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,35 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use log::error;
+use mongodb::Client;
+use serde_json::json;
+
+use crate::{
+    entities::result_types::EntityResult,
+    models::SearchRequestModel,
+    search::{self, SearchIndex},
+    utils::reference_ops::ReferenceCache,
+};
+
+/// the results of /search as json, for search-as-you-type and other clients
+#[get("/api/search")]
+pub async fn get_search_results(
+    mongoc: web::Data<Client>,
+    search_index: web::Data<dyn SearchIndex>,
+    reference_cache: web::Data<ReferenceCache>,
+    query: web::Query<SearchRequestModel>,
+) -> impl Responder {
+    if query.q.as_deref().unwrap_or_default().trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({ "error": "The q parameter is required" }));
+    }
+    if query.page == Some(0) {
+        return HttpResponse::BadRequest().json(json!({ "error": "Pages start at 1" }));
+    }
+
+    match search::find_results(&mongoc, search_index.get_ref(), &reference_cache, &query).await {
+        EntityResult::Success(r) => HttpResponse::Ok().json(r),
+        EntityResult::Error(e) => {
+            error!("Failed to search posts: {:?}", e);
+            HttpResponse::InternalServerError().json(json!({ "error": "Error searching posts" }))
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{env, sync::OnceLock};
+
+use actix_session::{Session, SessionExt};
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    cookie::{Cookie, SameSite},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error, HttpMessage, HttpResponse,
+};
+use log::{error, info};
+use mongodb::Client;
+
+use crate::{
+    entities::{
+        accounts::{AccountEntity, AccountRole},
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    utils::{date_ops, db_ops::Database, environ::Environ, hash_ops, random_ops},
+};
+
+pub const SESSION_ACCOUNT_KEY: &str = "account_id";
+pub const SESSION_CSRF_KEY: &str = "csrf_token";
+/// readable by the admin scripts, which echo it back in the csrf header
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// admin pages and upload endpoints, the login page itself stays public
+fn is_protected(path: &str) -> bool {
+    let admin = (path == "/admin" || path.starts_with("/admin/")) && path != "/admin/login";
+    admin || path.starts_with("/api/photo") || path == "/api/markdown"
+}
+
+/// the csrf token of the session, created on first use
+pub fn csrf_token(session: &Session) -> String {
+    if let Ok(Some(token)) = session.get::<String>(SESSION_CSRF_KEY) {
+        return token;
+    }
+    let token = random_ops::generate_guid(32);
+    if let Err(e) = session.insert(SESSION_CSRF_KEY, &token) {
+        error!("Failed to store csrf token: {:?}", e);
+    }
+    token
+}
+
+pub fn csrf_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(CSRF_COOKIE, token)
+        .path("/")
+        .same_site(SameSite::Strict)
+        .secure(!cfg!(debug_assertions))
+        .http_only(false)
+        .finish()
+}
+
+/// SESSION_KEY when set, otherwise a per process secret so signed links die with the process
+fn signing_secret() -> &'static [u8] {
+    static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+    SECRET.get_or_init(|| match Environ::default().session_key {
+        Some(key) => key.into_bytes(),
+        None => random_ops::generate_guid(64).into_bytes(),
+    })
+}
+
+/// path of a draft preview that stays valid until the expiry timestamp
+pub fn preview_path(post_id: &str, expires: i64) -> String {
+    let signature = hash_ops::sign(
+        &format!("preview:{}:{}", post_id, expires),
+        signing_secret(),
+    );
+    format!(
+        "/preview/{}?expires={}&signature={}",
+        post_id, expires, signature
+    )
+}
+
+pub fn verify_preview(post_id: &str, expires: i64, signature: &str) -> bool {
+    expires > date_ops::to_timestamp()
+        && hash_ops::verify_signature(
+            &format!("preview:{}:{}", post_id, expires),
+            signature,
+            signing_secret(),
+        )
+}
+
+/// path that confirms a newsletter subscription, valid until the expiry timestamp
+pub fn confirm_subscription_path(subscriber_id: &str, expires: i64) -> String {
+    let signature = hash_ops::sign(
+        &format!("subscribe:{}:{}", subscriber_id, expires),
+        signing_secret(),
+    );
+    format!(
+        "/newsletter/confirm/{}?expires={}&signature={}",
+        subscriber_id, expires, signature
+    )
+}
+
+pub fn verify_subscription(subscriber_id: &str, expires: i64, signature: &str) -> bool {
+    expires > date_ops::to_timestamp()
+        && hash_ops::verify_signature(
+            &format!("subscribe:{}:{}", subscriber_id, expires),
+            signature,
+            signing_secret(),
+        )
+}
+
+/// path that ends a newsletter subscription; it never expires, so mail sent without
+/// SESSION_KEY set carries links that stop working on restart
+pub fn unsubscribe_path(subscriber_id: &str) -> String {
+    let signature = hash_ops::sign(&format!("unsubscribe:{}", subscriber_id), signing_secret());
+    format!(
+        "/newsletter/unsubscribe/{}?signature={}",
+        subscriber_id, signature
+    )
+}
+
+pub fn verify_unsubscribe(subscriber_id: &str, signature: &str) -> bool {
+    hash_ops::verify_signature(
+        &format!("unsubscribe:{}", subscriber_id),
+        signature,
+        signing_secret(),
+    )
+}
+
+async fn find_account(req: &ServiceRequest, session: &Session) -> Option<AccountEntity> {
+    let account_id = session.get::<String>(SESSION_ACCOUNT_KEY).ok().flatten()?;
+    let mongoc = req.app_data::<web::Data<Client>>()?;
+
+    let collection = Database::get_collection(mongoc, "accounts");
+    match Database::find::<AccountEntity>(collection, account_id).await {
+        EntityResult::Success(r) => Some(r),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => None,
+        EntityResult::Error(e) => {
+            error!("Failed to find account: {:?}", e);
+            None
+        }
+    }
+}
+
+/// guards the admin and upload routes: requires a signed in account, checks the csrf header
+/// on every unsafe method and hands the account to the handlers as request data
+pub async fn require_login(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    // the router matches the percent-decoded path, so /%61dmin reaches the admin routes too
+    if !is_protected(req.match_info().as_str()) {
+        return next.call(req).await.map(|res| res.map_into_boxed_body());
+    }
+
+    let session = req.get_session();
+
+    let account = match find_account(&req, &session).await {
+        Some(account) => account,
+        None => {
+            let response = if req.method().is_safe() {
+                HttpResponse::SeeOther()
+                    .insert_header((
+                        header::LOCATION,
+                        format!("/admin/login?next={}", req.path()),
+                    ))
+                    .finish()
+            } else {
+                HttpResponse::Unauthorized().body("Sign in required")
+            };
+            return Ok(req.into_response(response));
+        }
+    };
+
+    if !req.method().is_safe() {
+        let expected = session.get::<String>(SESSION_CSRF_KEY).ok().flatten();
+        let given = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if expected.is_none() || expected.as_deref() != given {
+            return Ok(req.into_response(HttpResponse::Forbidden().body("Invalid CSRF token")));
+        }
+    }
+
+    req.extensions_mut().insert(account);
+    next.call(req).await.map(|res| res.map_into_boxed_body())
+}
+
+/// creates the editor account from ADMIN_EMAIL and ADMIN_PASSWORD when it does not exist yet,
+/// so a fresh install can sign in and add the other accounts
+pub async fn bootstrap_editor(mongoc: &Client) {
+    let (email, password) = match (env::var("ADMIN_EMAIL"), env::var("ADMIN_PASSWORD")) {
+        (Ok(email), Ok(password)) => (email.trim().to_lowercase(), password),
+        _ => return,
+    };
+
+    let collection = Database::get_collection::<AccountEntity>(mongoc, "accounts");
+    match Database::find_by::<AccountEntity, String>(
+        collection.clone(),
+        String::from("email"),
+        email.clone(),
+    )
+    .await
+    {
+        EntityResult::Success(_) => return,
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {}
+        EntityResult::Error(e) => {
+            error!("Failed to look up editor account: {:?}", e);
+            return;
+        }
+    }
+
+    let password_hash = match hash_ops::cook_hash(&password) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to hash editor password: {:?}", e);
+            return;
+        }
+    };
+
+    let account = AccountEntity {
+        _id: None,
+        email,
+        password_hash,
+        role: AccountRole::Editor,
+        author: None,
+    };
+
+    match Database::create(collection, account).await {
+        EntityResult::Success(r) => info!("Editor account created {:?}", r),
+        EntityResult::Error(e) => error!("Failed to create editor account: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{cookie::Key, http::StatusCode, middleware, test, App};
+
+    use super::*;
+
+    async fn reached() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn percent_encoded_paths_require_login() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(require_login))
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .route("/admin/posts", web::get().to(reached))
+                .route("/api/photos", web::post().to(reached)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/%61dmin/posts").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+        let req = test::TestRequest::post().uri("/api/%70hotos").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+}
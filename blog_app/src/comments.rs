@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use log::error;
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::{FindOptions, IndexOptions},
+    Client, IndexModel,
+};
+use serde::Deserialize;
+
+use crate::{
+    entities::{
+        blogs::PostEntity,
+        comments::{CommentEntity, CommentStatus},
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+    },
+    models::{CommentRequestModel, CommentResponseModel},
+    utils::{date_ops, db_ops::Database, hash_ops},
+};
+
+/// comments one client may leave within the window before it has to wait
+const RATE_LIMIT_COMMENTS: u64 = 3;
+const RATE_LIMIT_WINDOW_MINUTES: i64 = 10;
+
+#[derive(Debug)]
+pub enum CommentError {
+    /// the post or the comment replied to cannot take comments
+    NotFound(String),
+    RateLimited,
+    Database(DatabaseErrorType),
+}
+
+#[derive(Debug, Deserialize)]
+struct CommentCount {
+    #[serde(rename = "_id")]
+    post_id: String,
+    count: u64,
+}
+
+/// comments are read by post and status, rate limits by client and date
+pub async fn init(mongoc: &Client) {
+    let collection = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    let indexes = [
+        IndexModel::builder()
+            .keys(doc! { "post_id": 1, "status": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("comment_post".to_string())
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "client_hash": 1, "created_date": -1 })
+            .options(
+                IndexOptions::builder()
+                    .name("comment_client".to_string())
+                    .build(),
+            )
+            .build(),
+    ];
+    for index in indexes {
+        if let EntityResult::Error(e) = Database::create_index(collection.clone(), index).await {
+            error!("Failed to create a comment index: {:?}", e);
+        }
+    }
+}
+
+/// the client address is hashed before it is stored or compared
+pub fn client_hash(address: &str) -> String {
+    hash_ops::string_hasher(address)
+}
+
+async fn find_published_post(mongoc: &Client, post_id: &str) -> Result<(), CommentError> {
+    let object_id = ObjectId::parse_str(post_id)
+        .map_err(|_| CommentError::NotFound(String::from("Post not found")))?;
+    let mut filter = PostEntity::published();
+    filter.insert("_id", object_id);
+
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    match Database::count(collection, filter).await {
+        EntityResult::Success(0) => Err(CommentError::NotFound(String::from("Post not found"))),
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(CommentError::Database(e)),
+    }
+}
+
+/// replies go to approved comments of the same post only
+async fn find_parent(mongoc: &Client, post_id: &str, parent_id: &str) -> Result<(), CommentError> {
+    let object_id = ObjectId::parse_str(parent_id)
+        .map_err(|_| CommentError::NotFound(String::from("Comment not found")))?;
+    let filter = doc! {
+        "_id": object_id,
+        "post_id": post_id,
+        "status": bson::to_bson(&CommentStatus::Approved).unwrap(),
+    };
+
+    let collection = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    match Database::count(collection, filter).await {
+        EntityResult::Success(0) => Err(CommentError::NotFound(String::from("Comment not found"))),
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(CommentError::Database(e)),
+    }
+}
+
+async fn check_rate_limit(mongoc: &Client, client_hash: &str) -> Result<(), CommentError> {
+    let since = date_ops::local_date() - chrono::Duration::minutes(RATE_LIMIT_WINDOW_MINUTES);
+    let filter = doc! {
+        "client_hash": client_hash,
+        "created_date": { "$gt": bson::to_bson(&since).unwrap() },
+    };
+
+    let collection = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    match Database::count(collection, filter).await {
+        EntityResult::Success(count) if count >= RATE_LIMIT_COMMENTS => {
+            Err(CommentError::RateLimited)
+        }
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(CommentError::Database(e)),
+    }
+}
+
+/// queues a comment for moderation; a filled in honeypot is accepted and thrown away so bots
+/// do not learn what gave them away
+pub async fn submit(
+    mongoc: &Client,
+    model: &CommentRequestModel,
+    client_hash: String,
+) -> Result<(), CommentError> {
+    if !model.website.is_empty() {
+        return Ok(());
+    }
+
+    find_published_post(mongoc, &model.post_id).await?;
+    if let Some(parent_id) = &model.parent_id {
+        find_parent(mongoc, &model.post_id, parent_id).await?;
+    }
+    check_rate_limit(mongoc, &client_hash).await?;
+
+    let collection = Database::get_collection(mongoc, "comments");
+    match Database::create(collection, model.to(client_hash)).await {
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(CommentError::Database(e)),
+    }
+}
+
+/// the approved comments of a post as threads, with how many there are
+pub async fn find_thread(
+    mongoc: &Client,
+    post_id: &str,
+) -> EntityResult<(Vec<CommentResponseModel>, usize)> {
+    let filter = doc! {
+        "post_id": post_id,
+        "status": bson::to_bson(&CommentStatus::Approved).unwrap(),
+    };
+    let options = FindOptions::builder()
+        .sort(doc! { "created_date": 1 })
+        .build();
+
+    let collection = Database::get_collection(mongoc, "comments");
+    match Database::find_many::<CommentEntity>(collection, filter, options).await {
+        EntityResult::Success(r) => {
+            EntityResult::Success((CommentResponseModel::thread(&r, None), r.len()))
+        }
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+/// approved comments per post id
+pub async fn count_approved(mongoc: &Client) -> EntityResult<HashMap<String, u64>> {
+    let pipeline = vec![
+        doc! { "$match": { "status": bson::to_bson(&CommentStatus::Approved).unwrap() } },
+        doc! { "$group": { "_id": "$post_id", "count": { "$sum": 1 } } },
+    ];
+
+    let collection = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    match Database::aggregate::<CommentEntity, CommentCount>(collection, pipeline).await {
+        EntityResult::Success(r) => EntityResult::Success(
+            r.into_iter()
+                .map(|count| (count.post_id, count.count))
+                .collect(),
+        ),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+pub async fn set_status(
+    mongoc: &Client,
+    comment_id: &str,
+    status: CommentStatus,
+) -> EntityResult<SuccessResultType> {
+    let object_id = match ObjectId::parse_str(comment_id) {
+        Ok(r) => r,
+        Err(_) => {
+            return EntityResult::Error(DatabaseErrorType::NotFound(
+                String::from("Invalid comment id"),
+                comment_id.to_string(),
+            ))
+        }
+    };
+
+    let collection = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    Database::update_many(
+        collection,
+        doc! { "_id": object_id },
+        doc! { "$set": { "status": bson::to_bson(&status).unwrap() } },
+    )
+    .await
+}
@@ -0,0 +1,286 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDateTime};
+use log::{error, info};
+use mongodb::{bson::doc, options::FindOptions, Client};
+
+use crate::{
+    entities::{
+        blogs::PostEntity,
+        contents::ContentEntity,
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+        revisions::RevisionEntity,
+    },
+    utils::{date_ops, db_ops::Database, environ::Environ, file_ops, hash_ops},
+};
+
+/// where the filesystem backend keeps post bodies, as {key}.md
+pub const MARKDOWNS_DIR: &str = "./assets/markdowns";
+
+#[derive(Debug, Clone)]
+pub struct ContentInfo {
+    pub key: String,
+    pub bytes: u64,
+    pub created_date: NaiveDateTime,
+}
+
+/// storage for the markdown bodies of posts; keys are the sha256 of the markdown, so saving
+/// the same body twice stores it once (bodies uploaded before that keep their random keys)
+#[async_trait]
+pub trait ContentStore: Send + Sync {
+    async fn read(&self, key: &str) -> EntityResult<String>;
+
+    /// stores the markdown and returns the key to refer to it by
+    async fn write(&self, markdown: &str) -> EntityResult<String>;
+
+    async fn delete(&self, key: &str) -> EntityResult<SuccessResultType>;
+
+    /// every stored body without its markdown
+    async fn list(&self) -> EntityResult<Vec<ContentInfo>>;
+}
+
+/// keys are hex hashes or, for old uploads, generated alphanumerics; anything else could
+/// escape the markdowns folder
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn not_found(key: &str) -> DatabaseErrorType {
+    DatabaseErrorType::NotFound(
+        format!("Error finding content {}", key),
+        "Content not found".to_string(),
+    )
+}
+
+/// bodies in the contents collection, the default so every instance sees the same posts
+pub struct DatabaseContentStore {
+    mongoc: Client,
+}
+
+impl DatabaseContentStore {
+    pub fn new(mongoc: Client) -> Self {
+        DatabaseContentStore { mongoc }
+    }
+}
+
+#[async_trait]
+impl ContentStore for DatabaseContentStore {
+    async fn read(&self, key: &str) -> EntityResult<String> {
+        let collection = Database::get_collection(&self.mongoc, "contents");
+        match Database::find_one::<ContentEntity>(collection, doc! { "_id": key }).await {
+            EntityResult::Success(r) => EntityResult::Success(r.markdown),
+            EntityResult::Error(e) => EntityResult::Error(e),
+        }
+    }
+
+    async fn write(&self, markdown: &str) -> EntityResult<String> {
+        let key = hash_ops::string_hasher(markdown);
+
+        let collection = Database::get_collection::<ContentEntity>(&self.mongoc, "contents");
+        match Database::count(collection.clone(), doc! { "_id": &key }).await {
+            EntityResult::Success(0) => {}
+            EntityResult::Success(_) => return EntityResult::Success(key),
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        }
+
+        let content = ContentEntity {
+            _id: key.clone(),
+            markdown: markdown.to_string(),
+            bytes: markdown.len() as u64,
+            created_date: date_ops::local_date(),
+        };
+        match Database::create(collection, content).await {
+            EntityResult::Success(_) => EntityResult::Success(key),
+            EntityResult::Error(e) => EntityResult::Error(e),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> EntityResult<SuccessResultType> {
+        let collection = Database::get_collection::<ContentEntity>(&self.mongoc, "contents");
+        Database::delete_one(collection, doc! { "_id": key }).await
+    }
+
+    async fn list(&self) -> EntityResult<Vec<ContentInfo>> {
+        let collection = Database::get_collection(&self.mongoc, "contents");
+        match Database::find_many::<ContentEntity>(
+            collection,
+            doc! {},
+            FindOptions::builder()
+                .projection(doc! { "markdown": 0 })
+                .build(),
+        )
+        .await
+        {
+            EntityResult::Success(r) => EntityResult::Success(
+                r.into_iter()
+                    .map(|content| ContentInfo {
+                        key: content._id,
+                        bytes: content.bytes,
+                        created_date: content.created_date,
+                    })
+                    .collect(),
+            ),
+            EntityResult::Error(e) => EntityResult::Error(e),
+        }
+    }
+}
+
+/// bodies as files in the markdowns folder, handy for local development
+pub struct FilesystemContentStore;
+
+impl FilesystemContentStore {
+    fn path(key: &str) -> String {
+        format!("{}/{}.md", MARKDOWNS_DIR, key)
+    }
+}
+
+#[async_trait]
+impl ContentStore for FilesystemContentStore {
+    async fn read(&self, key: &str) -> EntityResult<String> {
+        if !is_valid_key(key) {
+            return EntityResult::Error(not_found(key));
+        }
+        match file_ops::read_file(&Self::path(key)) {
+            Ok(r) => EntityResult::Success(r),
+            Err(_) => EntityResult::Error(not_found(key)),
+        }
+    }
+
+    async fn write(&self, markdown: &str) -> EntityResult<String> {
+        let key = hash_ops::string_hasher(markdown);
+        match file_ops::write_file(&Self::path(&key), markdown) {
+            Ok(_) => EntityResult::Success(key),
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error writing content {}", key),
+                e,
+            )),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> EntityResult<SuccessResultType> {
+        if !is_valid_key(key) {
+            return EntityResult::Error(not_found(key));
+        }
+        match fs::remove_file(Self::path(key)) {
+            Ok(_) => EntityResult::Success(SuccessResultType::Deleted(key.to_string())),
+            Err(_) => EntityResult::Error(not_found(key)),
+        }
+    }
+
+    async fn list(&self) -> EntityResult<Vec<ContentInfo>> {
+        // a fresh checkout may not have the folder yet
+        let entries = match fs::read_dir(MARKDOWNS_DIR) {
+            Ok(r) => r,
+            Err(_) => return EntityResult::Success(vec![]),
+        };
+
+        let contents = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let key = path.file_stem()?.to_str()?.to_string();
+                let metadata = entry.metadata().ok()?;
+                if path.extension()? != "md" || !metadata.is_file() || !is_valid_key(&key) {
+                    return None;
+                }
+                Some(ContentInfo {
+                    key,
+                    bytes: metadata.len(),
+                    created_date: metadata
+                        .modified()
+                        .map(|modified| DateTime::<Local>::from(modified).naive_local())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect();
+        EntityResult::Success(contents)
+    }
+}
+
+/// the backend picked by CONTENT_STORE
+pub fn from_environ(mongoc: &Client) -> Arc<dyn ContentStore> {
+    match Environ::default().content_store.as_str() {
+        "filesystem" => Arc::new(FilesystemContentStore),
+        _ => Arc::new(DatabaseContentStore::new(mongoc.clone())),
+    }
+}
+
+/// imports the markdown files of the filesystem backend into the database backend and points
+/// posts and revisions at the new keys; the files are only removed once everything is imported
+pub async fn migrate_to_database(mongoc: &Client, dry_run: bool, remove_files: bool) {
+    let files = FilesystemContentStore;
+    let database = DatabaseContentStore::new(mongoc.clone());
+
+    let contents = match files.list().await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to list markdown files: {:?}", e);
+            return;
+        }
+    };
+
+    let mut keys = HashMap::new();
+    for content in &contents {
+        let markdown = match files.read(&content.key).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to read {}: {:?}", content.key, e);
+                return;
+            }
+        };
+        if dry_run {
+            info!("Would import {} ({} bytes)", content.key, content.bytes);
+            continue;
+        }
+        match database.write(&markdown).await {
+            EntityResult::Success(key) => {
+                keys.insert(content.key.clone(), key);
+            }
+            EntityResult::Error(e) => {
+                error!("Failed to import {}: {:?}", content.key, e);
+                return;
+            }
+        }
+    }
+
+    for (old_key, new_key) in &keys {
+        let posts = Database::get_collection::<PostEntity>(mongoc, "posts");
+        let revisions = Database::get_collection::<RevisionEntity>(mongoc, "revisions");
+        let updates = [
+            Database::update_many(
+                posts,
+                doc! { "body": old_key },
+                doc! { "$set": { "body": new_key } },
+            )
+            .await,
+            Database::update_many(
+                revisions,
+                doc! { "post.body": old_key },
+                doc! { "$set": { "post.body": new_key } },
+            )
+            .await,
+        ];
+        for update in updates {
+            if let EntityResult::Error(e) = update {
+                error!("Failed to point posts at {}: {:?}", new_key, e);
+                return;
+            }
+        }
+        info!("Imported {} as {}", old_key, new_key);
+    }
+
+    if remove_files && !dry_run {
+        for old_key in keys.keys() {
+            if let EntityResult::Error(e) = files.delete(old_key).await {
+                error!("Failed to remove {}: {:?}", old_key, e);
+            }
+        }
+    }
+
+    info!(
+        "{} of {} markdown files imported",
+        keys.len(),
+        contents.len()
+    );
+}
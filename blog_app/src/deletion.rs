@@ -0,0 +1,414 @@
+use log::{error, info};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Document},
+    options::FindOptions,
+    Client,
+};
+
+use crate::{
+    content::ContentStore,
+    entities::{
+        accounts::AccountEntity,
+        blogs::{PostEntity, PostStatus, SeriesEntity},
+        comments::CommentEntity,
+        redirects::RedirectEntity,
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+        revisions::RevisionEntity,
+    },
+    models::{BulkAction, BulkFailureModel, BulkRequestModel, BulkResponseModel},
+    search::{self, SearchIndex},
+    utils::{date_ops, db_ops::Database},
+};
+
+#[derive(Debug)]
+pub enum DeleteError {
+    NotFound,
+    Forbidden(String),
+    /// posts are archived before they can be deleted for good
+    NotArchived,
+    /// only archived posts are restored, anything else is left as it is
+    NotRestorable,
+    /// still used by posts or accounts, and nothing given to take them over
+    InUse(String),
+    /// the author or tag to take the posts over is unknown or one of those deleted
+    InvalidReplacement,
+    Database(DatabaseErrorType),
+}
+
+impl DeleteError {
+    /// what the list page shows next to the item that failed
+    pub fn reason(&self) -> String {
+        match self {
+            DeleteError::NotFound => String::from("Not found"),
+            DeleteError::Forbidden(reason) => reason.clone(),
+            DeleteError::NotArchived => String::from("Archive the post before deleting it"),
+            DeleteError::NotRestorable => String::from("Only archived posts can be restored"),
+            DeleteError::InUse(reason) => reason.clone(),
+            DeleteError::InvalidReplacement => String::from("The replacement cannot be used"),
+            DeleteError::Database(_) => String::from("Database error"),
+        }
+    }
+}
+
+/// authors and tags are referenced from lists on posts
+#[derive(Debug, Clone, Copy)]
+pub enum PostReference {
+    Author,
+    Tag,
+}
+
+impl PostReference {
+    /// the collection, named like the list on PostEntity that holds the ids
+    fn collection(&self) -> &'static str {
+        match self {
+            PostReference::Author => "authors",
+            PostReference::Tag => "tags",
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<ObjectId, DeleteError> {
+    ObjectId::parse_str(id).map_err(|_| DeleteError::NotFound)
+}
+
+async fn find_post(mongoc: &Client, post_id: &str) -> Result<PostEntity, DeleteError> {
+    parse_id(post_id)?;
+    let collection = Database::get_collection(mongoc, "posts");
+    match Database::find::<PostEntity>(collection, post_id.to_string()).await {
+        EntityResult::Success(r) => Ok(r),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => Err(DeleteError::NotFound),
+        EntityResult::Error(e) => Err(DeleteError::Database(e)),
+    }
+}
+
+/// archives a post, taking it offline, or brings an archived one back as a draft
+pub async fn set_post_archived(
+    mongoc: &Client,
+    search: &dyn SearchIndex,
+    content: &dyn ContentStore,
+    account: &AccountEntity,
+    post_id: &str,
+    archived: bool,
+) -> Result<(), DeleteError> {
+    let mut post = find_post(mongoc, post_id).await?;
+    if !account.can_edit_post(&post) {
+        return Err(DeleteError::Forbidden(String::from(
+            "You can only archive your own posts",
+        )));
+    }
+
+    // restoring a published or scheduled post would quietly take it offline
+    let mut filter = doc! { "_id": post._id };
+    if !archived {
+        if post.status != PostStatus::Archived {
+            return Err(DeleteError::NotRestorable);
+        }
+        filter.insert("status", bson::to_bson(&PostStatus::Archived).unwrap());
+    }
+
+    post.status = match archived {
+        true => PostStatus::Archived,
+        false => PostStatus::Draft,
+    };
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    match Database::update_many(
+        collection,
+        filter,
+        doc! { "$set": { "status": bson::to_bson(&post.status).unwrap() } },
+    )
+    .await
+    {
+        // published again in the meantime
+        EntityResult::Success(SuccessResultType::Updated(count)) if !archived && count == "0" => {
+            return Err(DeleteError::NotRestorable);
+        }
+        EntityResult::Success(_) => {}
+        EntityResult::Error(e) => return Err(DeleteError::Database(e)),
+    }
+
+    // the search index keeps a copy of the status to leave unpublished posts out
+    if let EntityResult::Error(e) = search::index_post(search, content, post_id, &post).await {
+        error!("Failed to index post {}: {:?}", post_id, e);
+    }
+    Ok(())
+}
+
+/// deletes an archived post with what only exists for it: its comments, revisions, redirects
+/// and search entry, and its place in a series. Markdown and images are left to gc-media
+pub async fn delete_post(
+    mongoc: &Client,
+    search: &dyn SearchIndex,
+    post_id: &str,
+) -> Result<(), DeleteError> {
+    let post = find_post(mongoc, post_id).await?;
+    if post.status != PostStatus::Archived {
+        return Err(DeleteError::NotArchived);
+    }
+
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    match Database::delete_one(collection, doc! { "_id": post._id }).await {
+        EntityResult::Success(r) => info!("Post deleted {:?}", r),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => return Err(DeleteError::NotFound),
+        EntityResult::Error(e) => return Err(DeleteError::Database(e)),
+    }
+
+    // the post is gone already, what is left behind is logged rather than reported
+    let series = Database::get_collection::<SeriesEntity>(mongoc, "series");
+    if let EntityResult::Error(e) = Database::update_many(
+        series,
+        doc! { "posts": post_id },
+        doc! { "$pull": { "posts": post_id } },
+    )
+    .await
+    {
+        error!("Failed to take post {} out of its series: {:?}", post_id, e);
+    }
+
+    let by_post = doc! { "post_id": post_id };
+    let comments = Database::get_collection::<CommentEntity>(mongoc, "comments");
+    if let EntityResult::Error(e) = Database::delete_many(comments, by_post.clone()).await {
+        error!("Failed to delete comments of post {}: {:?}", post_id, e);
+    }
+    let revisions = Database::get_collection::<RevisionEntity>(mongoc, "revisions");
+    if let EntityResult::Error(e) = Database::delete_many(revisions, by_post.clone()).await {
+        error!("Failed to delete revisions of post {}: {:?}", post_id, e);
+    }
+    let redirects = Database::get_collection::<RedirectEntity>(mongoc, "redirects");
+    if let EntityResult::Error(e) = Database::delete_many(redirects, by_post).await {
+        error!("Failed to delete redirects of post {}: {:?}", post_id, e);
+    }
+    match search.remove(post_id).await {
+        EntityResult::Success(_) | EntityResult::Error(DatabaseErrorType::NotFound(..)) => {}
+        EntityResult::Error(e) => error!("Failed to remove post {} from search: {:?}", post_id, e),
+    }
+    Ok(())
+}
+
+async fn count_matching(
+    mongoc: &Client,
+    collection: &str,
+    filter: Document,
+) -> Result<u64, DeleteError> {
+    let collection = Database::get_collection::<Document>(mongoc, collection);
+    match Database::count(collection, filter).await {
+        EntityResult::Success(r) => Ok(r),
+        EntityResult::Error(e) => Err(DeleteError::Database(e)),
+    }
+}
+
+/// archived authors and tags are left out of the pickers of the post form
+pub async fn set_reference_archived(
+    mongoc: &Client,
+    reference: PostReference,
+    id: &str,
+    archived: bool,
+) -> Result<(), DeleteError> {
+    let object_id = parse_id(id)?;
+    if count_matching(mongoc, reference.collection(), doc! { "_id": object_id }).await? == 0 {
+        return Err(DeleteError::NotFound);
+    }
+
+    let update = match archived {
+        true => {
+            doc! { "$set": { "archived_date": bson::to_bson(&date_ops::local_date()).unwrap() } }
+        }
+        false => doc! { "$unset": { "archived_date": "" } },
+    };
+    let collection = Database::get_collection::<Document>(mongoc, reference.collection());
+    match Database::update_many(collection, doc! { "_id": object_id }, update).await {
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(DeleteError::Database(e)),
+    }
+}
+
+/// swaps one id for another in the list on every post that has it, keeping its place in the
+/// list and dropping it where the post had both already
+async fn reassign_posts(
+    mongoc: &Client,
+    reference: PostReference,
+    id: &str,
+    replacement: &str,
+) -> Result<Vec<PostEntity>, DeleteError> {
+    let field = reference.collection();
+    let filter = doc! { field: id };
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let post_ids = match Database::find_many::<PostEntity>(
+        collection.clone(),
+        filter.clone(),
+        FindOptions::default(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r
+            .into_iter()
+            .filter_map(|post| post._id)
+            .collect::<Vec<_>>(),
+        EntityResult::Error(e) => return Err(DeleteError::Database(e)),
+    };
+
+    let list = format!("${}", field);
+    let pipeline = vec![
+        doc! { "$set": { field: { "$map": {
+            "input": &list,
+            "in": { "$cond": [{ "$eq": ["$$this", id] }, replacement, "$$this"] },
+        } } } },
+        doc! { "$set": { field: { "$reduce": {
+            "input": &list,
+            "initialValue": [],
+            "in": { "$cond": [
+                { "$in": ["$$this", "$$value"] },
+                "$$value",
+                { "$concatArrays": ["$$value", ["$$this"]] },
+            ] },
+        } } } },
+    ];
+    if let EntityResult::Error(e) =
+        Database::update_many(collection.clone(), filter, pipeline).await
+    {
+        return Err(DeleteError::Database(e));
+    }
+
+    match Database::find_many::<PostEntity>(
+        collection,
+        doc! { "_id": { "$in": post_ids } },
+        FindOptions::default(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => Ok(r),
+        EntityResult::Error(e) => Err(DeleteError::Database(e)),
+    }
+}
+
+/// deletes an author or tag; one still in use is refused unless a replacement is given, which
+/// then takes over its posts, and for authors the accounts writing as it
+pub async fn delete_reference(
+    mongoc: &Client,
+    search: &dyn SearchIndex,
+    content: &dyn ContentStore,
+    reference: PostReference,
+    id: &str,
+    replacement: Option<&str>,
+) -> Result<(), DeleteError> {
+    let object_id = parse_id(id)?;
+    if count_matching(mongoc, reference.collection(), doc! { "_id": object_id }).await? == 0 {
+        return Err(DeleteError::NotFound);
+    }
+
+    let posts = count_matching(mongoc, "posts", doc! { reference.collection(): id }).await?;
+    let accounts = match reference {
+        PostReference::Author => count_matching(mongoc, "accounts", doc! { "author": id }).await?,
+        PostReference::Tag => 0,
+    };
+
+    match replacement {
+        None if posts > 0 || accounts > 0 => {
+            return Err(DeleteError::InUse(format!(
+                "Used by {} posts and {} accounts, pick who takes them over",
+                posts, accounts
+            )));
+        }
+        None => {}
+        Some(replacement) => {
+            let replacement_id =
+                ObjectId::parse_str(replacement).map_err(|_| DeleteError::InvalidReplacement)?;
+            if replacement == id
+                || count_matching(
+                    mongoc,
+                    reference.collection(),
+                    doc! { "_id": replacement_id },
+                )
+                .await?
+                    == 0
+            {
+                return Err(DeleteError::InvalidReplacement);
+            }
+
+            for post in reassign_posts(mongoc, reference, id, replacement).await? {
+                let post_id = post._id.unwrap().to_hex();
+                if let EntityResult::Error(e) =
+                    search::index_post(search, content, &post_id, &post).await
+                {
+                    error!("Failed to index post {}: {:?}", post_id, e);
+                }
+            }
+            if let PostReference::Author = reference {
+                let collection = Database::get_collection::<AccountEntity>(mongoc, "accounts");
+                if let EntityResult::Error(e) = Database::update_many(
+                    collection,
+                    doc! { "author": id },
+                    doc! { "$set": { "author": replacement } },
+                )
+                .await
+                {
+                    return Err(DeleteError::Database(e));
+                }
+            }
+        }
+    }
+
+    let collection = Database::get_collection::<Document>(mongoc, reference.collection());
+    match Database::delete_one(collection, doc! { "_id": object_id }).await {
+        EntityResult::Success(r) => {
+            info!("Deleted from {} {:?}", reference.collection(), r);
+            Ok(())
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => Err(DeleteError::NotFound),
+        EntityResult::Error(e) => Err(DeleteError::Database(e)),
+    }
+}
+
+/// applies a list page action to each author or tag, reporting the ones that failed
+pub async fn apply_to_references(
+    mongoc: &Client,
+    search: &dyn SearchIndex,
+    content: &dyn ContentStore,
+    reference: PostReference,
+    model: &BulkRequestModel,
+) -> BulkResponseModel {
+    let mut response = BulkResponseModel::default();
+    for id in &model.ids {
+        let result = match model.action {
+            BulkAction::Archive => set_reference_archived(mongoc, reference, id, true).await,
+            BulkAction::Restore => set_reference_archived(mongoc, reference, id, false).await,
+            // a replacement deleted in the same go would leave the posts pointing nowhere
+            BulkAction::Delete
+                if model
+                    .reassign_to
+                    .as_ref()
+                    .is_some_and(|replacement| model.ids.contains(replacement)) =>
+            {
+                Err(DeleteError::InvalidReplacement)
+            }
+            BulkAction::Delete => {
+                delete_reference(
+                    mongoc,
+                    search,
+                    content,
+                    reference,
+                    id,
+                    model.reassign_to.as_deref(),
+                )
+                .await
+            }
+        };
+        match result {
+            Ok(()) => response.done += 1,
+            Err(e) => {
+                if let DeleteError::Database(e) = &e {
+                    error!(
+                        "Failed to update {} {}: {:?}",
+                        reference.collection(),
+                        id,
+                        e
+                    );
+                }
+                response.failed.push(BulkFailureModel {
+                    id: id.clone(),
+                    reason: e.reason(),
+                });
+            }
+        }
+    }
+    response
+}
@@ -0,0 +1,58 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::blogs::PostEntity;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountRole {
+    /// writes and edits their own posts and author profile
+    Author,
+    /// edits every post, author and tag, and manages accounts
+    Editor,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccountEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub email: String,
+    pub password_hash: String,
+    pub role: AccountRole,
+    /// id of the AuthorEntity this account writes as
+    pub author: Option<String>,
+}
+
+impl AccountEntity {
+    pub fn is_editor(&self) -> bool {
+        self.role == AccountRole::Editor
+    }
+
+    /// editors act as any author, an author only as the AuthorEntity tied to the account
+    pub fn can_act_as(&self, author_id: &str) -> bool {
+        self.is_editor() || self.author.as_deref() == Some(author_id)
+    }
+
+    /// a post may be saved by anyone who can act as one of its authors, co-authors included
+    pub fn can_write_as(&self, authors: &[String]) -> bool {
+        authors.iter().any(|author| self.can_act_as(author))
+    }
+
+    pub fn can_edit_post(&self, post: &PostEntity) -> bool {
+        self.can_write_as(&post.authors)
+    }
+
+    /// only editors and the main author may drop authors from a post or put another author
+    /// first; co-authors keep everyone already on it, the main author still leading
+    pub fn can_change_authors(&self, post: &PostEntity, authors: &[String]) -> bool {
+        let main_author = match post.authors.first() {
+            Some(r) => r,
+            None => return self.is_editor(),
+        };
+        if self.can_act_as(main_author) {
+            return true;
+        }
+        authors.first() == Some(main_author)
+            && post.authors.iter().all(|author| authors.contains(author))
+    }
+}
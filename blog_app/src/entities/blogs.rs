@@ -1,9 +1,9 @@
 use chrono::NaiveDateTime;
-use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
 
 use serde::{Deserialize, Serialize};
 
-use crate::utils::{date_ops, image_ops::ImagePath};
+use crate::utils::{date_ops, image_ops::ImagePath, string_ops};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TagEntity {
@@ -11,6 +11,9 @@ pub struct TagEntity {
     pub _id: Option<ObjectId>,
     pub name: String,
     pub description: String,
+    /// archived tags stay on the posts that have them but cannot be picked for others
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_date: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +26,32 @@ pub struct AuthorEntity {
     pub bio: String,
     pub photo_url: ImagePath,
     pub intro: String,
+    /// archived authors stay on the posts they wrote but cannot be picked for others
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_date: Option<NaiveDateTime>,
+}
+
+/// a multi-part series, its posts in reading order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeriesEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub name: String,
+    pub description: String,
+    /// ids of the PostEntity records, part one first
+    pub posts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PostStatus {
+    Draft,
+    /// goes live on its own once the publish date passes
+    Scheduled,
+    /// posts saved before statuses existed were all live
+    #[default]
+    Published,
+    Archived,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,8 +68,14 @@ pub struct PostEntity {
     pub publish_date: NaiveDateTime,
     pub modified_date: NaiveDateTime,
     pub hero_image: ImagePath,
-    pub author: String,
-    pub tag: String,
+    /// ids of the AuthorEntity records that wrote the post, the first is the main author
+    pub authors: Vec<String>,
+    /// ids of the TagEntity records of the post, the first is the main tag
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub featured: bool,
+    #[serde(default)]
+    pub status: PostStatus,
 }
 
 impl TagEntity {
@@ -49,10 +84,22 @@ impl TagEntity {
             _id: None,
             name: "not-set".to_string(),
             description: "not-set".to_string(),
+            archived_date: None,
         }
     }
 }
 
+impl TagEntity {
+    pub fn slug(&self) -> String {
+        string_ops::slugify(&self.name)
+    }
+
+    /// relative url of the tag archive
+    pub fn url(&self) -> String {
+        format!("/tags/{}", self.slug())
+    }
+}
+
 impl Default for TagEntity {
     fn default() -> Self {
         Self::new()
@@ -69,10 +116,22 @@ impl AuthorEntity {
             photo_url: ImagePath::new("not-set".to_string(), "not-set".to_string()),
             intro: "not-set".to_string(),
             last_name: "not-set".to_string(),
+            archived_date: None,
         }
     }
 }
 
+impl AuthorEntity {
+    pub fn slug(&self) -> String {
+        string_ops::slugify(format!("{} {}", self.first_name, self.last_name).as_str())
+    }
+
+    /// relative url of the author archive, the id keeps it stable across name changes
+    pub fn url(&self) -> String {
+        format!("/authors/{}/{}", self._id.unwrap(), self.slug())
+    }
+}
+
 impl Default for AuthorEntity {
     fn default() -> Self {
         Self::new()
@@ -93,8 +152,32 @@ impl PostEntity {
             publish_date: date_ops::local_date(),
             modified_date: date_ops::local_date(),
             hero_image: ImagePath::new("not-set".to_string(), "not-set".to_string()),
-            author: "not-set".to_string(),
-            tag: "not-set".to_string(),
+            authors: vec![],
+            tags: vec![],
+            featured: false,
+            status: PostStatus::Draft,
+        }
+    }
+
+    /// filter for posts the public can read: scheduled or published, with the publish date passed
+    pub fn published() -> Document {
+        doc! {
+            "status": { "$nin": ["draft", "archived"] },
+            "publish_date": { "$lte": bson::to_bson(&date_ops::local_date()).unwrap() },
+        }
+    }
+
+    /// the status readers see: scheduled and published posts differ only by the publish date
+    pub fn effective_status(&self) -> PostStatus {
+        match self.status {
+            PostStatus::Scheduled | PostStatus::Published => {
+                if self.publish_date > date_ops::local_date() {
+                    PostStatus::Scheduled
+                } else {
+                    PostStatus::Published
+                }
+            }
+            status => status,
         }
     }
 }
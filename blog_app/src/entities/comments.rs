@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentStatus {
+    /// waiting in the moderation queue, only editors see it
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// a reader comment on a post, or a reply to another comment of the same post
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommentEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    /// id of the PostEntity commented on
+    pub post_id: String,
+    /// id of the CommentEntity replied to, none for top level comments
+    pub parent_id: Option<String>,
+    pub name: String,
+    /// shown to editors only
+    pub email: String,
+    /// markdown, rendered and sanitised on display
+    pub body: String,
+    pub status: CommentStatus,
+    pub created_date: NaiveDateTime,
+    /// sha256 of the client address, enough to rate limit without keeping the address
+    pub client_hash: String,
+}
@@ -0,0 +1,14 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// a markdown post body, stored once per distinct content
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContentEntity {
+    /// sha256 of the markdown, which is also the key posts refer to it by
+    pub _id: String,
+    /// left out when listing bodies
+    #[serde(default)]
+    pub markdown: String,
+    pub bytes: u64,
+    pub created_date: NaiveDateTime,
+}
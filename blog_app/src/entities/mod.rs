@@ -1,2 +1,9 @@
+pub mod accounts;
 pub mod blogs;
+pub mod comments;
+pub mod contents;
+pub mod redirects;
 pub mod result_types;
+pub mod revisions;
+pub mod searches;
+pub mod subscribers;
@@ -0,0 +1,18 @@
+use chrono::NaiveDateTime;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// an old permalink under /blogs that answers with a 301; automatic redirects follow the post
+/// to wherever its permalink is now, manual ones go to a fixed url
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedirectEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    /// unique, never the permalink of a post
+    pub permalink: String,
+    /// id of the PostEntity that used the permalink, for automatic redirects
+    pub post_id: Option<String>,
+    /// where a manual redirect goes, a path on the site or an absolute url
+    pub target: Option<String>,
+    pub created_date: NaiveDateTime,
+}
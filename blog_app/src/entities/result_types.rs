@@ -4,6 +4,8 @@ pub enum DatabaseErrorType {
     ConnectionError(String, String),
     QueryError(String, String),
     MutationError(String, String),
+    /// a write broke a unique index
+    Duplicate(String, String),
 }
 
 #[derive(Debug)]
@@ -0,0 +1,25 @@
+use chrono::NaiveDateTime;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::blogs::PostEntity;
+
+/// a snapshot of a post taken on every save, so older versions can be compared and restored
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevisionEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    /// id of the PostEntity this is a revision of
+    pub post_id: String,
+    /// 1 for the first save, counting up
+    pub number: u64,
+    /// the post metadata as saved, including the key of its markdown file
+    pub post: PostEntity,
+    /// the markdown body at the time of the save, kept even if the file is replaced later
+    pub markdown: String,
+    /// email of the account that saved it
+    pub editor: String,
+    pub created_date: NaiveDateTime,
+    /// set when the save restored an earlier revision
+    pub restored_from: Option<u64>,
+}
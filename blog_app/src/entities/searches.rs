@@ -0,0 +1,24 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::blogs::PostStatus;
+
+/// the searchable text of a post, kept in step with the post on every save
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchEntity {
+    /// id of the PostEntity it indexes
+    pub _id: String,
+    pub title: String,
+    pub subtitle: String,
+    pub kicker: String,
+    pub tldr: String,
+    /// the markdown body as plain text
+    pub text: String,
+    pub tags: Vec<String>,
+    /// copied from the post so unpublished posts can be filtered out of results
+    pub status: PostStatus,
+    pub publish_date: NaiveDateTime,
+    /// relevance of a search result, only read back from queries
+    #[serde(default, skip_serializing)]
+    pub score: f64,
+}
@@ -0,0 +1,41 @@
+use chrono::NaiveDateTime;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberStatus {
+    /// signed up, waiting for the confirmation link to be opened
+    Pending,
+    Confirmed,
+    Unsubscribed,
+}
+
+/// a newsletter subscriber, kept after unsubscribing so a resubscribe needs a new opt-in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubscriberEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    /// lower cased, unique
+    pub email: String,
+    pub status: SubscriberStatus,
+    pub created_date: NaiveDateTime,
+    pub confirmed_date: Option<NaiveDateTime>,
+    pub unsubscribed_date: Option<NaiveDateTime>,
+    /// when the last confirmation link went out, another one waits for the cooldown
+    pub confirmation_sent_date: Option<NaiveDateTime>,
+    /// hash of the client address the last confirmation was asked from
+    pub client_hash: Option<String>,
+}
+
+/// a digest that went out, the next one starts after its date
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DigestEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub sent_date: NaiveDateTime,
+    /// ids of the PostEntity records it listed
+    pub posts: Vec<String>,
+    pub recipients: u64,
+    pub failures: u64,
+}
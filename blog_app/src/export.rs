@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use actix_web::{http::StatusCode, test, web, App};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::{options::FindOptions, Client};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    content::ContentStore,
+    entities::{
+        blogs::{AuthorEntity, PostEntity, TagEntity},
+        result_types::EntityResult,
+    },
+    pages,
+    utils::{
+        db_ops::Database, hash_ops, markdown_ops::MarkdownCache, reference_ops::ReferenceCache,
+        related_ops::RelatedCache,
+    },
+};
+
+/// remembers what each post page was rendered from, for incremental exports
+const MANIFEST_FILE: &str = ".export-manifest.json";
+/// folders of assets that are not public: markdown sources and admin form schemas
+const PRIVATE_ASSETS: [&str; 2] = ["markdowns", "schema"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// by post id
+    posts: HashMap<String, ExportedPost>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedPost {
+    path: String,
+    hash: String,
+}
+
+#[derive(Debug, Default)]
+struct ExportReport {
+    written: usize,
+    skipped: usize,
+    removed: usize,
+    failed: usize,
+}
+
+/// where the page of a url goes: files keep their name, pages become an index.html in a
+/// folder of their own so /blogs/some-post is served without an extension
+fn output_path(out_dir: &Path, url: &str) -> PathBuf {
+    let relative = url.trim_start_matches('/');
+    let last = relative.rsplit('/').next().unwrap_or_default();
+    match last.contains('.') {
+        true => out_dir.join(relative),
+        false => out_dir.join(relative).join("index.html"),
+    }
+}
+
+fn read_manifest(out_dir: &Path) -> Manifest {
+    fs::read_to_string(out_dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write_manifest(out_dir: &Path, manifest: &Manifest) -> io::Result<()> {
+    fs::write(
+        out_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(manifest).unwrap(),
+    )
+}
+
+/// copies the public assets, leaving files that are already there and as new alone
+fn copy_assets(source: &Path, target: &Path, top_level: bool) -> io::Result<usize> {
+    fs::create_dir_all(target)?;
+    let mut copied = 0;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if top_level && PRIVATE_ASSETS.iter().any(|private| name == *private) {
+            continue;
+        }
+
+        let from = entry.path();
+        let to = target.join(&name);
+        if from.is_dir() {
+            copied += copy_assets(&from, &to, false)?;
+            continue;
+        }
+
+        let source_meta = entry.metadata()?;
+        let up_to_date = fs::metadata(&to).is_ok_and(|target_meta| {
+            target_meta.len() == source_meta.len()
+                && match (target_meta.modified(), source_meta.modified()) {
+                    (Ok(target_time), Ok(source_time)) => target_time >= source_time,
+                    _ => false,
+                }
+        });
+        if !up_to_date {
+            fs::copy(&from, &to)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// what a post page depends on directly; related posts, series and comments are not part of
+/// it, a full export picks those up
+async fn post_hash(content: &dyn ContentStore, post: &PostEntity) -> String {
+    let markdown = match content.read(&post.body).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(_) => String::new(),
+    };
+    hash_ops::string_hasher(&format!(
+        "{}{}",
+        serde_json::to_string(post).unwrap(),
+        markdown
+    ))
+}
+
+/// renders the public blog into out_dir through the same handlers and templates the server
+/// uses; listings are exported as their first page since pagination relies on query strings.
+/// Without `full` only post pages whose post or markdown changed are rendered again
+pub async fn export_site(
+    mongoc: &Client,
+    handlebars: Handlebars<'static>,
+    content: web::Data<dyn ContentStore>,
+    out_dir: &Path,
+    full: bool,
+) {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(handlebars))
+            .app_data(web::Data::new(mongoc.clone()))
+            .app_data(web::Data::new(MarkdownCache::default()))
+            .app_data(web::Data::new(RelatedCache::default()))
+            .app_data(web::Data::new(ReferenceCache::default()))
+            .app_data(content.clone())
+            .configure(pages::configure_public),
+    )
+    .await;
+
+    let mut report = ExportReport::default();
+
+    // renders one url and writes it out, false when the handler did not answer with a page
+    let render = async |url: &str, report: &mut ExportReport| -> bool {
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(url).to_request()).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+        if status != StatusCode::OK {
+            error!("Failed to export {}: {}", url, status);
+            report.failed += 1;
+            return false;
+        }
+
+        let path = output_path(out_dir, url);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, &body));
+        match written {
+            Ok(()) => {
+                report.written += 1;
+                true
+            }
+            Err(e) => {
+                error!("Failed to write {:?}: {:?}", path, e);
+                report.failed += 1;
+                false
+            }
+        }
+    };
+
+    let posts_collection = Database::get_collection(mongoc, "posts");
+    let posts = match Database::find_many::<PostEntity>(
+        posts_collection,
+        PostEntity::published(),
+        FindOptions::default(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return;
+        }
+    };
+    let authors =
+        match Database::find_all::<AuthorEntity>(Database::get_collection(mongoc, "authors")).await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to find authors: {:?}", e);
+                return;
+            }
+        };
+    let tags = match Database::find_all::<TagEntity>(Database::get_collection(mongoc, "tags")).await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find tags: {:?}", e);
+            return;
+        }
+    };
+
+    // listings, feeds and archives change with any post, they are always rendered
+    let mut urls = vec![
+        String::from("/"),
+        String::from("/feed.xml"),
+        String::from("/rss.xml"),
+        String::from("/sitemap.xml"),
+        String::from("/robots.txt"),
+        String::from("/styles/highlight.css"),
+    ];
+    for tag in &tags {
+        urls.push(tag.url());
+        urls.push(format!("{}/feed.xml", tag.url()));
+        urls.push(format!("{}/rss.xml", tag.url()));
+    }
+    urls.extend(authors.iter().map(|author| author.url()));
+
+    for url in &urls {
+        render(url, &mut report).await;
+    }
+
+    let mut manifest = read_manifest(out_dir);
+    let mut exported = HashMap::new();
+    for post in &posts {
+        let post_id = post._id.unwrap().to_hex();
+        let url = format!("/blogs/{}", post.permalink);
+        let path = output_path(out_dir, &url)
+            .strip_prefix(out_dir)
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let hash = post_hash(content.get_ref(), post).await;
+
+        let unchanged = !full
+            && manifest
+                .posts
+                .get(&post_id)
+                .is_some_and(|previous| previous.hash == hash && previous.path == path)
+            && out_dir.join(&path).exists();
+        if unchanged {
+            report.skipped += 1;
+        } else if !render(&url, &mut report).await {
+            continue;
+        }
+        exported.insert(post_id, ExportedPost { path, hash });
+    }
+
+    // posts that were unpublished or renamed since the last export
+    for (post_id, previous) in manifest.posts.drain() {
+        let still_there = exported
+            .get(&post_id)
+            .is_some_and(|post| post.path == previous.path);
+        if still_there {
+            continue;
+        }
+        let path = out_dir.join(&previous.path);
+        if fs::remove_file(&path).is_ok() {
+            report.removed += 1;
+            if let Some(folder) = path.parent() {
+                fs::remove_dir(folder).ok();
+            }
+        }
+    }
+    manifest.posts = exported;
+
+    if let Err(e) = write_manifest(out_dir, &manifest) {
+        error!("Failed to write the export manifest: {:?}", e);
+    }
+
+    match copy_assets(Path::new("./assets"), &out_dir.join("assets"), true) {
+        Ok(copied) => info!("Copied {} asset files", copied),
+        Err(e) => {
+            error!("Failed to copy assets: {:?}", e);
+            report.failed += 1;
+        }
+    }
+
+    info!(
+        "Exported to {:?}: {} written, {} unchanged, {} removed, {} failed",
+        out_dir, report.written, report.skipped, report.removed, report.failed
+    );
+}
@@ -1,36 +1,139 @@
 #[macro_use]
 pub mod macros;
 pub mod api;
+pub mod auth;
+pub mod comments;
+pub mod content;
+pub mod deletion;
 pub mod entities;
+pub mod export;
+pub mod media;
+pub mod migrations;
 pub mod models;
+pub mod newsletter;
 pub mod pages;
+pub mod permalinks;
+pub mod search;
 pub mod utils;
 
 use actix_cors::Cors;
 use actix_files as fs;
-use actix_web::{http, middleware, web, App, HttpServer};
+use actix_session::{config::PersistentSession, storage::CookieSessionStore, SessionMiddleware};
+use actix_web::{
+    cookie::{time::Duration, Key},
+    http, middleware, web, App, HttpServer,
+};
 use api::{
+    comments::post_comment,
     files::post_markdown,
     photos::{post_photo, post_photos},
+    search::get_search_results,
 };
+use clap::{Parser, Subcommand};
+use content::ContentStore;
 use dotenv::from_filename;
-use handlebars::Handlebars;
+use handlebars::{html_escape, Context, Handlebars, Helper, HelperResult, Output, RenderContext};
 use mongodb::Client;
+use newsletter::Mailer;
 use pages::{
     admin::{
+        account::{get_account_list, get_login, post_create_account, post_login, post_logout},
         author::{
-            get_author_list, get_create_author, get_edit_author, post_create_author,
-            post_edit_author,
+            get_author_list, get_create_author, get_edit_author, post_bulk_authors,
+            post_create_author, post_edit_author,
+        },
+        comment::{get_comment_list, post_comment_status},
+        media::{get_media_list, post_delete_media},
+        newsletter::{get_newsletter, post_send_digest},
+        post::{
+            get_create_post, get_edit_post, get_post_list, get_post_markdown, post_bulk_posts,
+            post_create_post, post_edit_post, post_preview_link,
+        },
+        redirect::{get_redirect_list, post_create_redirect, post_delete_redirect},
+        revision::{get_revisions, post_restore_revision},
+        series::{
+            get_create_series, get_edit_series, get_series_list, post_create_series,
+            post_edit_series,
+        },
+        tag::{
+            get_create_tag, get_edit_tag, get_tag_list, post_bulk_tags, post_create_tag,
+            post_edit_tag,
         },
-        post::{get_create_post, get_edit_post, get_post_list, post_create_post, post_edit_post},
-        tag::{get_create_tag, get_edit_tag, get_tag_list, post_create_tag, post_edit_tag},
     },
-    blogs::{get_post, get_posts},
+    blogs::get_post_preview,
+    newsletter::{get_confirm_subscription, get_unsubscribe, post_subscribe, post_unsubscribe},
+    search::get_search,
+};
+use search::SearchIndex;
+use std::path::PathBuf;
+use utils::{
+    environ::Environ, file_ops::read_files_from_dir, image_ops, markdown_ops::MarkdownCache,
+    reference_ops::ReferenceCache, related_ops::RelatedCache,
 };
-use utils::{environ::Environ, file_ops::read_files_from_dir};
 
-fn configure_handlebars() -> Handlebars<'static> {
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// delete uploaded images and markdown files that no post, author or revision uses
+    GcMedia {
+        /// only list what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+
+        /// keep files younger than this, they may belong to an edit that is not saved yet
+        #[arg(long, default_value_t = 24)]
+        min_age_hours: i64,
+    },
+    /// index every post for search again, e.g. for posts saved before search existed
+    ReindexSearch,
+    /// copy the markdown files in assets/markdowns into the database and point posts at them
+    MigrateContent {
+        /// only list what would be imported
+        #[arg(long)]
+        dry_run: bool,
+
+        /// delete the files once they are all imported
+        #[arg(long)]
+        remove_files: bool,
+    },
+    /// render the published blog into plain files any static host can serve; search, comment
+    /// forms and newsletter sign ups post to the server at SITE_URL
+    ExportStatic {
+        #[arg(long, default_value = "./static")]
+        out_dir: PathBuf,
+
+        /// render every post again instead of only the ones that changed
+        #[arg(long)]
+        full: bool,
+    },
+}
+
+/// `server_url` prefixes what the pages send back to the server: search, comments and newsletter
+/// sign ups. Empty when served, SITE_URL when exported to a host that only serves files
+fn configure_handlebars(server_url: &str) -> Handlebars<'static> {
     let mut handlebars = Handlebars::new();
+    handlebars.register_helper("picture", Box::new(image_ops::picture_helper));
+    let server_url = html_escape(server_url);
+    handlebars.register_helper(
+        "server_url",
+        Box::new(
+            move |_: &Helper,
+                  _: &Handlebars,
+                  _: &Context,
+                  _: &mut RenderContext,
+                  out: &mut dyn Output|
+                  -> HelperResult {
+                out.write(&server_url)?;
+                Ok(())
+            },
+        ),
+    );
     read_files_from_dir("./pages", ".hbs")
         .iter()
         .for_each(|file| {
@@ -44,6 +147,8 @@ fn configure_handlebars() -> Handlebars<'static> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
     let env_file = if cfg!(debug_assertions) {
         ".env.dev"
     } else {
@@ -62,27 +167,119 @@ async fn main() -> std::io::Result<()> {
         .await
         .unwrap();
 
+    migrations::run(&mongoc).await;
+
+    let content_store: web::Data<dyn ContentStore> =
+        web::Data::from(content::from_environ(&mongoc));
+    let search_index: web::Data<dyn SearchIndex> = web::Data::from(search::init(&mongoc).await);
+
+    match cli.command {
+        Some(Commands::GcMedia {
+            dry_run,
+            min_age_hours,
+        }) => {
+            media::collect_garbage(&mongoc, content_store.get_ref(), min_age_hours, dry_run).await;
+            return Ok(());
+        }
+        Some(Commands::MigrateContent {
+            dry_run,
+            remove_files,
+        }) => {
+            content::migrate_to_database(&mongoc, dry_run, remove_files).await;
+            return Ok(());
+        }
+        Some(Commands::ReindexSearch) => {
+            search::rebuild(&mongoc, search_index.get_ref(), content_store.get_ref()).await;
+            return Ok(());
+        }
+        Some(Commands::ExportStatic { out_dir, full }) => {
+            export::export_site(
+                &mongoc,
+                configure_handlebars(&env_default.site_url),
+                content_store.clone(),
+                &out_dir,
+                full,
+            )
+            .await;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    auth::bootstrap_editor(&mongoc).await;
+    newsletter::init(&mongoc).await;
+    comments::init(&mongoc).await;
+    permalinks::init(&mongoc).await;
+    pages::admin::revision::init(&mongoc).await;
+    let mailer: web::Data<dyn Mailer> = match newsletter::from_environ() {
+        Ok(r) => web::Data::from(r),
+        Err(e) => {
+            log::error!("Invalid email settings: {:?}", e);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid email settings: {:?}", e),
+            ));
+        }
+    };
+
+    let session_key = match &env_default.session_key {
+        Some(key) => Key::derive_from(key.as_bytes()),
+        None => {
+            log::warn!("SESSION_KEY is not set, sessions will not survive a restart");
+            Key::generate()
+        }
+    };
+
     let ip = "localhost";
     let port = env_default.web_app_port;
 
     println!("Actix running at http://{ip}:{port}");
 
-    let handlebars = configure_handlebars();
+    let handlebars = configure_handlebars("");
+    let markdown_cache = web::Data::new(MarkdownCache::default());
+    let related_cache = web::Data::new(RelatedCache::default());
+    let reference_cache = web::Data::new(ReferenceCache::default());
 
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_origin(env_default.allowed_origin.as_str())
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
-            .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
+            .allowed_headers(vec![
+                http::header::AUTHORIZATION,
+                http::header::ACCEPT,
+                http::header::CONTENT_TYPE,
+            ])
             .max_age(3600);
 
+        let sessions =
+            SessionMiddleware::builder(CookieSessionStore::default(), session_key.clone())
+                .cookie_name(String::from("blog_session"))
+                .cookie_secure(!cfg!(debug_assertions))
+                .session_lifecycle(PersistentSession::default().session_ttl(Duration::hours(12)))
+                .build();
+
         App::new()
+            .wrap(middleware::from_fn(auth::require_login))
+            .wrap(sessions)
             .wrap(middleware::Logger::default())
             .wrap(cors)
             .app_data(web::Data::new(handlebars.clone()))
             .app_data(web::Data::new(mongoc.clone()))
+            .app_data(markdown_cache.clone())
+            .app_data(related_cache.clone())
+            .app_data(reference_cache.clone())
+            .app_data(content_store.clone())
+            .app_data(search_index.clone())
+            .app_data(mailer.clone())
             .service(fs::Files::new("/assets", "./assets").show_files_listing())
-            .service(get_posts)
+            .configure(pages::configure_public)
+            .service(get_login)
+            .service(post_login)
+            .service(post_logout)
+            .service(get_account_list)
+            .service(get_media_list)
+            .service(post_delete_media)
+            .service(post_create_account)
             .service(get_create_post)
             .service(post_create_post)
             .service(get_create_author)
@@ -90,18 +287,44 @@ async fn main() -> std::io::Result<()> {
             .service(get_create_tag)
             .service(post_create_tag)
             .service(get_tag_list)
+            .service(post_bulk_tags)
             .service(get_edit_tag)
             .service(post_edit_tag)
+            .service(get_series_list)
+            .service(get_create_series)
+            .service(post_create_series)
+            .service(get_edit_series)
+            .service(post_edit_series)
             .service(post_photo)
             .service(post_photos)
             .service(post_markdown)
             .service(get_author_list)
+            .service(post_bulk_authors)
             .service(get_edit_author)
             .service(post_edit_author)
             .service(get_post_list)
+            .service(post_bulk_posts)
             .service(get_edit_post)
             .service(post_edit_post)
-            .service(get_post)
+            .service(post_preview_link)
+            .service(get_post_markdown)
+            .service(get_revisions)
+            .service(post_restore_revision)
+            .service(get_post_preview)
+            .service(get_search)
+            .service(get_search_results)
+            .service(post_comment)
+            .service(get_redirect_list)
+            .service(post_create_redirect)
+            .service(post_delete_redirect)
+            .service(get_comment_list)
+            .service(post_comment_status)
+            .service(post_subscribe)
+            .service(get_confirm_subscription)
+            .service(get_unsubscribe)
+            .service(post_unsubscribe)
+            .service(get_newsletter)
+            .service(post_send_digest)
     })
     .bind((ip, port))?
     .run()
@@ -0,0 +1,342 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use log::{error, info};
+use mongodb::Client;
+use serde::Serialize;
+
+use crate::{
+    content::ContentStore,
+    entities::{
+        blogs::{AuthorEntity, PostEntity},
+        result_types::{DatabaseErrorType, EntityResult},
+        revisions::RevisionEntity,
+    },
+    utils::{
+        date_ops,
+        db_ops::Database,
+        file_ops,
+        image_ops::{ImagePath, ImageSize, RELATIVE_PATH, UPLOADS_DIR},
+    },
+};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Markdown,
+}
+
+/// something that points at an asset: a post, an author or a saved revision of a post
+#[derive(Debug, Serialize, Clone)]
+pub struct MediaReference {
+    pub kind: &'static str,
+    pub title: String,
+    pub url: String,
+}
+
+/// an uploaded image with all of its sizes and formats, or a stored markdown body
+#[derive(Debug, Serialize)]
+pub struct MediaAsset {
+    pub key: String,
+    pub kind: MediaKind,
+    /// public path of the original upload
+    pub url: String,
+    pub thumbnail: Option<String>,
+    /// bytes stored, summed over every file of the asset
+    pub bytes: u64,
+    pub size: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub uploaded_date: NaiveDateTime,
+    pub uploaded: String,
+    pub references: Vec<MediaReference>,
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+impl MediaAsset {
+    fn new(key: String, kind: MediaKind, url: String) -> Self {
+        MediaAsset {
+            key,
+            kind,
+            url,
+            thumbnail: None,
+            bytes: 0,
+            size: String::new(),
+            width: None,
+            height: None,
+            uploaded_date: NaiveDateTime::default(),
+            uploaded: String::new(),
+            references: vec![],
+            files: vec![],
+        }
+    }
+
+    /// reads the dimensions from the image header, the pixels are not decoded
+    pub fn load_dimensions(&mut self) {
+        if self.kind != MediaKind::Image {
+            return;
+        }
+        let original = self.files.iter().find(|file| {
+            file.file_stem().and_then(|stem| stem.to_str()) == Some(self.key.as_str())
+        });
+        if let Some((width, height)) = original.and_then(|file| image::image_dimensions(file).ok())
+        {
+            self.width = Some(width);
+            self.height = Some(height);
+        }
+    }
+
+    /// removes the image files, or the markdown body from the content store
+    pub async fn delete(&self, content: &dyn ContentStore) -> Result<(), String> {
+        if self.kind == MediaKind::Markdown {
+            return match content.delete(&self.key).await {
+                EntityResult::Success(_) => Ok(()),
+                EntityResult::Error(e) => Err(format!("{:?}", e)),
+            };
+        }
+        for file in &self.files {
+            fs::remove_file(file).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// upload keys are generated alphanumerics, anything else never names an asset
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn modified_date(modified: SystemTime) -> NaiveDateTime {
+    DateTime::<Local>::from(modified).naive_local()
+}
+
+/// every uploaded image and stored markdown body, newest first, without references
+pub async fn scan_assets(content: &dyn ContentStore) -> EntityResult<Vec<MediaAsset>> {
+    let mut assets = BTreeMap::<String, MediaAsset>::new();
+
+    // a fresh install has no uploads folder until the first upload
+    for entry in fs::read_dir(UPLOADS_DIR).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let metadata = match entry.metadata() {
+            Ok(r) if r.is_file() => r,
+            _ => continue,
+        };
+        let (stem, extension) = match (
+            path.file_stem().and_then(|s| s.to_str()),
+            path.extension().and_then(|s| s.to_str()),
+        ) {
+            (Some(stem), Some(extension)) => (stem.to_string(), extension.to_string()),
+            _ => continue,
+        };
+
+        // resized and converted copies are named {key}-{size}.{extension}
+        let key = stem.split('-').next().unwrap_or_default().to_string();
+        if !is_valid_key(&key) {
+            continue;
+        }
+
+        let asset = assets.entry(key.clone()).or_insert_with(|| {
+            let url = format!("{}/{}.{}", RELATIVE_PATH, key, extension);
+            MediaAsset::new(key.clone(), MediaKind::Image, url)
+        });
+
+        if stem == key {
+            let image = ImagePath::new(key.clone(), extension);
+            asset.url = image.original_r_path();
+            asset.thumbnail = Some(image.from_r(&ImageSize::Thumbnail));
+        }
+        if let Ok(modified) = metadata.modified() {
+            asset.uploaded_date = asset.uploaded_date.max(modified_date(modified));
+        }
+        asset.bytes += metadata.len();
+        asset.files.push(path);
+    }
+
+    let contents = match content.list().await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    for info in contents {
+        let mut asset = MediaAsset::new(
+            info.key.clone(),
+            MediaKind::Markdown,
+            format!("/admin/markdown/{}", info.key),
+        );
+        asset.bytes = info.bytes;
+        asset.uploaded_date = info.created_date;
+        assets.insert(info.key, asset);
+    }
+
+    let mut assets = assets.into_values().collect::<Vec<_>>();
+    for asset in assets.iter_mut() {
+        asset.size = file_ops::display_size(asset.bytes);
+        asset.uploaded = asset.uploaded_date.format("%Y-%m-%d %H:%M").to_string();
+    }
+    assets.sort_by_key(|asset| std::cmp::Reverse(asset.uploaded_date));
+    EntityResult::Success(assets)
+}
+
+/// upload keys of the images a markdown body embeds
+fn embedded_keys(markdown: &str) -> Vec<String> {
+    markdown
+        .match_indices(RELATIVE_PATH)
+        .filter_map(|(index, _)| {
+            let path = markdown[index..]
+                .split(|c: char| !(c.is_ascii_alphanumeric() || "/._-".contains(c)))
+                .next()?;
+            ImagePath::parse(path).map(|image| image.key)
+        })
+        .collect()
+}
+
+/// every asset key referenced by a post, an author or a revision, with what references it
+pub async fn find_references(
+    mongoc: &Client,
+    content: &dyn ContentStore,
+) -> EntityResult<HashMap<String, Vec<MediaReference>>> {
+    let mut references = HashMap::<String, Vec<MediaReference>>::new();
+    let mut add = |key: String, reference: &MediaReference| {
+        let entry = references.entry(key).or_default();
+        if !entry.iter().any(|r| r.url == reference.url) {
+            entry.push(reference.clone());
+        }
+    };
+
+    let posts_collection = Database::get_collection(mongoc, "posts");
+    let posts = match Database::find_all::<PostEntity>(posts_collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    for post in posts {
+        let post_id = post._id.map(|id| id.to_hex()).unwrap_or_default();
+        let reference = MediaReference {
+            kind: "post",
+            title: post.title.clone(),
+            url: format!("/admin/post/{}", post_id),
+        };
+        add(post.hero_image.key.clone(), &reference);
+        add(post.body.clone(), &reference);
+        // a post whose body went missing still references what its revisions embed
+        let markdown = match content.read(&post.body).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(DatabaseErrorType::NotFound(..)) => String::new(),
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+        for key in embedded_keys(&markdown) {
+            add(key, &reference);
+        }
+    }
+
+    let authors_collection = Database::get_collection(mongoc, "authors");
+    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    for author in authors {
+        let reference = MediaReference {
+            kind: "author",
+            title: format!("{} {}", author.first_name, author.last_name),
+            url: format!(
+                "/admin/author/{}",
+                author._id.map(|id| id.to_hex()).unwrap_or_default()
+            ),
+        };
+        add(author.photo_url.key.clone(), &reference);
+    }
+
+    // older revisions keep their images and markdown so they can still be restored
+    let revisions_collection = Database::get_collection(mongoc, "revisions");
+    let revisions = match Database::find_all::<RevisionEntity>(revisions_collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    for revision in revisions {
+        let reference = MediaReference {
+            kind: "revision",
+            title: format!("{} (revision {})", revision.post.title, revision.number),
+            url: format!(
+                "/admin/post/{}/revisions?to={}",
+                revision.post_id, revision.number
+            ),
+        };
+        add(revision.post.hero_image.key.clone(), &reference);
+        add(revision.post.body.clone(), &reference);
+        for key in embedded_keys(&revision.markdown) {
+            add(key, &reference);
+        }
+    }
+
+    EntityResult::Success(references)
+}
+
+/// all assets with their references filled in
+pub async fn find_assets(
+    mongoc: &Client,
+    content: &dyn ContentStore,
+) -> EntityResult<Vec<MediaAsset>> {
+    let mut references = match find_references(mongoc, content).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let mut assets = match scan_assets(content).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    for asset in assets.iter_mut() {
+        asset.references = references.remove(&asset.key).unwrap_or_default();
+    }
+    EntityResult::Success(assets)
+}
+
+/// deletes the assets nothing references; assets younger than min_age_hours are kept because
+/// they may belong to an edit that has not been saved yet
+pub async fn collect_garbage(
+    mongoc: &Client,
+    content: &dyn ContentStore,
+    min_age_hours: i64,
+    dry_run: bool,
+) {
+    let assets = match find_assets(mongoc, content).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find media references: {:?}", e);
+            return;
+        }
+    };
+
+    let cutoff = date_ops::local_date() - chrono::Duration::hours(min_age_hours);
+    let orphans = assets
+        .iter()
+        .filter(|asset| asset.references.is_empty() && asset.uploaded_date < cutoff)
+        .collect::<Vec<_>>();
+
+    let mut freed = 0;
+    for asset in &orphans {
+        if dry_run {
+            info!("Would delete {} ({})", asset.url, asset.size);
+            continue;
+        }
+        match asset.delete(content).await {
+            Ok(_) => {
+                info!("Deleted {} ({})", asset.url, asset.size);
+                freed += asset.bytes;
+            }
+            Err(e) => error!("Failed to delete {}: {:?}", asset.url, e),
+        }
+    }
+
+    info!(
+        "{} of {} assets are orphans, {} freed",
+        orphans.len(),
+        assets.len(),
+        file_ops::display_size(freed)
+    );
+}
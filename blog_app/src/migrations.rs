@@ -0,0 +1,111 @@
+use log::{error, info};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Client,
+};
+use serde::Deserialize;
+
+use crate::{
+    entities::{
+        blogs::PostEntity,
+        result_types::{EntityResult, SuccessResultType},
+    },
+    permalinks,
+    utils::db_ops::Database,
+};
+
+#[derive(Debug, Deserialize)]
+struct SharedPermalink {
+    #[serde(rename = "_id")]
+    permalink: String,
+    /// oldest first, the oldest post keeps the permalink
+    posts: Vec<ObjectId>,
+}
+
+/// brings documents saved by older versions up to date; every step only matches documents
+/// still in the old shape, so running them on each start is harmless
+pub async fn run(mongoc: &Client) {
+    multiple_authors_and_tags(mongoc).await;
+    unique_permalinks(mongoc).await;
+}
+
+/// posts had a single author and tag id, now they have lists with the main one first; saved
+/// revisions and the search index hold copies of posts and are moved over too
+async fn multiple_authors_and_tags(mongoc: &Client) {
+    let fields = [
+        ("posts", "author", "authors"),
+        ("posts", "tag", "tags"),
+        ("revisions", "post.author", "post.authors"),
+        ("revisions", "post.tag", "post.tags"),
+        ("search", "tag", "tags"),
+    ];
+
+    for (collection, old, new) in fields {
+        let pipeline = vec![
+            doc! { "$set": { new: [format!("${}", old)] } },
+            doc! { "$unset": old },
+        ];
+        match Database::update_many(
+            Database::get_collection::<Document>(mongoc, collection),
+            doc! { old: { "$exists": true } },
+            pipeline,
+        )
+        .await
+        {
+            EntityResult::Success(SuccessResultType::Updated(count)) if count != "0" => {
+                info!("Moved {} {} to {}: {}", collection, old, new, count)
+            }
+            EntityResult::Success(_) => {}
+            EntityResult::Error(e) => error!("Failed to move {} {}: {:?}", collection, old, e),
+        }
+    }
+}
+
+/// permalinks were typed in freely and could be shared by several posts, which the unique
+/// index does not allow; newer posts get a numbered permalink
+async fn unique_permalinks(mongoc: &Client) {
+    let pipeline = vec![
+        doc! { "$sort": { "_id": 1 } },
+        doc! { "$group": { "_id": "$permalink", "posts": { "$push": "$_id" } } },
+        doc! { "$match": { "posts.1": { "$exists": true } } },
+    ];
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let shared = match Database::aggregate::<PostEntity, SharedPermalink>(
+        collection.clone(),
+        pipeline,
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find shared permalinks: {:?}", e);
+            return;
+        }
+    };
+
+    for shared in shared {
+        for post_id in shared.posts.into_iter().skip(1) {
+            let permalink = match permalinks::unique(mongoc, &shared.permalink, Some(post_id)).await
+            {
+                EntityResult::Success(r) => r,
+                EntityResult::Error(e) => {
+                    error!("Failed to find a permalink for {}: {:?}", post_id, e);
+                    continue;
+                }
+            };
+            match Database::update_many(
+                collection.clone(),
+                doc! { "_id": post_id },
+                doc! { "$set": { "permalink": &permalink } },
+            )
+            .await
+            {
+                EntityResult::Success(_) => info!(
+                    "Moved post {} from {} to {}",
+                    post_id, shared.permalink, permalink
+                ),
+                EntityResult::Error(e) => error!("Failed to move post {}: {:?}", post_id, e),
+            }
+        }
+    }
+}
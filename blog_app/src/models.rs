@@ -1,17 +1,24 @@
 use std::vec;
 
 use log::error;
-use mongodb::{bson::oid::ObjectId, Collection};
+use mongodb::Collection;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     entities::{
-        blogs::{AuthorEntity, PostEntity, TagEntity},
+        accounts::{AccountEntity, AccountRole},
+        blogs::{AuthorEntity, PostEntity, PostStatus, SeriesEntity, TagEntity},
+        comments::{CommentEntity, CommentStatus},
+        redirects::RedirectEntity,
         result_types::EntityResult,
+        revisions::RevisionEntity,
     },
-    utils::{date_ops, db_ops::Database, image_ops::ImagePath},
+    utils::{date_ops, db_ops::Database, image_ops::ImagePath, markdown_ops},
 };
 
+/// highest page number honoured from a query string, larger ones are read as this one
+pub const MAX_PAGE: u64 = 10_000;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthorRequestModel {
     pub first_name: String,
@@ -22,7 +29,7 @@ pub struct AuthorRequestModel {
     pub intro: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AuthorResponseModel {
     pub id: String,
     pub first_name: String,
@@ -32,6 +39,8 @@ pub struct AuthorResponseModel {
     pub profile_photo: String,
     pub thumbnail_photo: String,
     pub intro: String,
+    pub url: String,
+    pub archived: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,11 +49,50 @@ pub struct TagRequestModel {
     pub description: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TagResponseModel {
     pub id: String,
     pub name: String,
     pub description: String,
+    pub url: String,
+    pub archived: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeriesRequestModel {
+    pub name: String,
+    pub description: String,
+    pub posts: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesResponseModel {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// comma separated post ids, for the admin form
+    pub post_ids: String,
+    pub parts: usize,
+}
+
+/// a part of a series as linked from a post
+#[derive(Debug, Serialize, Clone)]
+pub struct SeriesPartModel {
+    pub number: usize,
+    pub title: String,
+    pub url: String,
+    pub current: bool,
+}
+
+/// where a post sits in its series, with the parts around it
+#[derive(Debug, Serialize)]
+pub struct SeriesNavigationModel {
+    pub name: String,
+    pub description: String,
+    pub part: usize,
+    pub parts: Vec<SeriesPartModel>,
+    pub prev: Option<SeriesPartModel>,
+    pub next: Option<SeriesPartModel>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,12 +109,22 @@ pub struct PostResponseModel {
     pub profile_image: String,
     pub publish_date: String,
     pub modified_date: String,
+    /// the main author and tag, for cards and listings
     pub author: AuthorResponseModel,
     pub tag: TagResponseModel,
+    pub authors: Vec<AuthorResponseModel>,
+    pub tags: Vec<TagResponseModel>,
+    pub featured: bool,
+    pub status: PostStatus,
+    /// approved comments, only filled in where listings show it
+    #[serde(default)]
+    pub comments: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PostRequestModel {
+    /// made from the title when left empty
+    #[serde(default)]
     pub permalink: String,
     pub title: String,
     pub subtitle: String,
@@ -77,8 +135,285 @@ pub struct PostRequestModel {
     pub hero_image: String,
     pub publish_date: i64,
     pub modified_date: i64,
+    /// author ids, the main author first
+    pub authors: Vec<String>,
+    /// tag ids, the main tag first
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub featured: bool,
+    pub status: PostStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequestModel {
+    pub email: String,
+    pub password: String,
+    pub csrf_token: String,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountRequestModel {
+    pub email: String,
+    pub password: String,
+    pub role: AccountRole,
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountResponseModel {
+    pub id: String,
+    pub email: String,
+    pub role: AccountRole,
+    pub author: Option<String>,
+}
+
+impl AccountResponseModel {
+    pub fn from(entity: AccountEntity) -> Self {
+        AccountResponseModel {
+            id: entity._id.unwrap().to_string(),
+            email: entity.email,
+            role: entity.role,
+            author: entity.author,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevisionResponseModel {
+    pub number: u64,
+    pub title: String,
+    pub editor: String,
+    pub created_date: String,
+    pub restored_from: Option<u64>,
+}
+
+/// the revisions to compare, by number; both default to the latest save and the one before
+#[derive(Debug, Deserialize)]
+pub struct RevisionQuery {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+/// a metadata field that differs between two revisions
+#[derive(Debug, Serialize)]
+pub struct FieldChangeModel {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub page: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentRequestModel {
+    pub post_id: String,
+    /// the comment replied to, none for a top level comment
+    pub parent_id: Option<String>,
+    pub name: String,
+    pub email: String,
+    pub body: String,
+    /// honeypot, hidden from readers; bots that fill it in are dropped quietly
+    #[serde(default)]
+    pub website: String,
+}
+
+/// an approved comment with its approved replies, oldest first
+#[derive(Debug, Serialize)]
+pub struct CommentResponseModel {
+    pub id: String,
+    pub name: String,
+    /// sanitised html
+    pub body: String,
+    pub created_date: String,
+    pub replies: Vec<CommentResponseModel>,
+}
+
+/// a comment as the moderation queue shows it
+#[derive(Debug, Serialize)]
+pub struct CommentAdminModel {
+    pub id: String,
+    pub post_title: String,
+    pub post_permalink: String,
+    pub is_reply: bool,
+    pub name: String,
+    pub email: String,
+    pub body: String,
+    pub status: CommentStatus,
+    pub created_date: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentStatusRequestModel {
+    pub status: CommentStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentQuery {
+    pub status: Option<CommentStatus>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Archive,
+    Restore,
+    /// for good, posts have to be archived first
+    Delete,
+}
+
+/// an action on the posts, authors or tags ticked on a list page
+#[derive(Debug, Deserialize)]
+pub struct BulkRequestModel {
+    pub action: BulkAction,
+    pub ids: Vec<String>,
+    /// the author or tag that takes over the posts of deleted ones
+    #[serde(default)]
+    pub reassign_to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkFailureModel {
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct BulkResponseModel {
+    pub done: usize,
+    pub failed: Vec<BulkFailureModel>,
+}
+
+/// a redirect set up by hand in the admin
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedirectRequestModel {
+    /// the old permalink, without /blogs/
+    pub permalink: String,
+    pub target: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RedirectResponseModel {
+    pub id: String,
+    pub permalink: String,
+    /// where it goes, the current url of the post for automatic redirects
+    pub target: String,
+    /// title of the post an automatic redirect follows
+    pub post_title: Option<String>,
+    pub manual: bool,
+    pub created_date: String,
+}
+
+/// the newsletter sign up form
+#[derive(Debug, Deserialize)]
+pub struct SubscribeRequestModel {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchRequestModel {
+    pub q: Option<String>,
+    /// slug of the tag to narrow the results to
+    pub tag: Option<String>,
+    pub page: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResultModel {
+    pub id: String,
+    pub title: String,
+    pub subtitle: String,
+    pub url: String,
+    pub profile_image: String,
+    pub publish_date: String,
     pub author: String,
-    pub tag: String,
+    pub author_url: String,
+    pub tag: TagResponseModel,
+    /// html excerpt with the matched terms marked
+    pub snippet: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchFacetModel {
+    pub name: String,
+    pub slug: String,
+    pub count: u64,
+    pub selected: bool,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponseModel {
+    pub query: String,
+    pub tag: Option<String>,
+    pub total: u64,
+    pub results: Vec<SearchResultModel>,
+    pub facets: Vec<SearchFacetModel>,
+    pub pagination: PaginationModel,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaginationModel {
+    pub page: u64,
+    pub pages: u64,
+    pub total: u64,
+    pub prev: Option<u64>,
+    pub next: Option<u64>,
+}
+
+impl RevisionResponseModel {
+    pub fn from(entity: RevisionEntity) -> Self {
+        RevisionResponseModel {
+            number: entity.number,
+            title: entity.post.title,
+            editor: entity.editor,
+            created_date: entity.created_date.format("%Y-%m-%d %H:%M").to_string(),
+            restored_from: entity.restored_from,
+        }
+    }
+}
+
+impl SearchResultModel {
+    pub fn from(post: PostResponseModel, snippet: String, score: f64) -> Self {
+        SearchResultModel {
+            url: format!("/blogs/{}", post.permalink),
+            author: format!("{} {}", post.author.first_name, post.author.last_name),
+            author_url: post.author.url,
+            id: post.id,
+            title: post.title,
+            subtitle: post.subtitle,
+            profile_image: post.profile_image,
+            publish_date: post.publish_date,
+            tag: post.tag,
+            snippet,
+            score,
+        }
+    }
+}
+
+impl PageQuery {
+    /// 1-based page number, anything below 1 is treated as the first page and anything above
+    /// MAX_PAGE as the last one, so the offset of a page always fits
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).clamp(1, MAX_PAGE)
+    }
+}
+
+impl PaginationModel {
+    pub fn new(page: u64, page_size: u64, total: u64) -> Self {
+        let pages = total.div_ceil(page_size).max(1);
+        PaginationModel {
+            page,
+            pages,
+            total,
+            prev: if page > 1 { Some(page - 1) } else { None },
+            next: if page < pages { Some(page + 1) } else { None },
+        }
+    }
 }
 
 impl AuthorRequestModel {
@@ -91,6 +426,7 @@ impl AuthorRequestModel {
             photo_url: ImagePath::from_string(self.photo_url.as_str()),
             intro: self.intro.to_string(),
             last_name: self.last_name.clone(),
+            archived_date: None,
         }
     }
 }
@@ -112,6 +448,11 @@ impl Default for PostResponseModel {
             modified_date: date_ops::local_date().to_string(),
             author: AuthorResponseModel::default(),
             tag: TagResponseModel::default(),
+            authors: vec![],
+            tags: vec![],
+            featured: false,
+            status: PostStatus::Draft,
+            comments: 0,
         }
     }
 }
@@ -127,6 +468,8 @@ impl Default for AuthorResponseModel {
             profile_photo: "not-set".to_string(),
             thumbnail_photo: "not-set".to_string(),
             intro: "not-set".to_string(),
+            url: "not-set".to_string(),
+            archived: false,
         }
     }
 }
@@ -135,6 +478,8 @@ impl AuthorResponseModel {
     pub fn from(entity: AuthorEntity) -> Self {
         AuthorResponseModel {
             id: entity._id.unwrap().to_string(),
+            url: entity.url(),
+            archived: entity.archived_date.is_some(),
             first_name: entity.first_name,
             last_name: entity.last_name,
             email: entity.email,
@@ -156,12 +501,162 @@ impl AuthorResponseModel {
     }
 }
 
+impl CommentRequestModel {
+    pub fn to(&self, client_hash: String) -> CommentEntity {
+        CommentEntity {
+            _id: None,
+            post_id: self.post_id.to_string(),
+            parent_id: self.parent_id.clone(),
+            name: self.name.trim().to_string(),
+            email: self.email.trim().to_lowercase(),
+            body: self.body.to_string(),
+            status: CommentStatus::Pending,
+            created_date: date_ops::local_date(),
+            client_hash,
+        }
+    }
+}
+
+impl CommentResponseModel {
+    pub fn from(entity: &CommentEntity) -> Self {
+        CommentResponseModel {
+            id: entity._id.unwrap().to_hex(),
+            name: entity.name.to_string(),
+            body: markdown_ops::render_comment(&entity.body),
+            created_date: date_ops::to_display_date(entity.created_date),
+            replies: vec![],
+        }
+    }
+
+    /// nests the comments under their parents; replies whose parent is not among the
+    /// comments, e.g. because it was rejected, are left out with it
+    pub fn thread(entities: &[CommentEntity], parent_id: Option<&str>) -> Vec<Self> {
+        entities
+            .iter()
+            .filter(|entity| entity.parent_id.as_deref() == parent_id)
+            .map(|entity| {
+                let mut comment = CommentResponseModel::from(entity);
+                comment.replies = CommentResponseModel::thread(entities, Some(&comment.id));
+                comment
+            })
+            .collect()
+    }
+}
+
+impl CommentAdminModel {
+    pub fn from(entity: CommentEntity, post: Option<&PostEntity>) -> Self {
+        CommentAdminModel {
+            id: entity._id.unwrap().to_hex(),
+            post_title: post.map(|p| p.title.clone()).unwrap_or_default(),
+            post_permalink: post.map(|p| p.permalink.clone()).unwrap_or_default(),
+            is_reply: entity.parent_id.is_some(),
+            name: entity.name,
+            email: entity.email,
+            body: markdown_ops::render_comment(&entity.body),
+            status: entity.status,
+            created_date: date_ops::to_display_date(entity.created_date),
+        }
+    }
+}
+
+impl RedirectRequestModel {
+    pub fn to(&self) -> RedirectEntity {
+        RedirectEntity {
+            _id: None,
+            permalink: self.permalink.trim().to_string(),
+            post_id: None,
+            target: Some(self.target.trim().to_string()),
+            created_date: date_ops::local_date(),
+        }
+    }
+}
+
+impl RedirectResponseModel {
+    /// post is the one an automatic redirect follows, none when it was deleted since
+    pub fn from(entity: RedirectEntity, post: Option<&PostEntity>) -> Self {
+        let target = match (&entity.target, post) {
+            (Some(target), _) => target.clone(),
+            (None, Some(post)) => format!("/blogs/{}", post.permalink),
+            (None, None) => String::new(),
+        };
+        RedirectResponseModel {
+            id: entity._id.unwrap().to_hex(),
+            permalink: entity.permalink,
+            target,
+            post_title: post.map(|p| p.title.clone()),
+            manual: entity.post_id.is_none(),
+            created_date: date_ops::to_display_date(entity.created_date),
+        }
+    }
+}
+
+impl SeriesRequestModel {
+    pub fn to(&self) -> SeriesEntity {
+        SeriesEntity {
+            _id: None,
+            name: self.name.to_string(),
+            description: self.description.to_string(),
+            posts: self.posts.clone(),
+        }
+    }
+}
+
+impl SeriesResponseModel {
+    pub fn from(entity: SeriesEntity) -> Self {
+        SeriesResponseModel {
+            id: entity._id.unwrap().to_string(),
+            name: entity.name,
+            description: entity.description,
+            post_ids: entity.posts.join(","),
+            parts: entity.posts.len(),
+        }
+    }
+
+    pub fn from_vec(entities: Vec<SeriesEntity>) -> Vec<Self> {
+        entities
+            .into_iter()
+            .map(SeriesResponseModel::from)
+            .collect()
+    }
+}
+
+impl SeriesNavigationModel {
+    /// the navigation of a series as seen from one of its posts; posts are the parts readers
+    /// can open, in series order, and always include the current one
+    pub fn new(series: &SeriesEntity, posts: &[PostEntity], current_id: &str) -> Self {
+        let parts = posts
+            .iter()
+            .enumerate()
+            .map(|(index, post)| SeriesPartModel {
+                number: index + 1,
+                title: post.title.clone(),
+                url: format!("/blogs/{}", post.permalink),
+                current: post._id.map(|id| id.to_hex()).as_deref() == Some(current_id),
+            })
+            .collect::<Vec<_>>();
+        let index = parts
+            .iter()
+            .position(|part| part.current)
+            .unwrap_or_default();
+
+        SeriesNavigationModel {
+            name: series.name.clone(),
+            description: series.description.clone(),
+            part: index + 1,
+            prev: index.checked_sub(1).map(|i| parts[i].clone()),
+            next: parts.get(index + 1).cloned(),
+            parts,
+        }
+    }
+}
+
 impl TagRequestModel {
     pub fn to(&self) -> TagEntity {
         TagEntity {
             _id: None,
             name: self.name.to_string(),
             description: self.description.to_string(),
+            archived_date: None,
         }
     }
 }
@@ -172,6 +667,8 @@ impl Default for TagResponseModel {
             id: "not-set".to_string(),
             name: "not-set".to_string(),
             description: "not-set".to_string(),
+            url: "not-set".to_string(),
+            archived: false,
         }
     }
 }
@@ -180,6 +677,8 @@ impl TagResponseModel {
     pub fn from(entity: TagEntity) -> Self {
         TagResponseModel {
             id: entity._id.unwrap().to_string(),
+            url: entity.url(),
+            archived: entity.archived_date.is_some(),
             name: entity.name.to_string(),
             description: entity.description.to_string(),
         }
@@ -213,6 +712,11 @@ impl PostResponseModel {
             modified_date: date_ops::to_display_date(entity.modified_date).to_string(),
             author: AuthorResponseModel::default(),
             tag: TagResponseModel::default(),
+            authors: vec![],
+            tags: vec![],
+            featured: entity.featured,
+            status: entity.effective_status(),
+            comments: 0,
         }
     }
 
@@ -238,60 +742,38 @@ impl PostResponseModel {
 
     pub fn all(
         posts: Vec<PostEntity>,
-        authors: Vec<AuthorEntity>,
-        tags: Vec<TagEntity>,
+        authors: &[AuthorEntity],
+        tags: &[TagEntity],
     ) -> Vec<PostResponseModel> {
-        let mut post_responses = Vec::new();
+        posts
+            .into_iter()
+            .map(|post| PostResponseModel::combine(post, authors, tags))
+            .collect()
+    }
 
-        for post in posts {
-            let post_author = AuthorResponseModel::from(
+    /// the post with its authors and tags resolved, in the order the post lists them; ids
+    /// that no longer resolve are skipped
+    pub fn combine(entity: PostEntity, authors: &[AuthorEntity], tags: &[TagEntity]) -> Self {
+        let post_authors = entity
+            .authors
+            .iter()
+            .filter_map(|id| {
                 authors
                     .iter()
-                    .find(|author| {
-                        author
-                            ._id
-                            .unwrap()
-                            .eq(&ObjectId::parse_str(post.author.as_str()).unwrap())
-                    })
-                    .unwrap()
-                    .clone(),
-            );
-
-            let post_tag = TagResponseModel::from(
+                    .find(|author| author._id.map(|a| a.to_string()).as_deref() == Some(id))
+            })
+            .map(|author| AuthorResponseModel::from(author.clone()))
+            .collect::<Vec<_>>();
+        let post_tags = entity
+            .tags
+            .iter()
+            .filter_map(|id| {
                 tags.iter()
-                    .find(|tag| {
-                        tag._id
-                            .unwrap()
-                            .eq(&ObjectId::parse_str(post.tag.as_str()).unwrap())
-                    })
-                    .unwrap()
-                    .clone(),
-            );
-
-            let post_response = PostResponseModel {
-                id: post._id.unwrap().to_string(),
-                permalink: post.permalink.clone(),
-                title: post.title.clone(),
-                keywords: post.kicker.clone(),
-                subtitle: post.subtitle.clone(),
-                body: post.body.clone(),
-                description: post.description.clone(),
-                tldr: post.tldr.clone(),
-                hero_image: post.hero_image.hero_r_path(),
-                profile_image: post.hero_image.profile_r_path(),
-                publish_date: date_ops::to_display_date(post.publish_date).to_string(),
-                modified_date: date_ops::to_display_date(post.modified_date).to_string(),
-                author: post_author,
-                tag: post_tag,
-            };
-
-            post_responses.push(post_response);
-        }
-
-        post_responses
-    }
+                    .find(|tag| tag._id.map(|t| t.to_string()).as_deref() == Some(id))
+            })
+            .map(|tag| TagResponseModel::from(tag.clone()))
+            .collect::<Vec<_>>();
 
-    pub fn combine(entity: PostEntity, authors: Vec<AuthorEntity>, tags: Vec<TagEntity>) -> Self {
         PostResponseModel {
             id: entity._id.unwrap().to_string(),
             permalink: entity.permalink.to_string(),
@@ -305,25 +787,13 @@ impl PostResponseModel {
             profile_image: entity.hero_image.profile_r_path(),
             publish_date: date_ops::to_display_date(entity.publish_date).to_string(),
             modified_date: date_ops::to_display_date(entity.modified_date).to_string(),
-            author: authors
-                .iter()
-                .find(|author| {
-                    author
-                        ._id
-                        .unwrap()
-                        .eq(&ObjectId::parse_str(entity.author.as_str()).unwrap())
-                })
-                .map(|author| AuthorResponseModel::from(author.clone()))
-                .unwrap(),
-            tag: tags
-                .iter()
-                .find(|tag| {
-                    tag._id
-                        .unwrap()
-                        .eq(&ObjectId::parse_str(entity.tag.as_str()).unwrap())
-                })
-                .map(|tag| TagResponseModel::from(tag.clone()))
-                .unwrap(),
+            author: post_authors.first().cloned().unwrap_or_default(),
+            tag: post_tags.first().cloned().unwrap_or_default(),
+            authors: post_authors,
+            tags: post_tags,
+            featured: entity.featured,
+            status: entity.effective_status(),
+            comments: 0,
         }
     }
 }
@@ -339,11 +809,13 @@ impl PostRequestModel {
             hero_image: ImagePath::from_string(self.hero_image.as_str()),
             subtitle: self.subtitle.to_string(),
             _id: None,
-            author: self.author.clone(),
-            tag: self.tag.clone(),
+            authors: self.authors.clone(),
+            tags: self.tags.clone(),
             kicker: self.keywords.to_string(),
             publish_date: date_ops::from(self.publish_date),
             modified_date: date_ops::from(self.modified_date),
+            featured: self.featured,
+            status: self.status,
         }
     }
 }
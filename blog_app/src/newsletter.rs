@@ -0,0 +1,530 @@
+use std::{str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use handlebars::Handlebars;
+use lettre::{
+    message::{
+        header::{HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    transport::smtp::authentication::Credentials,
+    Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use log::{error, info};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::{FindOptions, IndexOptions},
+    Client, Collection, IndexModel,
+};
+use serde_json::json;
+
+use crate::{
+    auth,
+    entities::{
+        blogs::{AuthorEntity, PostEntity, TagEntity},
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+        subscribers::{DigestEntity, SubscriberEntity, SubscriberStatus},
+    },
+    models::PostResponseModel,
+    utils::{date_ops, db_ops::Database, environ::Environ, seo_ops},
+};
+
+/// how long a confirmation link stays valid
+const CONFIRM_TTL_SECONDS: i64 = 48 * 60 * 60;
+/// the first digest covers this many days, later ones start where the previous one ended
+const FIRST_DIGEST_DAYS: i64 = 14;
+/// an address is sent one confirmation link within this time, however often it is entered
+const RESEND_COOLDOWN_MINUTES: i64 = 15;
+/// confirmation links one client may ask for within the window before it has to wait
+const RATE_LIMIT_SUBSCRIBES: u64 = 5;
+const RATE_LIMIT_WINDOW_MINUTES: i64 = 60;
+
+#[derive(Debug)]
+pub enum MailError {
+    Address(String),
+    Build(String),
+    Render(String),
+    Send(String),
+}
+
+#[derive(Debug)]
+pub enum SubscribeError {
+    RateLimited,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct OutgoingMail {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    /// absolute url, sent as List-Unsubscribe with one-click support
+    pub unsubscribe_url: Option<String>,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: OutgoingMail) -> Result<(), MailError>;
+}
+
+/// sends over smtp; without EMAIL_TLS it talks plain smtp, which is what MailHog expects
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(environ: &Environ) -> Result<Self, MailError> {
+        let mut builder = if environ.email_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&environ.email_server)
+                .map_err(|e| MailError::Build(e.to_string()))?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&environ.email_server)
+        }
+        .port(environ.email_port);
+
+        if let (Some(username), Some(password)) = (&environ.email_username, &environ.email_password)
+        {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = environ
+            .email_from
+            .parse::<Mailbox>()
+            .map_err(|e| MailError::Address(e.to_string()))?;
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: OutgoingMail) -> Result<(), MailError> {
+        let to = mail
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| MailError::Address(e.to_string()))?;
+
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject);
+        if let Some(url) = mail.unsubscribe_url {
+            builder = builder
+                .raw_header(HeaderValue::new(
+                    HeaderName::new_from_ascii_str("List-Unsubscribe"),
+                    format!("<{}>", url),
+                ))
+                .raw_header(HeaderValue::new(
+                    HeaderName::new_from_ascii_str("List-Unsubscribe-Post"),
+                    String::from("List-Unsubscribe=One-Click"),
+                ));
+        }
+
+        let message = builder
+            .multipart(MultiPart::alternative_plain_html(mail.text, mail.html))
+            .map_err(|e| MailError::Build(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| MailError::Send(e.to_string()))
+    }
+}
+
+/// the smtp mailer, checked once at startup so bad settings stop the server before it listens
+pub fn from_environ() -> Result<Arc<dyn Mailer>, MailError> {
+    Environ::check_email().map_err(MailError::Build)?;
+    let mailer = SmtpMailer::new(&Environ::default())?;
+    Ok(Arc::new(mailer))
+}
+
+/// one subscriber per address; confirmations are counted by client and date
+pub async fn init(mongoc: &Client) {
+    let collection = Database::get_collection::<SubscriberEntity>(mongoc, "subscribers");
+    let indexes = [
+        IndexModel::builder()
+            .keys(doc! { "email": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("subscriber_email".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "client_hash": 1, "confirmation_sent_date": -1 })
+            .options(
+                IndexOptions::builder()
+                    .name("subscriber_client".to_string())
+                    .build(),
+            )
+            .build(),
+    ];
+    for index in indexes {
+        if let EntityResult::Error(e) = Database::create_index(collection.clone(), index).await {
+            error!("Failed to create a subscriber index: {:?}", e);
+        }
+    }
+}
+
+/// the address lower cased and trimmed, or none when it is not an email address
+pub fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    Address::from_str(&email).ok().map(|_| email)
+}
+
+fn render_mail(
+    handlebars: &Handlebars<'_>,
+    name: &str,
+    data: &serde_json::Value,
+) -> Result<(String, String), MailError> {
+    let html = handlebars
+        .render(&format!("{}-html", name), data)
+        .map_err(|e| MailError::Render(e.to_string()))?;
+    let text = handlebars
+        .render(&format!("{}-text", name), data)
+        .map_err(|e| MailError::Render(e.to_string()))?;
+    Ok((html, text))
+}
+
+async fn send_confirmation(
+    handlebars: &Handlebars<'_>,
+    mailer: &dyn Mailer,
+    subscriber_id: &str,
+    email: &str,
+) -> Result<(), MailError> {
+    let expires = date_ops::to_timestamp() + CONFIRM_TTL_SECONDS;
+    let data = json!({
+        "confirm_url": seo_ops::absolute_url(&auth::confirm_subscription_path(subscriber_id, expires)),
+        "site_name": seo_ops::SITE_NAME,
+        "hours": CONFIRM_TTL_SECONDS / 3600,
+    });
+    let (html, text) = render_mail(handlebars, "newsletter-confirm", &data)?;
+
+    mailer
+        .send(OutgoingMail {
+            to: email.to_string(),
+            subject: format!("Confirm your subscription to {}", seo_ops::SITE_NAME),
+            html,
+            text,
+            unsubscribe_url: None,
+        })
+        .await
+}
+
+async fn check_rate_limit(
+    collection: Collection<SubscriberEntity>,
+    client_hash: &str,
+) -> Result<(), SubscribeError> {
+    let since = date_ops::local_date() - chrono::Duration::minutes(RATE_LIMIT_WINDOW_MINUTES);
+    let filter = doc! {
+        "client_hash": client_hash,
+        "confirmation_sent_date": { "$gt": bson::to_bson(&since).unwrap() },
+    };
+    match Database::count(collection, filter).await {
+        EntityResult::Success(count) if count >= RATE_LIMIT_SUBSCRIBES => {
+            Err(SubscribeError::RateLimited)
+        }
+        EntityResult::Success(_) => Ok(()),
+        EntityResult::Error(e) => Err(SubscribeError::Failed(format!(
+            "Failed to count subscribers: {:?}",
+            e
+        ))),
+    }
+}
+
+/// records the address as pending and mails it a confirmation link; addresses that are
+/// already confirmed, or were sent a link moments ago, are left alone so the form neither
+/// tells who is subscribed nor mails anyone over and over
+pub async fn subscribe(
+    mongoc: &Client,
+    handlebars: &Handlebars<'_>,
+    mailer: &dyn Mailer,
+    email: &str,
+    client_hash: &str,
+) -> Result<(), SubscribeError> {
+    let collection = Database::get_collection::<SubscriberEntity>(mongoc, "subscribers");
+    check_rate_limit(collection.clone(), client_hash).await?;
+
+    let existing = match Database::find_one(collection.clone(), doc! { "email": email }).await {
+        EntityResult::Success(r) => Some(r),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => None,
+        EntityResult::Error(e) => {
+            return Err(SubscribeError::Failed(format!(
+                "Failed to find subscriber: {:?}",
+                e
+            )))
+        }
+    };
+
+    let now = date_ops::local_date();
+    let subscriber_id = match existing {
+        Some(subscriber) if subscriber.status == SubscriberStatus::Confirmed => {
+            info!("Subscriber {} is already confirmed", email);
+            return Ok(());
+        }
+        Some(subscriber) => {
+            let subscriber_id = subscriber._id.unwrap();
+            // matching on the cooldown makes two requests at once send a single link
+            let cooldown = now - chrono::Duration::minutes(RESEND_COOLDOWN_MINUTES);
+            let filter = doc! {
+                "_id": subscriber_id,
+                "$or": [
+                    { "confirmation_sent_date": null },
+                    { "confirmation_sent_date": { "$lte": bson::to_bson(&cooldown).unwrap() } },
+                ],
+            };
+            let update = doc! { "$set": {
+                "status": bson::to_bson(&SubscriberStatus::Pending).unwrap(),
+                "unsubscribed_date": null,
+                "confirmation_sent_date": bson::to_bson(&now).unwrap(),
+                "client_hash": client_hash,
+            }};
+            match Database::update_many(collection, filter, update).await {
+                EntityResult::Success(SuccessResultType::Updated(count)) if count == "0" => {
+                    info!("Subscriber {} was sent a confirmation moments ago", email);
+                    return Ok(());
+                }
+                EntityResult::Success(_) => {}
+                EntityResult::Error(e) => {
+                    return Err(SubscribeError::Failed(format!(
+                        "Failed to update subscriber: {:?}",
+                        e
+                    )))
+                }
+            }
+            subscriber_id.to_hex()
+        }
+        None => {
+            // the id is set up front, the confirmation link needs it
+            let subscriber_id = ObjectId::new();
+            let entity = SubscriberEntity {
+                _id: Some(subscriber_id),
+                email: email.to_string(),
+                status: SubscriberStatus::Pending,
+                created_date: now,
+                confirmed_date: None,
+                unsubscribed_date: None,
+                confirmation_sent_date: Some(now),
+                client_hash: Some(client_hash.to_string()),
+            };
+            match Database::create(collection, entity).await {
+                EntityResult::Success(_) => {}
+                // signed up by another request at the same moment, which sends the link
+                EntityResult::Error(DatabaseErrorType::Duplicate(..)) => return Ok(()),
+                EntityResult::Error(e) => {
+                    return Err(SubscribeError::Failed(format!(
+                        "Failed to add subscriber: {:?}",
+                        e
+                    )))
+                }
+            }
+            subscriber_id.to_hex()
+        }
+    };
+
+    send_confirmation(handlebars, mailer, &subscriber_id, email)
+        .await
+        .map_err(|e| SubscribeError::Failed(format!("Failed to send confirmation: {:?}", e)))
+}
+
+/// moves a subscriber from one status to another, false when it was not in the first one
+async fn change_status(
+    mongoc: &Client,
+    subscriber_id: &str,
+    from: &[SubscriberStatus],
+    to: SubscriberStatus,
+) -> EntityResult<bool> {
+    let object_id = match ObjectId::parse_str(subscriber_id) {
+        Ok(r) => r,
+        Err(_) => return EntityResult::Success(false),
+    };
+    let date_field = match to {
+        SubscriberStatus::Confirmed => "confirmed_date",
+        SubscriberStatus::Unsubscribed => "unsubscribed_date",
+        SubscriberStatus::Pending => "created_date",
+    };
+
+    let collection = Database::get_collection::<SubscriberEntity>(mongoc, "subscribers");
+    let filter = doc! {
+        "_id": object_id,
+        "status": { "$in": bson::to_bson(from).unwrap() },
+    };
+    let update = doc! { "$set": {
+        "status": bson::to_bson(&to).unwrap(),
+        date_field: bson::to_bson(&date_ops::local_date()).unwrap(),
+    }};
+    match Database::update_many(collection, filter, update).await {
+        EntityResult::Success(SuccessResultType::Updated(count)) => {
+            EntityResult::Success(count != "0")
+        }
+        EntityResult::Success(_) => EntityResult::Success(false),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+pub async fn confirm(mongoc: &Client, subscriber_id: &str) -> EntityResult<bool> {
+    change_status(
+        mongoc,
+        subscriber_id,
+        &[SubscriberStatus::Pending],
+        SubscriberStatus::Confirmed,
+    )
+    .await
+}
+
+pub async fn unsubscribe(mongoc: &Client, subscriber_id: &str) -> EntityResult<bool> {
+    change_status(
+        mongoc,
+        subscriber_id,
+        &[SubscriberStatus::Pending, SubscriberStatus::Confirmed],
+        SubscriberStatus::Unsubscribed,
+    )
+    .await
+}
+
+pub async fn last_digest(mongoc: &Client) -> EntityResult<Option<DigestEntity>> {
+    let collection = Database::get_collection::<DigestEntity>(mongoc, "digests");
+    let options = FindOptions::builder()
+        .sort(doc! { "sent_date": -1 })
+        .limit(1)
+        .build();
+    match Database::find_many(collection, doc! {}, options).await {
+        EntityResult::Success(r) => EntityResult::Success(r.into_iter().next()),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+/// published posts that no digest has listed yet, newest first
+pub async fn find_digest_posts(mongoc: &Client) -> EntityResult<Vec<PostResponseModel>> {
+    let since = match last_digest(mongoc).await {
+        EntityResult::Success(Some(digest)) => digest.sent_date,
+        EntityResult::Success(None) => {
+            date_ops::local_date() - chrono::Duration::days(FIRST_DIGEST_DAYS)
+        }
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let mut filter = PostEntity::published();
+    filter.insert(
+        "publish_date",
+        doc! {
+            "$gt": bson::to_bson(&since).unwrap(),
+            "$lte": bson::to_bson(&date_ops::local_date()).unwrap(),
+        },
+    );
+    let options = FindOptions::builder()
+        .sort(doc! { "publish_date": -1 })
+        .build();
+
+    let collection = Database::get_collection(mongoc, "posts");
+    let posts = match Database::find_many::<PostEntity>(collection, filter, options).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    let authors =
+        match Database::find_all::<AuthorEntity>(Database::get_collection(mongoc, "authors")).await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+    let tags = match Database::find_all::<TagEntity>(Database::get_collection(mongoc, "tags")).await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    EntityResult::Success(PostResponseModel::all(posts, &authors, &tags))
+}
+
+/// mails the new posts to every confirmed subscriber, each with their own unsubscribe link,
+/// and records the digest; failed deliveries are counted, not retried
+pub async fn send_digest(
+    mongoc: &Client,
+    handlebars: &Handlebars<'_>,
+    mailer: &dyn Mailer,
+) -> Result<DigestEntity, String> {
+    let posts = match find_digest_posts(mongoc).await {
+        EntityResult::Success(r) if r.is_empty() => {
+            return Err(String::from("No new posts since the last digest"))
+        }
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return Err(format!("Failed to find posts: {:?}", e)),
+    };
+
+    let collection = Database::get_collection::<SubscriberEntity>(mongoc, "subscribers");
+    let filter = doc! { "status": bson::to_bson(&SubscriberStatus::Confirmed).unwrap() };
+    let subscribers = match Database::find_many(collection, filter, FindOptions::default()).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return Err(format!("Failed to find subscribers: {:?}", e)),
+    };
+
+    let site_url = Environ::default().site_url;
+    let subject = match posts.len() {
+        1 => format!("New on {}: {}", seo_ops::SITE_NAME, posts[0].title),
+        n => format!("{} new posts on {}", n, seo_ops::SITE_NAME),
+    };
+
+    let mut digest = DigestEntity {
+        _id: None,
+        sent_date: date_ops::local_date(),
+        posts: posts.iter().map(|post| post.id.clone()).collect(),
+        recipients: 0,
+        failures: 0,
+    };
+
+    for subscriber in subscribers {
+        let unsubscribe_url =
+            seo_ops::absolute_url(&auth::unsubscribe_path(&subscriber._id.unwrap().to_hex()));
+        let data = json!({
+            "site_name": seo_ops::SITE_NAME,
+            "site_url": site_url,
+            "posts": posts,
+            "unsubscribe_url": unsubscribe_url,
+        });
+
+        let sent = match render_mail(handlebars, "newsletter-digest", &data) {
+            Ok((html, text)) => {
+                mailer
+                    .send(OutgoingMail {
+                        to: subscriber.email.clone(),
+                        subject: subject.clone(),
+                        html,
+                        text,
+                        unsubscribe_url: Some(unsubscribe_url),
+                    })
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => digest.recipients += 1,
+            Err(e) => {
+                error!("Failed to send digest to {}: {:?}", subscriber.email, e);
+                digest.failures += 1;
+            }
+        }
+    }
+
+    let collection = Database::get_collection::<DigestEntity>(mongoc, "digests");
+    if let EntityResult::Error(e) = Database::create(collection, digest.clone()).await {
+        error!("Failed to record digest: {:?}", e);
+    }
+
+    Ok(digest)
+}
+
+pub async fn count_subscribers(mongoc: &Client, status: SubscriberStatus) -> EntityResult<u64> {
+    let collection = Database::get_collection::<SubscriberEntity>(mongoc, "subscribers");
+    Database::count(
+        collection,
+        doc! { "status": bson::to_bson(&status).unwrap() },
+    )
+    .await
+}
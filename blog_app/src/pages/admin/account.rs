@@ -0,0 +1,268 @@
+use actix_session::Session;
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::{bson::oid::ObjectId, Client};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    auth::{self, SESSION_ACCOUNT_KEY, SESSION_CSRF_KEY},
+    entities::{
+        accounts::{AccountEntity, AccountRole},
+        blogs::AuthorEntity,
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{AccountRequestModel, AccountResponseModel, AuthorResponseModel, LoginRequestModel},
+    utils::{db_ops::Database, hash_ops, random_ops},
+};
+
+/// shortest password accepted for a new account
+const MIN_PASSWORD_LENGTH: usize = 12;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    pub next: Option<String>,
+}
+
+/// only admin pages are valid targets after signing in, anything else could be an open redirect
+fn next_location(next: Option<String>) -> String {
+    match next {
+        Some(next) if next.starts_with("/admin/") && !next.starts_with("/admin/login") => next,
+        _ => String::from("/admin/posts"),
+    }
+}
+
+fn render_login(
+    handlebars: &Handlebars<'_>,
+    session: &Session,
+    next: Option<String>,
+    error: Option<&str>,
+) -> Result<String, handlebars::RenderError> {
+    handlebars.render(
+        "login",
+        &json!({
+            "title": "Sign in",
+            "csrf_token": auth::csrf_token(session),
+            "next": next,
+            "error": error
+        }),
+    )
+}
+
+#[get("/admin/login")]
+pub async fn get_login(
+    handlebars: web::Data<Handlebars<'_>>,
+    session: Session,
+    query: web::Query<LoginQuery>,
+) -> impl Responder {
+    match render_login(&handlebars, &session, query.into_inner().next, None) {
+        Ok(body) => HttpResponse::Ok().body(body),
+        Err(e) => {
+            error!("Error rendering login template: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/admin/login")]
+pub async fn post_login(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    session: Session,
+    model: web::Form<LoginRequestModel>,
+) -> impl Responder {
+    let model = model.into_inner();
+
+    let expected = session.get::<String>(SESSION_CSRF_KEY).ok().flatten();
+    if expected.as_deref() != Some(model.csrf_token.as_str()) {
+        return HttpResponse::Forbidden().body("Invalid CSRF token");
+    }
+
+    let collection = Database::get_collection(&mongoc, "accounts");
+    let account = match Database::find_by::<AccountEntity, String>(
+        collection,
+        String::from("email"),
+        model.email.trim().to_lowercase(),
+    )
+    .await
+    {
+        EntityResult::Success(r) if hash_ops::verify(&model.password, &r.password_hash) => r,
+        EntityResult::Success(_) | EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            info!("Failed sign in for {}", model.email);
+            return match render_login(
+                &handlebars,
+                &session,
+                model.next,
+                Some("Wrong email or password"),
+            ) {
+                Ok(body) => HttpResponse::Unauthorized().body(body),
+                Err(e) => {
+                    error!("Error rendering login template: {:?}", e);
+                    HttpResponse::InternalServerError().finish()
+                }
+            };
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find account: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error signing in");
+        }
+    };
+
+    // a fresh session id and csrf token on every sign in
+    session.renew();
+    let csrf_token = random_ops::generate_guid(32);
+    if let Err(e) = session
+        .insert(SESSION_ACCOUNT_KEY, account._id.unwrap().to_string())
+        .and_then(|_| session.insert(SESSION_CSRF_KEY, &csrf_token))
+    {
+        error!("Failed to store session: {:?}", e);
+        return HttpResponse::InternalServerError().body("Error signing in");
+    }
+
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, next_location(model.next)))
+        .cookie(auth::csrf_cookie(csrf_token))
+        .finish()
+}
+
+#[post("/admin/logout")]
+pub async fn post_logout(session: Session) -> impl Responder {
+    session.purge();
+
+    let mut cookie = auth::csrf_cookie(String::new());
+    cookie.make_removal();
+
+    HttpResponse::Ok().cookie(cookie).body("Signed out")
+}
+
+#[get("/admin/accounts")]
+pub async fn get_account_list(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage accounts");
+    }
+
+    let collection = Database::get_collection(&mongoc, "accounts");
+    let accounts = match Database::find_all::<AccountEntity>(collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find accounts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding accounts");
+        }
+    };
+
+    let authors_collection = Database::get_collection(&mongoc, "authors");
+    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find authors: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors");
+        }
+    };
+
+    render_template!(
+        handlebars,
+        "account-list",
+        json!({
+            "title": "Accounts",
+            "accounts": accounts.into_iter().map(AccountResponseModel::from).collect::<Vec<_>>(),
+            "authors": AuthorResponseModel::from_vec(authors)
+        })
+    )
+}
+
+#[post("/admin/account")]
+pub async fn post_create_account(
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+    model: web::Json<AccountRequestModel>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage accounts");
+    }
+
+    let model = model.into_inner();
+    let email = model.email.trim().to_lowercase();
+    if !email.contains('@') {
+        return HttpResponse::BadRequest().body("A valid email is required");
+    }
+    if model.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return HttpResponse::BadRequest().body(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+
+    // author accounts write as an existing author
+    let author = match (model.role, model.author.filter(|a| !a.is_empty())) {
+        (AccountRole::Author, None) => {
+            return HttpResponse::BadRequest().body("Author accounts need an author")
+        }
+        (_, Some(author)) => {
+            if ObjectId::parse_str(&author).is_err() {
+                return HttpResponse::BadRequest().body("Unknown author");
+            }
+            let authors_collection = Database::get_collection(&mongoc, "authors");
+            match Database::find::<AuthorEntity>(authors_collection, author.clone()).await {
+                EntityResult::Success(_) => Some(author),
+                EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+                    return HttpResponse::BadRequest().body("Unknown author")
+                }
+                EntityResult::Error(e) => {
+                    error!("Failed to find author: {:?}", e);
+                    return HttpResponse::InternalServerError().body("Error finding author");
+                }
+            }
+        }
+        (AccountRole::Editor, None) => None,
+    };
+
+    let collection = Database::get_collection::<AccountEntity>(&mongoc, "accounts");
+    match Database::find_by::<AccountEntity, String>(
+        collection.clone(),
+        String::from("email"),
+        email.clone(),
+    )
+    .await
+    {
+        EntityResult::Success(_) => {
+            return HttpResponse::Conflict().body("An account with this email already exists")
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {}
+        EntityResult::Error(e) => {
+            error!("Failed to find account: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating account");
+        }
+    }
+
+    let password_hash = match hash_ops::cook_hash(&model.password) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to hash password: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating account");
+        }
+    };
+
+    let entity = AccountEntity {
+        _id: None,
+        email,
+        password_hash,
+        role: model.role,
+        author,
+    };
+
+    match Database::create(collection, entity).await {
+        EntityResult::Success(r) => {
+            info!("Account created {:?}", r);
+            HttpResponse::Ok().body("Account created")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to create account: {:?}", e);
+            HttpResponse::BadRequest().body("Error creating account")
+        }
+    }
+}
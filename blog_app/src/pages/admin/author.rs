@@ -6,17 +6,28 @@ use mongodb::Client;
 use serde_json::json;
 
 use crate::{
-    entities::{blogs::AuthorEntity, result_types::EntityResult},
-    models::{AuthorRequestModel, AuthorResponseModel},
+    content::ContentStore,
+    deletion::{self, PostReference},
+    entities::{accounts::AccountEntity, blogs::AuthorEntity, result_types::EntityResult},
+    models::{AuthorRequestModel, AuthorResponseModel, BulkRequestModel},
+    search::SearchIndex,
     utils::{
         db_ops::Database,
         file_ops,
         json_ops::{self, JsonOpsResult},
+        reference_ops::ReferenceCache,
+        related_ops::RelatedCache,
     },
 };
 
 #[get("/admin/author")]
-pub async fn get_create_author(handlebars: web::Data<Handlebars<'_>>) -> impl Responder {
+pub async fn get_create_author(
+    handlebars: web::Data<Handlebars<'_>>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add authors");
+    }
     render_template!(
         handlebars,
         "author-create",
@@ -31,6 +42,7 @@ pub async fn get_create_author(handlebars: web::Data<Handlebars<'_>>) -> impl Re
 pub async fn get_author_list(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let collection = Database::get_collection(&mongoc, "authors");
     match Database::find_all::<AuthorEntity>(collection).await {
@@ -41,6 +53,7 @@ pub async fn get_author_list(
                 "author-list",
                 json!({
                     "title": "All Authors",
+                    "bulk": account.is_editor(),
                     "authors": AuthorResponseModel::from_vec(r)
                 })
             )
@@ -57,8 +70,12 @@ pub async fn get_edit_author(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
     path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let author_id = path.into_inner();
+    if !account.can_act_as(&author_id) {
+        return HttpResponse::Forbidden().body("You can only edit your own author profile");
+    }
     let collection = Database::get_collection(&mongoc, "authors");
     match Database::find::<AuthorEntity>(collection, author_id).await {
         EntityResult::Success(r) => {
@@ -84,9 +101,15 @@ pub async fn get_edit_author(
 pub async fn post_create_author(
     model: web::Json<AuthorRequestModel>,
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     debug!("{:?}", model);
 
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add authors");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/author-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
@@ -96,6 +119,7 @@ pub async fn post_create_author(
             match Database::create(collection, model.to()).await {
                 EntityResult::Success(r) => {
                     info!("Author created {:?}", r);
+                    reference_cache.clear();
                     HttpResponse::Ok().body("Author created")
                 }
                 EntityResult::Error(e) => {
@@ -114,12 +138,18 @@ pub async fn post_create_author(
 #[post("/admin/author/{id}")]
 pub async fn post_edit_author(
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
     path: web::Path<String>,
     model: web::Json<AuthorRequestModel>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let author_id = path.into_inner();
     debug!("{:?}", model);
 
+    if !account.can_act_as(&author_id) {
+        return HttpResponse::Forbidden().body("You can only edit your own author profile");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/author-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
@@ -129,6 +159,7 @@ pub async fn post_edit_author(
             match Database::update(collection, model.to(), author_id).await {
                 EntityResult::Success(r) => {
                     info!("Author updated {:?}", r);
+                    reference_cache.clear();
                     HttpResponse::Ok().body("Author updated")
                 }
                 EntityResult::Error(e) => {
@@ -143,3 +174,34 @@ pub async fn post_edit_author(
         }
     }
 }
+
+/// archives, restores or deletes the authors ticked on the author list; authors still used by
+/// posts are only deleted when another author takes the posts over
+#[post("/admin/authors/bulk")]
+pub async fn post_bulk_authors(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
+    reference_cache: web::Data<ReferenceCache>,
+    model: web::Json<BulkRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    debug!("{:?}", model);
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can archive or delete authors");
+    }
+
+    let response = deletion::apply_to_references(
+        &mongoc,
+        search_index.get_ref(),
+        content.get_ref(),
+        PostReference::Author,
+        &model,
+    )
+    .await;
+    related_cache.clear();
+    reference_cache.clear();
+
+    HttpResponse::Ok().json(response)
+}
@@ -0,0 +1,109 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::{
+    bson::{self, doc},
+    options::FindOptions,
+    Client,
+};
+use serde_json::json;
+
+use crate::{
+    comments,
+    entities::{
+        accounts::AccountEntity,
+        blogs::PostEntity,
+        comments::{CommentEntity, CommentStatus},
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{CommentAdminModel, CommentQuery, CommentStatusRequestModel},
+    utils::db_ops::Database,
+};
+
+/// the moderation queue by default, approved or rejected comments on request
+#[get("/admin/comments")]
+pub async fn get_comment_list(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    query: web::Query<CommentQuery>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can moderate comments");
+    }
+    let status = query.status.unwrap_or(CommentStatus::Pending);
+
+    let collection = Database::get_collection(&mongoc, "comments");
+    let options = FindOptions::builder()
+        .sort(doc! { "created_date": -1 })
+        .build();
+    let entities = match Database::find_many::<CommentEntity>(
+        collection,
+        doc! { "status": bson::to_bson(&status).unwrap() },
+        options,
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find comments: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding comments");
+        }
+    };
+
+    let posts_collection = Database::get_collection(&mongoc, "posts");
+    let posts = match Database::find_all::<PostEntity>(posts_collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    let result = entities
+        .into_iter()
+        .map(|entity| {
+            let post = posts
+                .iter()
+                .find(|post| post._id.map(|id| id.to_hex()).as_deref() == Some(&entity.post_id));
+            CommentAdminModel::from(entity, post)
+        })
+        .collect::<Vec<_>>();
+
+    render_template!(
+        handlebars,
+        "comment-list",
+        json!({
+            "title": "Comments",
+            "status": status,
+            "comments": result
+        })
+    )
+}
+
+#[post("/admin/comment/{id}/status")]
+pub async fn post_comment_status(
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    model: web::Json<CommentStatusRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let comment_id = path.into_inner();
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can moderate comments");
+    }
+
+    match comments::set_status(&mongoc, &comment_id, model.status).await {
+        EntityResult::Success(r) => {
+            info!("Comment {} moderated {:?}", comment_id, r);
+            HttpResponse::Ok().body("Comment updated")
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            HttpResponse::NotFound().body("Comment not found")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to moderate comment: {:?}", e);
+            HttpResponse::InternalServerError().body("Error updating comment")
+        }
+    }
+}
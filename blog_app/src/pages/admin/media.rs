@@ -0,0 +1,114 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::Client;
+use serde_json::json;
+
+use crate::{
+    content::ContentStore,
+    entities::{accounts::AccountEntity, result_types::EntityResult},
+    media::{self, MediaAsset},
+    models::{PageQuery, PaginationModel},
+};
+
+/// assets per page of the media library
+const PAGE_SIZE: u64 = 24;
+
+#[get("/admin/media")]
+pub async fn get_media_list(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    query: web::Query<PageQuery>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    // the library lists what every post uses, drafts of other authors included
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage media");
+    }
+    let page = query.page();
+
+    let assets = match media::find_assets(&mongoc, content.get_ref()).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find media: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding media");
+        }
+    };
+
+    let total = assets.len() as u64;
+    let orphans = assets.iter().filter(|a| a.references.is_empty()).count();
+    let mut assets = assets
+        .into_iter()
+        .skip(usize::try_from((page - 1).saturating_mul(PAGE_SIZE)).unwrap_or(usize::MAX))
+        .take(PAGE_SIZE as usize)
+        .collect::<Vec<MediaAsset>>();
+    assets.iter_mut().for_each(MediaAsset::load_dimensions);
+
+    render_template!(
+        handlebars,
+        "media-list",
+        json!({
+            "title": "Media",
+            "assets": assets,
+            "orphans": orphans,
+            "base_url": "/admin/media",
+            "items": "uploads",
+            "pagination": PaginationModel::new(page, PAGE_SIZE, total)
+        })
+    )
+}
+
+/// deletes an asset, with all its sizes and formats, as long as nothing references it
+#[post("/admin/media/{key}/delete")]
+pub async fn post_delete_media(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can delete media");
+    }
+
+    let key = path.into_inner();
+    if !media::is_valid_key(&key) {
+        return HttpResponse::NotFound().body("Media not found");
+    }
+
+    // references are looked up again, the library page may be stale
+    let assets = match media::find_assets(&mongoc, content.get_ref()).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find media: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding media");
+        }
+    };
+    let asset = match assets.into_iter().find(|asset| asset.key == key) {
+        Some(r) => r,
+        None => return HttpResponse::NotFound().body("Media not found"),
+    };
+
+    if !asset.references.is_empty() {
+        return HttpResponse::Conflict().body(format!(
+            "Still used by {}",
+            asset
+                .references
+                .iter()
+                .map(|r| r.title.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    match asset.delete(content.get_ref()).await {
+        Ok(_) => {
+            info!("Media deleted {} by {}", asset.url, account.email);
+            HttpResponse::Ok().body("Media deleted")
+        }
+        Err(e) => {
+            error!("Failed to delete media {}: {:?}", asset.url, e);
+            HttpResponse::InternalServerError().body("Error deleting media")
+        }
+    }
+}
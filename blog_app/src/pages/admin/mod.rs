@@ -1,3 +1,10 @@
+pub mod account;
 pub mod author;
+pub mod comment;
+pub mod media;
+pub mod newsletter;
 pub mod post;
+pub mod redirect;
+pub mod revision;
+pub mod series;
 pub mod tag;
@@ -0,0 +1,104 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::Client;
+use serde_json::json;
+
+use crate::{
+    entities::{
+        accounts::AccountEntity, result_types::EntityResult, subscribers::SubscriberStatus,
+    },
+    newsletter::{self, Mailer},
+    utils::date_ops,
+};
+
+#[get("/admin/newsletter")]
+pub async fn get_newsletter(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can send the newsletter");
+    }
+
+    let mut counts = vec![];
+    for status in [
+        SubscriberStatus::Confirmed,
+        SubscriberStatus::Pending,
+        SubscriberStatus::Unsubscribed,
+    ] {
+        match newsletter::count_subscribers(&mongoc, status).await {
+            EntityResult::Success(r) => counts.push(r),
+            EntityResult::Error(e) => {
+                error!("Failed to count subscribers: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error counting subscribers");
+            }
+        }
+    }
+
+    let last_digest = match newsletter::last_digest(&mongoc).await {
+        EntityResult::Success(r) => r.map(|digest| {
+            json!({
+                "sent_date": date_ops::to_display_date(digest.sent_date),
+                "posts": digest.posts.len(),
+                "recipients": digest.recipients,
+                "failures": digest.failures,
+            })
+        }),
+        EntityResult::Error(e) => {
+            error!("Failed to find the last digest: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding the last digest");
+        }
+    };
+
+    let posts = match newsletter::find_digest_posts(&mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts for the digest: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    render_template!(
+        handlebars,
+        "newsletter-admin",
+        json!({
+            "title": "Newsletter",
+            "confirmed": counts[0],
+            "pending": counts[1],
+            "unsubscribed": counts[2],
+            "last_digest": last_digest,
+            "posts": posts
+        })
+    )
+}
+
+/// mails the posts published since the last digest to every confirmed subscriber
+#[post("/admin/newsletter/digest")]
+pub async fn post_send_digest(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    mailer: web::Data<dyn Mailer>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can send the newsletter");
+    }
+
+    match newsletter::send_digest(&mongoc, &handlebars, mailer.get_ref()).await {
+        Ok(digest) => {
+            info!("Digest sent {:?}", digest);
+            HttpResponse::Ok().body(format!(
+                "Digest of {} posts sent to {} subscribers, {} failed",
+                digest.posts.len(),
+                digest.recipients,
+                digest.failures
+            ))
+        }
+        Err(e) => {
+            error!("{}", e);
+            HttpResponse::BadRequest().body(e)
+        }
+    }
+}
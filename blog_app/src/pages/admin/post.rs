@@ -1,46 +1,72 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
 use handlebars::Handlebars;
 use log::{debug, error, info};
-use mongodb::Client;
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::FindOptions,
+    Client,
+};
 
 use serde_json::json;
 
 use crate::{
+    auth, comments,
+    content::{self, ContentStore},
+    deletion::{self, DeleteError},
     entities::{
-        blogs::{AuthorEntity, PostEntity, TagEntity},
-        result_types::EntityResult,
+        accounts::AccountEntity,
+        blogs::PostEntity,
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{
+        AuthorResponseModel, BulkAction, BulkFailureModel, BulkRequestModel, BulkResponseModel,
+        PostRequestModel, PostResponseModel, TagResponseModel,
     },
-    models::{AuthorResponseModel, PostRequestModel, PostResponseModel, TagResponseModel},
+    pages::admin::revision::record_revision,
+    permalinks,
+    search::{self, SearchIndex},
     utils::{
         date_ops,
         db_ops::Database,
+        environ::Environ,
         file_ops,
         json_ops::{self, JsonOpsResult},
+        reference_ops::ReferenceCache,
+        related_ops::RelatedCache,
     },
 };
 
+/// how long a shared preview link stays valid
+const PREVIEW_TTL_SECONDS: i64 = 72 * 60 * 60;
+
 #[get("/admin/post")]
 pub async fn get_create_post(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
-    let authors_collection = Database::get_collection(&mongoc, "authors");
-    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+    let references = match reference_cache.get(&mongoc).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find authors: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding authors");
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
         }
     };
 
-    let tags_collection = Database::get_collection(&mongoc, "tags");
-    let tags = match Database::find_all::<TagEntity>(tags_collection).await {
-        EntityResult::Success(r) => r,
-        EntityResult::Error(e) => {
-            error!("Failed to find tags: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding tags");
-        }
-    };
+    // archived authors and tags are not picked for new posts
+    let authors = references
+        .authors
+        .iter()
+        .filter(|author| author.archived_date.is_none())
+        .cloned()
+        .collect::<Vec<_>>();
+    let tags = references
+        .tags
+        .iter()
+        .filter(|tag| tag.archived_date.is_none())
+        .cloned()
+        .collect::<Vec<_>>();
     render_template!(
         handlebars,
         "post-create",
@@ -49,6 +75,8 @@ pub async fn get_create_post(
             "timestamp": date_ops::to_input_date(),
             "authors": AuthorResponseModel::from_vec(authors),
             "tags": TagResponseModel::from_vec(tags),
+            // authors start out writing as themselves
+            "author_ids": account.author.clone().unwrap_or_default(),
             "schema": file_ops::read_file("./assets/schema/post-schema.json").unwrap()
         })
     )
@@ -58,18 +86,80 @@ pub async fn get_create_post(
 pub async fn post_create_post(
     model: web::Json<PostRequestModel>,
     mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     debug!("{:?}", model);
 
+    if !account.can_write_as(&model.authors) {
+        return HttpResponse::Forbidden().body("You can only write posts you are an author of");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/post-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
     ) {
         JsonOpsResult::Success(_) => {
+            // the id is set up front so the first revision can point at the post
+            let post_id = ObjectId::new();
+            let mut entity = model.to();
+            entity._id = Some(post_id);
+            entity.permalink = match permalinks::unique(
+                &mongoc,
+                &permalinks::from_request(&model.permalink, &model.title),
+                Some(post_id),
+            )
+            .await
+            {
+                EntityResult::Success(r) => r,
+                EntityResult::Error(e) => {
+                    error!("Failed to find a permalink: {:?}", e);
+                    return HttpResponse::InternalServerError().body("Error finding a permalink");
+                }
+            };
+
             let collection = Database::get_collection(&mongoc, "posts");
-            match Database::create(collection, model.to()).await {
+            match Database::create(collection, entity.clone()).await {
                 EntityResult::Success(r) => {
                     info!("Post created {:?}", r);
+                    match record_revision(
+                        &mongoc,
+                        content.get_ref(),
+                        &post_id.to_hex(),
+                        &entity,
+                        &account,
+                        None,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Revision recorded {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to record revision: {:?}", e),
+                    }
+                    match search::index_post(
+                        search_index.get_ref(),
+                        content.get_ref(),
+                        &post_id.to_hex(),
+                        &entity,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Post indexed {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to index post: {:?}", e),
+                    }
+                    match permalinks::record_move(
+                        &mongoc,
+                        &post_id.to_hex(),
+                        None,
+                        &entity.permalink,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Permalink recorded {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to record permalink: {:?}", e),
+                    }
+                    related_cache.clear();
                     HttpResponse::Ok().body("Post created")
                 }
                 EntityResult::Error(e) => {
@@ -89,10 +179,25 @@ pub async fn post_create_post(
 pub async fn get_post_list(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
-    // get all posts
+    // all posts for editors, their own for authors
+    let filter = match (account.is_editor(), &account.author) {
+        (true, _) => doc! {},
+        (false, Some(author_id)) => doc! { "authors": author_id },
+        (false, None) => doc! { "_id": { "$exists": false } },
+    };
     let collection = Database::get_collection(&mongoc, "posts");
-    let posts = match Database::find_all::<PostEntity>(collection).await {
+    let posts = match Database::find_many::<PostEntity>(
+        collection,
+        filter,
+        FindOptions::builder()
+            .sort(doc! { "publish_date": -1 })
+            .build(),
+    )
+    .await
+    {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
             error!("Failed to find posts: {:?}", e);
@@ -100,25 +205,26 @@ pub async fn get_post_list(
         }
     };
 
-    let authors_collection = Database::get_collection(&mongoc, "authors");
-    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+    let references = match reference_cache.get(&mongoc).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find authors: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding authors");
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
         }
     };
 
-    let tags_collection = Database::get_collection(&mongoc, "tags");
-    let tags = match Database::find_all::<TagEntity>(tags_collection).await {
+    let comment_counts = match comments::count_approved(&mongoc).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find tags: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding tags");
+            error!("Failed to count comments: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error counting comments");
         }
     };
 
-    let result = PostResponseModel::all(posts, authors, tags);
+    let mut result = PostResponseModel::all(posts, &references.authors, &references.tags);
+    for post in result.iter_mut() {
+        post.comments = comment_counts.get(&post.id).copied().unwrap_or_default();
+    }
 
     render_template!(
         handlebars,
@@ -134,24 +240,16 @@ pub async fn get_post_list(
 pub async fn get_edit_post(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
     path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let post_id = path.into_inner();
-    let authors_collection = Database::get_collection(&mongoc, "authors");
-    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+    let references = match reference_cache.get(&mongoc).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find authors: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding authors");
-        }
-    };
-
-    let tags_collection = Database::get_collection(&mongoc, "tags");
-    let tags = match Database::find_all::<TagEntity>(tags_collection).await {
-        EntityResult::Success(r) => r,
-        EntityResult::Error(e) => {
-            error!("Failed to find tags: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding tags");
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
         }
     };
 
@@ -159,16 +257,39 @@ pub async fn get_edit_post(
     match Database::find::<PostEntity>(collection, post_id).await {
         EntityResult::Success(r) => {
             debug!("{:?}", r);
-            let post = PostResponseModel::combine(r.clone(), authors.clone(), tags.clone());
+            if !account.can_edit_post(&r) {
+                return HttpResponse::Forbidden().body("You can only edit your own posts");
+            }
+            let post = PostResponseModel::combine(r.clone(), &references.authors, &references.tags);
+            // archived authors and tags only stay on the posts that have them already
+            let authors = references
+                .authors
+                .iter()
+                .filter(|author| {
+                    author.archived_date.is_none()
+                        || r.authors.contains(&author._id.unwrap().to_hex())
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            let tags = references
+                .tags
+                .iter()
+                .filter(|tag| {
+                    tag.archived_date.is_none() || r.tags.contains(&tag._id.unwrap().to_hex())
+                })
+                .cloned()
+                .collect::<Vec<_>>();
             render_template!(
                 handlebars,
                 "post-edit",
                 json!({
                     "title": "Edit Post",
                     "post": post,
-                    "body_markdown": format!("/assets/markdowns/{}.md",post.body),
-                    "authors": AuthorResponseModel::from_vec(authors.clone()),
-                    "tags": TagResponseModel::from_vec(tags.clone()),
+                    "body_markdown": format!("/admin/markdown/{}", post.body),
+                    "authors": AuthorResponseModel::from_vec(authors),
+                    "author_ids": r.authors.join(","),
+                    "tag_ids": r.tags.join(","),
+                    "tags": TagResponseModel::from_vec(tags),
                     "timestamp": date_ops::to_input_date(),
                     "published_timestamp": date_ops::to_input_date_from(r.publish_date),
                     "schema": file_ops::read_file("./assets/schema/post-schema.json").unwrap()
@@ -185,21 +306,95 @@ pub async fn get_edit_post(
 #[post("/admin/post/{id}")]
 pub async fn post_edit_post(
     mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
     path: web::Path<String>,
     model: web::Json<PostRequestModel>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let post_id = path.into_inner();
     debug!("{:?}", model);
 
+    let collection = Database::get_collection(&mongoc, "posts");
+    let previous = match Database::find::<PostEntity>(collection, post_id.clone()).await {
+        EntityResult::Success(r)
+            if account.can_edit_post(&r) && account.can_write_as(&model.authors) =>
+        {
+            r
+        }
+        EntityResult::Success(_) => {
+            return HttpResponse::Forbidden().body("You can only edit your own posts");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find post: {:?}", e);
+            return HttpResponse::BadRequest().body("Error finding post");
+        }
+    };
+    if !account.can_change_authors(&previous, &model.authors) {
+        return HttpResponse::Forbidden()
+            .body("Only editors and the main author can remove or reorder authors");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/post-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
     ) {
         JsonOpsResult::Success(_) => {
+            let mut entity = model.to();
+            entity.permalink = match permalinks::unique(
+                &mongoc,
+                &permalinks::from_request(&model.permalink, &model.title),
+                previous._id,
+            )
+            .await
+            {
+                EntityResult::Success(r) => r,
+                EntityResult::Error(e) => {
+                    error!("Failed to find a permalink: {:?}", e);
+                    return HttpResponse::InternalServerError().body("Error finding a permalink");
+                }
+            };
             let collection = Database::get_collection(&mongoc, "posts");
-            match Database::update(collection, model.to(), post_id).await {
+            match Database::update(collection, entity.clone(), post_id.clone()).await {
                 EntityResult::Success(r) => {
                     info!("Post updated {:?}", r);
+                    match record_revision(
+                        &mongoc,
+                        content.get_ref(),
+                        &post_id,
+                        &entity,
+                        &account,
+                        None,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Revision recorded {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to record revision: {:?}", e),
+                    }
+                    match search::index_post(
+                        search_index.get_ref(),
+                        content.get_ref(),
+                        &post_id,
+                        &entity,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Post indexed {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to index post: {:?}", e),
+                    }
+                    match permalinks::record_move(
+                        &mongoc,
+                        &post_id,
+                        Some(&previous.permalink),
+                        &entity.permalink,
+                    )
+                    .await
+                    {
+                        EntityResult::Success(r) => info!("Permalink recorded {:?}", r),
+                        EntityResult::Error(e) => error!("Failed to record permalink: {:?}", e),
+                    }
+                    related_cache.clear();
                     HttpResponse::Ok().body("Post updated")
                 }
                 EntityResult::Error(e) => {
@@ -214,3 +409,134 @@ pub async fn post_edit_post(
         }
     }
 }
+
+/// archives, restores or deletes the posts ticked on the post list; deleting is for editors
+/// and for archived posts only
+#[post("/admin/posts/bulk")]
+pub async fn post_bulk_posts(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
+    model: web::Json<BulkRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    debug!("{:?}", model);
+    if model.action == BulkAction::Delete && !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can delete posts");
+    }
+
+    let mut response = BulkResponseModel::default();
+    for post_id in &model.ids {
+        let result = match model.action {
+            BulkAction::Archive | BulkAction::Restore => {
+                deletion::set_post_archived(
+                    &mongoc,
+                    search_index.get_ref(),
+                    content.get_ref(),
+                    &account,
+                    post_id,
+                    model.action == BulkAction::Archive,
+                )
+                .await
+            }
+            BulkAction::Delete => {
+                deletion::delete_post(&mongoc, search_index.get_ref(), post_id).await
+            }
+        };
+        match result {
+            Ok(()) => response.done += 1,
+            Err(e) => {
+                if let DeleteError::Database(e) = &e {
+                    error!("Failed to {:?} post {}: {:?}", model.action, post_id, e);
+                }
+                response.failed.push(BulkFailureModel {
+                    id: post_id.clone(),
+                    reason: e.reason(),
+                });
+            }
+        }
+    }
+    related_cache.clear();
+
+    HttpResponse::Ok().json(response)
+}
+
+/// a signed link that shows the post as it would be published, whatever its status
+#[post("/admin/post/{id}/preview")]
+pub async fn post_preview_link(
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let post_id = path.into_inner();
+    if ObjectId::parse_str(&post_id).is_err() {
+        return HttpResponse::NotFound().body("Post not found");
+    }
+
+    let collection = Database::get_collection(&mongoc, "posts");
+    match Database::find::<PostEntity>(collection, post_id.clone()).await {
+        EntityResult::Success(r) if account.can_edit_post(&r) => {}
+        EntityResult::Success(_) => {
+            return HttpResponse::Forbidden().body("You can only preview your own posts");
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            return HttpResponse::NotFound().body("Post not found");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find post: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding post");
+        }
+    }
+
+    let expires = date_ops::to_timestamp() + PREVIEW_TTL_SECONDS;
+    HttpResponse::Ok().json(json!({
+        "url": format!("{}{}", Environ::default().site_url, auth::preview_path(&post_id, expires)),
+        "expires": expires
+    }))
+}
+
+/// the markdown body of a post as a download, for editing offline
+#[get("/admin/markdown/{key}")]
+pub async fn get_post_markdown(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let key = path.into_inner();
+    if !content::is_valid_key(&key) {
+        return HttpResponse::NotFound().body("Markdown not found");
+    }
+
+    // drafts are only for their authors; markdown no post uses any more is for editors
+    let collection = Database::get_collection::<PostEntity>(&mongoc, "posts");
+    let allowed = match Database::find_one::<PostEntity>(collection, doc! { "body": &key }).await {
+        EntityResult::Success(post) => account.can_edit_post(&post),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => account.is_editor(),
+        EntityResult::Error(e) => {
+            error!("Failed to find post: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding post");
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().body("You can only download your own posts");
+    }
+
+    match content.read(&key).await {
+        EntityResult::Success(r) => HttpResponse::Ok()
+            .content_type("text/markdown; charset=utf-8")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.md\"", key),
+            ))
+            .body(r),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            HttpResponse::NotFound().body("Markdown not found")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find markdown: {:?}", e);
+            HttpResponse::InternalServerError().body("Error finding markdown")
+        }
+    }
+}
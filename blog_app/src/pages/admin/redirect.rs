@@ -0,0 +1,181 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{debug, error, info};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::FindOptions,
+    Client,
+};
+use serde_json::json;
+
+use crate::{
+    entities::{
+        accounts::AccountEntity,
+        blogs::PostEntity,
+        redirects::RedirectEntity,
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{RedirectRequestModel, RedirectResponseModel},
+    utils::{
+        db_ops::Database,
+        file_ops,
+        json_ops::{self, JsonOpsResult},
+    },
+};
+
+/// old permalinks of posts and the ones set up by hand, newest first
+#[get("/admin/redirects")]
+pub async fn get_redirect_list(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage redirects");
+    }
+
+    let collection = Database::get_collection(&mongoc, "redirects");
+    let options = FindOptions::builder()
+        .sort(doc! { "created_date": -1 })
+        .build();
+    let entities = match Database::find_many::<RedirectEntity>(collection, doc! {}, options).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find redirects: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding redirects");
+        }
+    };
+
+    let post_ids = entities
+        .iter()
+        .filter_map(|redirect| redirect.post_id.as_deref())
+        .filter_map(|id| ObjectId::parse_str(id).ok())
+        .collect::<Vec<_>>();
+    let posts_collection = Database::get_collection(&mongoc, "posts");
+    let posts = match Database::find_many::<PostEntity>(
+        posts_collection,
+        doc! { "_id": { "$in": post_ids } },
+        FindOptions::default(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    let result = entities
+        .into_iter()
+        .map(|entity| {
+            let post = posts.iter().find(|post| {
+                post._id.map(|id| id.to_hex()).as_deref() == entity.post_id.as_deref()
+            });
+            RedirectResponseModel::from(entity, post)
+        })
+        .collect::<Vec<_>>();
+
+    render_template!(
+        handlebars,
+        "redirect-list",
+        json!({
+            "title": "Redirects",
+            "redirects": result,
+            "schema": file_ops::read_file("./assets/schema/redirect-schema.json").unwrap()
+        })
+    )
+}
+
+#[post("/admin/redirects")]
+pub async fn post_create_redirect(
+    mongoc: web::Data<Client>,
+    model: web::Json<RedirectRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    debug!("{:?}", model);
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage redirects");
+    }
+
+    if let JsonOpsResult::Error(e) = json_ops::validate_json_text(
+        "./assets/schema/redirect-schema.json",
+        serde_json::to_string(&model).unwrap().as_str(),
+    ) {
+        error!("Failed to validate redirect: {:?}", e);
+        return HttpResponse::BadRequest().body("Error validating redirect");
+    }
+
+    let entity = model.to();
+    if entity.target.as_deref() == Some(format!("/blogs/{}", entity.permalink).as_str()) {
+        return HttpResponse::BadRequest().body("A redirect cannot point at itself");
+    }
+
+    // a post answers at its own permalink, a redirect there would never be used
+    let posts_collection = Database::get_collection::<PostEntity>(&mongoc, "posts");
+    match Database::count(posts_collection, doc! { "permalink": &entity.permalink }).await {
+        EntityResult::Success(0) => {}
+        EntityResult::Success(_) => {
+            return HttpResponse::Conflict().body("A post uses this permalink");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    }
+
+    let collection = Database::get_collection::<RedirectEntity>(&mongoc, "redirects");
+    match Database::count(collection.clone(), doc! { "permalink": &entity.permalink }).await {
+        EntityResult::Success(0) => {}
+        EntityResult::Success(_) => {
+            return HttpResponse::Conflict().body("This permalink redirects already");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find redirects: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding redirects");
+        }
+    }
+
+    match Database::create(collection, entity).await {
+        EntityResult::Success(r) => {
+            info!("Redirect created {:?}", r);
+            HttpResponse::Ok().body("Redirect created")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to create redirect: {:?}", e);
+            HttpResponse::BadRequest().body("Error creating redirect")
+        }
+    }
+}
+
+/// the old permalink answers with a 404 again afterwards
+#[post("/admin/redirect/{id}/delete")]
+pub async fn post_delete_redirect(
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let redirect_id = path.into_inner();
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage redirects");
+    }
+    let object_id = match ObjectId::parse_str(&redirect_id) {
+        Ok(r) => r,
+        Err(_) => return HttpResponse::NotFound().body("Redirect not found"),
+    };
+
+    let collection = Database::get_collection::<RedirectEntity>(&mongoc, "redirects");
+    match Database::delete_one(collection, doc! { "_id": object_id }).await {
+        EntityResult::Success(r) => {
+            info!("Redirect deleted {:?}", r);
+            HttpResponse::Ok().body("Redirect deleted")
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            HttpResponse::NotFound().body("Redirect not found")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to delete redirect: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting redirect")
+        }
+    }
+}
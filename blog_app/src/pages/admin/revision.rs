@@ -0,0 +1,321 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{error, info};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::{FindOptions, IndexOptions},
+    Client, IndexModel,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    content::ContentStore,
+    entities::{
+        accounts::AccountEntity,
+        blogs::PostEntity,
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+        revisions::RevisionEntity,
+    },
+    models::{FieldChangeModel, RevisionQuery, RevisionResponseModel},
+    permalinks,
+    search::{self, SearchIndex},
+    utils::{date_ops, db_ops::Database, diff_ops, related_ops::RelatedCache},
+};
+
+/// fields left out of the metadata comparison, the body is compared as markdown instead
+const IGNORED_FIELDS: [&str; 3] = ["_id", "body", "modified_date"];
+
+/// times a revision number is counted again when a concurrent save took it
+const MAX_ATTEMPTS: u32 = 5;
+
+/// a revision number is given out once per post, restore and compare look revisions up by it
+pub async fn init(mongoc: &Client) {
+    let collection = Database::get_collection::<RevisionEntity>(mongoc, "revisions");
+    let index = IndexModel::builder()
+        .keys(doc! { "post_id": 1, "number": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("revision_post_number".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build();
+    if let EntityResult::Error(e) = Database::create_index(collection, index).await {
+        error!("Failed to create the revision index: {:?}", e);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionPath {
+    pub id: String,
+    pub number: u64,
+}
+
+/// stores the post as just saved, together with its markdown, as the next revision of the post
+pub async fn record_revision(
+    mongoc: &Client,
+    content: &dyn ContentStore,
+    post_id: &str,
+    post: &PostEntity,
+    editor: &AccountEntity,
+    restored_from: Option<u64>,
+) -> EntityResult<SuccessResultType> {
+    let markdown = match content.read(&post.body).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let collection = Database::get_collection::<RevisionEntity>(mongoc, "revisions");
+    let mut attempt = 1;
+    loop {
+        let number = match Database::count(collection.clone(), doc! { "post_id": post_id }).await {
+            EntityResult::Success(r) => r + 1,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        let revision = RevisionEntity {
+            _id: None,
+            post_id: post_id.to_string(),
+            number,
+            post: post.clone(),
+            markdown: markdown.clone(),
+            editor: editor.email.clone(),
+            created_date: date_ops::local_date(),
+            restored_from,
+        };
+
+        // a save at the same moment took the number, count again
+        match Database::create(collection.clone(), revision).await {
+            EntityResult::Error(DatabaseErrorType::Duplicate(..)) if attempt < MAX_ATTEMPTS => {
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// metadata fields that differ between two posts, all of them when there is no older post
+fn changed_fields(old: Option<&PostEntity>, new: &PostEntity) -> Vec<FieldChangeModel> {
+    let display = |value: Option<&Value>| match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    };
+
+    let old = old
+        .and_then(|old| serde_json::to_value(old).ok())
+        .unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    let fields = match new.as_object() {
+        Some(r) => r.keys().cloned().collect::<Vec<_>>(),
+        None => return vec![],
+    };
+
+    fields
+        .into_iter()
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter(|field| old.get(field) != new.get(field))
+        .map(|field| FieldChangeModel {
+            old: display(old.get(&field)),
+            new: display(new.get(&field)),
+            field,
+        })
+        .collect()
+}
+
+/// the post if it exists and the account may edit it, otherwise the response to send
+async fn find_editable_post(
+    mongoc: &Client,
+    post_id: &str,
+    account: &AccountEntity,
+) -> Result<PostEntity, HttpResponse> {
+    if ObjectId::parse_str(post_id).is_err() {
+        return Err(HttpResponse::NotFound().body("Post not found"));
+    }
+
+    let collection = Database::get_collection(mongoc, "posts");
+    match Database::find::<PostEntity>(collection, post_id.to_string()).await {
+        EntityResult::Success(r) if account.can_edit_post(&r) => Ok(r),
+        EntityResult::Success(_) => {
+            Err(HttpResponse::Forbidden().body("You can only edit your own posts"))
+        }
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            Err(HttpResponse::NotFound().body("Post not found"))
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find post: {:?}", e);
+            Err(HttpResponse::InternalServerError().body("Error finding post"))
+        }
+    }
+}
+
+#[get("/admin/post/{id}/revisions")]
+pub async fn get_revisions(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    query: web::Query<RevisionQuery>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let post_id = path.into_inner();
+    let post = match find_editable_post(&mongoc, &post_id, &account).await {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    let collection = Database::get_collection::<RevisionEntity>(&mongoc, "revisions");
+    let revisions = match Database::find_many(
+        collection,
+        doc! { "post_id": &post_id },
+        FindOptions::builder().sort(doc! { "number": -1 }).build(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find revisions of post {}: {:?}", post_id, e);
+            return HttpResponse::InternalServerError().body("Error finding revisions");
+        }
+    };
+
+    // newest first, so the default comparison is the latest save against the one before it
+    let to = query
+        .to
+        .and_then(|number| revisions.iter().find(|r| r.number == number))
+        .or(revisions.first());
+    let from = match (query.from, to) {
+        (Some(number), _) => revisions.iter().find(|r| r.number == number),
+        (None, Some(to)) => revisions.iter().find(|r| r.number + 1 == to.number),
+        (None, None) => None,
+    };
+
+    let comparison = to.map(|to| {
+        let old_markdown = from.map(|r| r.markdown.as_str()).unwrap_or_default();
+        json!({
+            "from": from.map(|r| r.number),
+            "to": to.number,
+            "fields": changed_fields(from.map(|r| &r.post), &to.post),
+            "rows": diff_ops::side_by_side(old_markdown, &to.markdown)
+        })
+    });
+
+    render_template!(
+        handlebars,
+        "post-revisions",
+        json!({
+            "title": format!("History of {}", post.title),
+            "post_id": post_id,
+            "post_title": post.title,
+            "comparison": comparison,
+            "revisions": revisions.into_iter().map(RevisionResponseModel::from).collect::<Vec<_>>()
+        })
+    )
+}
+
+/// brings back the content and metadata of an earlier revision as a new save; the publishing
+/// status is left as it is so a restore never takes a post offline or live by accident
+#[post("/admin/post/{id}/revisions/{number}/restore")]
+pub async fn post_restore_revision(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
+    path: web::Path<RevisionPath>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let path = path.into_inner();
+    let current = match find_editable_post(&mongoc, &path.id, &account).await {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    let collection = Database::get_collection::<RevisionEntity>(&mongoc, "revisions");
+    let revision = match Database::find_one(
+        collection,
+        doc! { "post_id": &path.id, "number": path.number as i64 },
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            return HttpResponse::NotFound().body("Revision not found");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find revision: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding revision");
+        }
+    };
+
+    if !account.can_write_as(&revision.post.authors) {
+        return HttpResponse::Forbidden().body("You can only write posts as yourself");
+    }
+    if !account.can_change_authors(&current, &revision.post.authors) {
+        return HttpResponse::Forbidden()
+            .body("Only editors and the main author can remove or reorder authors");
+    }
+
+    // the body of the revision may have been removed since, store it again
+    let body = match content.write(&revision.markdown).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to restore post markdown: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error restoring post markdown");
+        }
+    };
+
+    let mut entity = revision.post;
+    entity._id = None;
+    entity.body = body;
+    entity.status = current.status;
+    entity.modified_date = date_ops::local_date();
+    // another post may have taken the permalink of the revision in the meantime
+    entity.permalink = match permalinks::unique(&mongoc, &entity.permalink, current._id).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find a permalink: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding a permalink");
+        }
+    };
+
+    let collection = Database::get_collection(&mongoc, "posts");
+    match Database::update(collection, entity.clone(), path.id.clone()).await {
+        EntityResult::Success(r) => info!("Post restored {:?}", r),
+        EntityResult::Error(e) => {
+            error!("Failed to restore post: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error restoring post");
+        }
+    }
+
+    match record_revision(
+        &mongoc,
+        content.get_ref(),
+        &path.id,
+        &entity,
+        &account,
+        Some(path.number),
+    )
+    .await
+    {
+        EntityResult::Success(r) => info!("Revision recorded {:?}", r),
+        EntityResult::Error(e) => error!("Failed to record revision: {:?}", e),
+    }
+    match search::index_post(search_index.get_ref(), content.get_ref(), &path.id, &entity).await {
+        EntityResult::Success(r) => info!("Post indexed {:?}", r),
+        EntityResult::Error(e) => error!("Failed to index post: {:?}", e),
+    }
+    match permalinks::record_move(
+        &mongoc,
+        &path.id,
+        Some(&current.permalink),
+        &entity.permalink,
+    )
+    .await
+    {
+        EntityResult::Success(r) => info!("Permalink recorded {:?}", r),
+        EntityResult::Error(e) => error!("Failed to record permalink: {:?}", e),
+    }
+    related_cache.clear();
+
+    HttpResponse::Ok().body(format!("Restored revision {}", path.number))
+}
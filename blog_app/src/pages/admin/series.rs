@@ -0,0 +1,254 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::{debug, error, info};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    Client,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    entities::{
+        accounts::AccountEntity,
+        blogs::{PostEntity, SeriesEntity},
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{SeriesRequestModel, SeriesResponseModel},
+    utils::{
+        db_ops::Database,
+        file_ops,
+        json_ops::{self, JsonOpsResult},
+        related_ops::RelatedCache,
+    },
+};
+
+/// every post as an id and title, for picking the parts of a series
+async fn find_post_choices(mongoc: &Client) -> EntityResult<Vec<Value>> {
+    let collection = Database::get_collection(mongoc, "posts");
+    match Database::find_all::<PostEntity>(collection).await {
+        EntityResult::Success(r) => EntityResult::Success(
+            r.into_iter()
+                .map(|post| json!({ "id": post._id.unwrap().to_hex(), "title": post.title }))
+                .collect(),
+        ),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+/// a post is part of one series at most, the one that claimed it first keeps it
+async fn find_claimed_post(
+    mongoc: &Client,
+    model: &SeriesRequestModel,
+    series_id: Option<ObjectId>,
+) -> EntityResult<Option<String>> {
+    let collection = Database::get_collection(mongoc, "series");
+    match Database::find_one::<SeriesEntity>(
+        collection,
+        doc! { "_id": { "$ne": series_id }, "posts": { "$in": &model.posts } },
+    )
+    .await
+    {
+        EntityResult::Success(r) => EntityResult::Success(Some(r.name)),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => EntityResult::Success(None),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+#[get("/admin/series")]
+pub async fn get_series_list(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can manage series");
+    }
+
+    let collection = Database::get_collection(&mongoc, "series");
+    match Database::find_all::<SeriesEntity>(collection).await {
+        EntityResult::Success(r) => render_template!(
+            handlebars,
+            "series-list",
+            json!({
+                "title": "All Series",
+                "series": SeriesResponseModel::from_vec(r)
+            })
+        ),
+        EntityResult::Error(e) => {
+            error!("Failed to list series: {:?}", e);
+            HttpResponse::InternalServerError().body("Error listing series")
+        }
+    }
+}
+
+#[get("/admin/series/new")]
+pub async fn get_create_series(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add series");
+    }
+
+    let posts = match find_post_choices(&mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    render_template!(
+        handlebars,
+        "series-create",
+        json!({
+            "title": "Add a new Series",
+            "posts": posts,
+            "schema": file_ops::read_file("./assets/schema/series-schema.json").unwrap()
+        })
+    )
+}
+
+#[post("/admin/series/new")]
+pub async fn post_create_series(
+    model: web::Json<SeriesRequestModel>,
+    mongoc: web::Data<Client>,
+    related_cache: web::Data<RelatedCache>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    debug!("{:?}", model);
+
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add series");
+    }
+
+    if let JsonOpsResult::Error(e) = json_ops::validate_json_text(
+        "./assets/schema/series-schema.json",
+        serde_json::to_string(&model).unwrap().as_str(),
+    ) {
+        error!("Failed to validate series: {:?}", e);
+        return HttpResponse::BadRequest().body("Error validating series");
+    }
+
+    match find_claimed_post(&mongoc, &model, None).await {
+        EntityResult::Success(None) => {}
+        EntityResult::Success(Some(name)) => {
+            return HttpResponse::Conflict().body(format!("A post is already part of {}", name));
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find series: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding series");
+        }
+    }
+
+    let collection = Database::get_collection(&mongoc, "series");
+    match Database::create(collection, model.to()).await {
+        EntityResult::Success(r) => {
+            info!("Series created {:?}", r);
+            related_cache.clear();
+            HttpResponse::Ok().body("Series created")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to create series: {:?}", e);
+            HttpResponse::BadRequest().body("Error creating series")
+        }
+    }
+}
+
+#[get("/admin/series/{id}")]
+pub async fn get_edit_series(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let series_id = path.into_inner();
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can edit series");
+    }
+    if ObjectId::parse_str(&series_id).is_err() {
+        return HttpResponse::NotFound().body("Series not found");
+    }
+
+    let posts = match find_post_choices(&mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    let collection = Database::get_collection(&mongoc, "series");
+    match Database::find::<SeriesEntity>(collection, series_id).await {
+        EntityResult::Success(r) => render_template!(
+            handlebars,
+            "series-edit",
+            json!({
+                "title": "Edit Series",
+                "series": SeriesResponseModel::from(r),
+                "posts": posts,
+                "schema": file_ops::read_file("./assets/schema/series-schema.json").unwrap()
+            })
+        ),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            HttpResponse::NotFound().body("Series not found")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find series: {:?}", e);
+            HttpResponse::InternalServerError().body("Error finding series")
+        }
+    }
+}
+
+#[post("/admin/series/{id}")]
+pub async fn post_edit_series(
+    mongoc: web::Data<Client>,
+    related_cache: web::Data<RelatedCache>,
+    path: web::Path<String>,
+    model: web::Json<SeriesRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    let series_id = path.into_inner();
+    debug!("{:?}", model);
+
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can edit series");
+    }
+    let object_id = match ObjectId::parse_str(&series_id) {
+        Ok(r) => r,
+        Err(_) => return HttpResponse::NotFound().body("Series not found"),
+    };
+
+    if let JsonOpsResult::Error(e) = json_ops::validate_json_text(
+        "./assets/schema/series-schema.json",
+        serde_json::to_string(&model).unwrap().as_str(),
+    ) {
+        error!("Failed to validate series: {:?}", e);
+        return HttpResponse::BadRequest().body("Error validating series");
+    }
+
+    match find_claimed_post(&mongoc, &model, Some(object_id)).await {
+        EntityResult::Success(None) => {}
+        EntityResult::Success(Some(name)) => {
+            return HttpResponse::Conflict().body(format!("A post is already part of {}", name));
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find series: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding series");
+        }
+    }
+
+    let collection = Database::get_collection(&mongoc, "series");
+    match Database::update(collection, model.to(), series_id).await {
+        EntityResult::Success(r) => {
+            info!("Series updated {:?}", r);
+            related_cache.clear();
+            HttpResponse::Ok().body("Series updated")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to update series: {:?}", e);
+            HttpResponse::BadRequest().body("Error updating series")
+        }
+    }
+}
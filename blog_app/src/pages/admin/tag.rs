@@ -6,17 +6,28 @@ use mongodb::Client;
 use serde_json::json;
 
 use crate::{
-    entities::{blogs::TagEntity, result_types::EntityResult},
-    models::{TagRequestModel, TagResponseModel},
+    content::ContentStore,
+    deletion::{self, PostReference},
+    entities::{accounts::AccountEntity, blogs::TagEntity, result_types::EntityResult},
+    models::{BulkRequestModel, TagRequestModel, TagResponseModel},
+    search::SearchIndex,
     utils::{
         db_ops::{self, Database},
         file_ops,
         json_ops::{self, JsonOpsResult},
+        reference_ops::ReferenceCache,
+        related_ops::RelatedCache,
     },
 };
 
 #[get("/admin/tag")]
-pub async fn get_create_tag(handlebars: web::Data<Handlebars<'_>>) -> impl Responder {
+pub async fn get_create_tag(
+    handlebars: web::Data<Handlebars<'_>>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add tags");
+    }
     render_template!(
         handlebars,
         "tag-create",
@@ -31,6 +42,7 @@ pub async fn get_create_tag(handlebars: web::Data<Handlebars<'_>>) -> impl Respo
 pub async fn get_tag_list(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let collection = Database::get_collection(&mongoc, "tags");
     match db_ops::Database::find_all::<TagEntity>(collection).await {
@@ -41,6 +53,7 @@ pub async fn get_tag_list(
                 "tag-list",
                 json!({
                     "title": "All Tags",
+                    "bulk": account.is_editor(),
                     "tags": TagResponseModel::from_vec(r)
                 })
             )
@@ -57,8 +70,12 @@ pub async fn get_edit_tag(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
     path: web::Path<String>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let tag_id = path.into_inner();
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can edit tags");
+    }
     let collection = Database::get_collection(&mongoc, "tags");
     match db_ops::Database::find::<TagEntity>(collection, tag_id).await {
         EntityResult::Success(r) => {
@@ -84,9 +101,15 @@ pub async fn get_edit_tag(
 pub async fn post_create_tag(
     model: web::Json<TagRequestModel>,
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     debug!("{:?}", model);
 
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can add tags");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/tag-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
@@ -96,6 +119,7 @@ pub async fn post_create_tag(
             match db_ops::Database::create(collection, model.to()).await {
                 EntityResult::Success(r) => {
                     info!("Tag created {:?}", r);
+                    reference_cache.clear();
                     HttpResponse::Ok().body("Tag created")
                 }
                 EntityResult::Error(e) => {
@@ -114,12 +138,18 @@ pub async fn post_create_tag(
 #[post("/admin/tag/{id}")]
 pub async fn post_edit_tag(
     mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
     path: web::Path<String>,
     model: web::Json<TagRequestModel>,
+    account: web::ReqData<AccountEntity>,
 ) -> impl Responder {
     let tag_id = path.into_inner();
     debug!("{:?}", model);
 
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can edit tags");
+    }
+
     match json_ops::validate_json_text(
         "./assets/schema/tag-schema.json",
         serde_json::to_string(&model).unwrap().as_str(),
@@ -129,6 +159,7 @@ pub async fn post_edit_tag(
             match Database::update(collection, model.to(), tag_id).await {
                 EntityResult::Success(r) => {
                     info!("Tag updated {:?}", r);
+                    reference_cache.clear();
                     HttpResponse::Ok().body("Tag updated")
                 }
                 EntityResult::Error(e) => {
@@ -143,3 +174,34 @@ pub async fn post_edit_tag(
         }
     }
 }
+
+/// archives, restores or deletes the tags ticked on the tag list; tags still used by
+/// posts are only deleted when another tag takes the posts over
+#[post("/admin/tags/bulk")]
+pub async fn post_bulk_tags(
+    mongoc: web::Data<Client>,
+    content: web::Data<dyn ContentStore>,
+    search_index: web::Data<dyn SearchIndex>,
+    related_cache: web::Data<RelatedCache>,
+    reference_cache: web::Data<ReferenceCache>,
+    model: web::Json<BulkRequestModel>,
+    account: web::ReqData<AccountEntity>,
+) -> impl Responder {
+    debug!("{:?}", model);
+    if !account.is_editor() {
+        return HttpResponse::Forbidden().body("Only editors can archive or delete tags");
+    }
+
+    let response = deletion::apply_to_references(
+        &mongoc,
+        search_index.get_ref(),
+        content.get_ref(),
+        PostReference::Tag,
+        &model,
+    )
+    .await;
+    related_cache.clear();
+    reference_cache.clear();
+
+    HttpResponse::Ok().json(response)
+}
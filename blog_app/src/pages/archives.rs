@@ -0,0 +1,217 @@
+use actix_web::{get, http::header, routes, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::error;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::FindOptions,
+    Client,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    entities::{
+        blogs::{AuthorEntity, PostEntity},
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{
+        AuthorResponseModel, PageQuery, PaginationModel, PostResponseModel, TagResponseModel,
+    },
+    utils::{db_ops::Database, reference_ops::ReferenceCache, seo_ops, string_ops},
+};
+
+/// posts per page of an archive
+const PAGE_SIZE: u64 = 12;
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorPath {
+    pub id: String,
+    pub slug: Option<String>,
+}
+
+fn redirect(location: String) -> HttpResponse {
+    HttpResponse::MovedPermanently()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// absolute url of an archive page, the first page has no page parameter
+fn canonical_url(path: &str, page: u64) -> String {
+    match page {
+        1 => seo_ops::absolute_url(path),
+        _ => seo_ops::absolute_url(&format!("{}?page={}", path, page)),
+    }
+}
+
+/// one page of published posts matching the filter, newest first
+async fn find_archive_page(
+    mongoc: &Client,
+    reference_cache: &ReferenceCache,
+    filter: Document,
+    page: u64,
+) -> EntityResult<(Vec<PostResponseModel>, PaginationModel)> {
+    let references = match reference_cache.get(mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let mut published = PostEntity::published();
+    published.extend(filter);
+
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let total = match Database::count(collection.clone(), published.clone()).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let posts = match Database::find_many(
+        collection,
+        published,
+        FindOptions::builder()
+            .sort(doc! { "publish_date": -1 })
+            .skip((page - 1).saturating_mul(PAGE_SIZE))
+            .limit(PAGE_SIZE as i64)
+            .build(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    EntityResult::Success((
+        PostResponseModel::all(posts, &references.authors, &references.tags),
+        PaginationModel::new(page, PAGE_SIZE, total),
+    ))
+}
+
+#[get("/tags/{slug}")]
+pub async fn get_tag_archive(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+) -> impl Responder {
+    let slug = path.into_inner();
+    let page = query.page();
+
+    let references = match reference_cache.get(&mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding tags");
+        }
+    };
+
+    // tag names are matched by slug so /tags/Data%20Structures lands on /tags/data-structures
+    let tag = match references
+        .tags
+        .iter()
+        .find(|tag| tag.slug() == string_ops::slugify(&slug))
+    {
+        Some(tag) => tag.clone(),
+        None => return HttpResponse::NotFound().body("Tag not found"),
+    };
+
+    if tag.slug() != slug {
+        return redirect(tag.url());
+    }
+
+    let (posts, pagination) = match find_archive_page(
+        &mongoc,
+        &reference_cache,
+        doc! { "tags": tag._id.unwrap().to_string() },
+        page,
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts of tag {}: {:?}", tag.name, e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    let canonical_url = canonical_url(&tag.url(), page);
+    render_template!(
+        handlebars,
+        "tag-archive",
+        json!({
+            "title": tag.name,
+            "description": tag.description,
+            "canonical_url": canonical_url,
+            "meta": seo_ops::tag_meta(&tag, &canonical_url),
+            "base_url": tag.url(),
+            "tag": TagResponseModel::from(tag),
+            "posts": posts,
+            "pagination": pagination
+        })
+    )
+}
+
+#[routes]
+#[get("/authors/{id}")]
+#[get("/authors/{id}/{slug}")]
+pub async fn get_author_archive(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    path: web::Path<AuthorPath>,
+    query: web::Query<PageQuery>,
+) -> impl Responder {
+    let path = path.into_inner();
+    let page = query.page();
+
+    if ObjectId::parse_str(&path.id).is_err() {
+        return HttpResponse::NotFound().body("Author not found");
+    }
+
+    let authors_collection = Database::get_collection(&mongoc, "authors");
+    let author = match Database::find::<AuthorEntity>(authors_collection, path.id.clone()).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            return HttpResponse::NotFound().body("Author not found")
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find author: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding author");
+        }
+    };
+
+    // a missing or stale slug, e.g. after a name change, redirects to the canonical url
+    if path.slug.as_deref() != Some(author.slug().as_str()) {
+        return redirect(author.url());
+    }
+
+    let (posts, pagination) = match find_archive_page(
+        &mongoc,
+        &reference_cache,
+        doc! { "authors": path.id.clone() },
+        page,
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts of author {}: {:?}", path.id, e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    let canonical_url = canonical_url(&author.url(), page);
+    render_template!(
+        handlebars,
+        "author-archive",
+        json!({
+            "title": format!("{} {}", author.first_name, author.last_name),
+            "description": author.bio,
+            "canonical_url": canonical_url,
+            "meta": seo_ops::author_meta(&author, &canonical_url),
+            "base_url": author.url(),
+            "author": AuthorResponseModel::from(author),
+            "posts": posts,
+            "pagination": pagination
+        })
+    )
+}
@@ -1,63 +1,78 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{
+    get,
+    http::header::{self, ContentType},
+    web, HttpResponse, Responder,
+};
 use handlebars::Handlebars;
 use log::error;
-use mongodb::Client;
-
-// use pulldown_cmark::{html, Parser};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::FindOptions,
+    Client,
+};
+use serde::Deserialize;
 use serde_json::json;
 
 use crate::{
+    auth, comments,
+    content::ContentStore,
     entities::{
-        blogs::{AuthorEntity, PostEntity, TagEntity},
-        result_types::EntityResult,
+        blogs::{PostEntity, SeriesEntity},
+        result_types::{DatabaseErrorType, EntityResult},
+    },
+    models::{
+        PageQuery, PaginationModel, PostResponseModel, SeriesNavigationModel, TagResponseModel,
+    },
+    permalinks,
+    utils::{
+        db_ops::Database,
+        markdown_ops::{self, MarkdownCache},
+        reference_ops::ReferenceCache,
+        related_ops::{self, RelatedCache, RelatedCandidate},
+        seo_ops::{self, ReadingStats},
     },
-    models::PostResponseModel,
-    utils::{db_ops::Database, file_ops, string_ops},
 };
 
+/// posts per page of the latest posts listing
+const PAGE_SIZE: u64 = 8;
+/// posts shown per tag in the by-tags section, including the tag's lead post
+const TAG_SECTION_SIZE: i64 = 4;
+
 #[get("/")]
 pub async fn get_posts(
-    handlebars: web::Data<Handlebars<'_>>,
-    _mongoc: web::Data<Client>,
-) -> impl Responder {
-    render_template!(
-        handlebars,
-        "blog-home",
-        json!({"title": "Computer Science Coach", "model": "world"})
-    )
-}
-
-#[get("/blogs/{permalink}")]
-pub async fn get_post(
     handlebars: web::Data<Handlebars<'_>>,
     mongoc: web::Data<Client>,
-    path: web::Path<String>,
+    reference_cache: web::Data<ReferenceCache>,
+    query: web::Query<PageQuery>,
 ) -> impl Responder {
-    let permalink = path.into_inner();
+    let page = query.page();
 
-    let authors_collection = Database::get_collection(&mongoc, "authors");
-    let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+    let references = match reference_cache.get(&mongoc).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find authors: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding authors");
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
         }
     };
+    let (authors, tags) = (&references.authors, &references.tags);
 
-    let tags_collection = Database::get_collection(&mongoc, "tags");
-    let tags = match Database::find_all::<TagEntity>(tags_collection).await {
+    let collection = Database::get_collection::<PostEntity>(&mongoc, "posts");
+    let total = match Database::count(collection.clone(), PostEntity::published()).await {
         EntityResult::Success(r) => r,
         EntityResult::Error(e) => {
-            error!("Failed to find tags: {:?}", e);
-            return HttpResponse::InternalServerError().body("Error finding tags");
+            error!("Failed to count posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error counting posts");
         }
     };
 
-    let collection = Database::get_collection(&mongoc, "posts");
-    let post = match Database::find_by::<PostEntity, String>(
-        collection,
-        String::from("permalink"),
-        permalink,
+    let latest_posts = match Database::find_many(
+        collection.clone(),
+        PostEntity::published(),
+        FindOptions::builder()
+            .sort(doc! { "publish_date": -1 })
+            .skip((page - 1).saturating_mul(PAGE_SIZE))
+            .limit(PAGE_SIZE as i64)
+            .build(),
     )
     .await
     {
@@ -68,22 +83,294 @@ pub async fn get_post(
         }
     };
 
-    let model = PostResponseModel::combine(post, authors, tags);
+    let mut model = json!({
+        "latest_posts": PostResponseModel::all(latest_posts, authors, tags),
+        "pagination": PaginationModel::new(page, PAGE_SIZE, total),
+    });
+
+    // hero, featured and by-tags sections only head the first page
+    if page == 1 {
+        let mut featured_filter = PostEntity::published();
+        featured_filter.insert("featured", true);
+        let featured_posts = match Database::find_many(
+            collection.clone(),
+            featured_filter,
+            FindOptions::builder()
+                .sort(doc! { "publish_date": -1 })
+                .limit(3)
+                .build(),
+        )
+        .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to find featured posts: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error finding featured posts");
+            }
+        };
+
+        let recent_posts = match Database::find_many(
+            collection.clone(),
+            PostEntity::published(),
+            FindOptions::builder()
+                .sort(doc! { "publish_date": -1 })
+                .limit(4)
+                .build(),
+        )
+        .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to find posts: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error finding posts");
+            }
+        };
+
+        // the newest featured post leads the page, falling back to the newest post
+        let mut featured_posts = PostResponseModel::all(featured_posts, authors, tags);
+        let mut recent_posts = PostResponseModel::all(recent_posts, authors, tags);
+        let hero_post = if !featured_posts.is_empty() {
+            Some(featured_posts.remove(0))
+        } else if !recent_posts.is_empty() {
+            Some(recent_posts.remove(0))
+        } else {
+            None
+        };
+        if let Some(hero_post) = &hero_post {
+            recent_posts.retain(|post| post.id != hero_post.id);
+        }
+        recent_posts.truncate(3);
+
+        let mut posts_by_tags = vec![];
+        for tag in tags.iter() {
+            let mut tag_filter = PostEntity::published();
+            tag_filter.insert("tags", tag._id.unwrap().to_string());
+            let tag_posts = match Database::find_many(
+                collection.clone(),
+                tag_filter,
+                FindOptions::builder()
+                    .sort(doc! { "featured": -1, "publish_date": -1 })
+                    .limit(TAG_SECTION_SIZE)
+                    .build(),
+            )
+            .await
+            {
+                EntityResult::Success(r) => r,
+                EntityResult::Error(e) => {
+                    error!("Failed to find posts of tag {}: {:?}", tag.name, e);
+                    return HttpResponse::InternalServerError().body("Error finding posts");
+                }
+            };
+
+            let mut tag_posts = PostResponseModel::all(tag_posts, authors, tags);
+            if tag_posts.is_empty() {
+                continue;
+            }
+            let featured_post = tag_posts.remove(0);
+            posts_by_tags.push(json!({
+                "tag": TagResponseModel::from(tag.clone()),
+                "featured_post": featured_post,
+                "posts": tag_posts,
+            }));
+        }
+
+        model["hero_post"] = json!(hero_post);
+        model["hero_posts"] = json!(recent_posts);
+        model["featured_posts"] = json!(featured_posts);
+        model["posts_by_tags"] = json!(posts_by_tags);
+    }
 
-    let post_markdown =
-        match file_ops::read_file(format!("./assets/markdowns/{}.md", model.body).as_str()) {
-            Ok(r) => r,
-            Err(e) => {
-                error!("Failed to find post markdown: {:?}", e);
-                return HttpResponse::InternalServerError().body("Error finding post markdown");
+    render_template!(
+        handlebars,
+        "blog-home",
+        json!({"title": "Computer Science Coach", "model": model})
+    )
+}
+
+/// stylesheet for the syntax highlighted code blocks of rendered posts
+#[get("/styles/highlight.css")]
+pub async fn get_highlight_css() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(ContentType(mime_guess::mime::TEXT_CSS))
+        .body(markdown_ops::highlight_css())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// the published posts most related to the post, ranked once and cached
+async fn find_related_posts(
+    mongoc: &Client,
+    related_cache: &RelatedCache,
+    post: &PostEntity,
+) -> EntityResult<Vec<PostEntity>> {
+    let post_id = post._id.unwrap().to_hex();
+    if let Some(related) = related_cache.get(&post_id) {
+        return EntityResult::Success(related);
+    }
+
+    let collection = Database::get_collection(mongoc, "posts");
+    let candidates = match Database::find_many::<RelatedCandidate>(
+        collection,
+        PostEntity::published(),
+        FindOptions::builder()
+            .projection(RelatedCandidate::projection())
+            .build(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let ranked = related_ops::rank(post, candidates);
+    let collection = Database::get_collection(mongoc, "posts");
+    let mut related = match Database::find_many::<PostEntity>(
+        collection,
+        doc! { "_id": { "$in": &ranked } },
+        FindOptions::default(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    related.sort_by_key(|related| ranked.iter().position(|id| Some(*id) == related._id));
+
+    related_cache.insert(post_id, related.clone());
+    EntityResult::Success(related)
+}
+
+/// the series the post belongs to, if any, with the parts readers can open
+async fn find_series_navigation(
+    mongoc: &Client,
+    post: &PostEntity,
+) -> EntityResult<Option<SeriesNavigationModel>> {
+    let post_id = post._id.unwrap().to_hex();
+
+    let series_collection = Database::get_collection(mongoc, "series");
+    let series =
+        match Database::find_one::<SeriesEntity>(series_collection, doc! { "posts": &post_id })
+            .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+                return EntityResult::Success(None)
             }
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+    // unpublished parts are left out, except the post itself when it is previewed
+    let ids = series
+        .posts
+        .iter()
+        .filter_map(|id| ObjectId::parse_str(id).ok())
+        .collect::<Vec<_>>();
+    let filter = doc! {
+        "_id": { "$in": ids },
+        "$or": [PostEntity::published(), doc! { "_id": post._id }],
+    };
+
+    let collection = Database::get_collection(mongoc, "posts");
+    let mut posts =
+        match Database::find_many::<PostEntity>(collection, filter, FindOptions::default()).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
         };
+    posts.sort_by_key(|part| {
+        let part_id = part._id.unwrap().to_hex();
+        series.posts.iter().position(|id| *id == part_id)
+    });
+
+    EntityResult::Success(Some(SeriesNavigationModel::new(&series, &posts, &post_id)))
+}
 
-    // let parser = Parser::new(post_markdown.as_str());
-    // // Allocate a string for the HTML output
-    // let mut html_output = String::new();
-    // // Convert markdown to HTML
-    // html::push_html(&mut html_output, parser);
+/// renders a post with its authors, tags, series, related posts, comments and markdown body;
+/// previews are kept out of search engines
+#[allow(clippy::too_many_arguments)]
+async fn render_post(
+    handlebars: &Handlebars<'_>,
+    mongoc: &Client,
+    markdown_cache: &MarkdownCache,
+    related_cache: &RelatedCache,
+    reference_cache: &ReferenceCache,
+    content: &dyn ContentStore,
+    post: PostEntity,
+    preview: bool,
+) -> HttpResponse {
+    let references = match reference_cache.get(mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
+        }
+    };
+    let (authors, tags) = (&references.authors, &references.tags);
+
+    // related posts and series links are extras, a post still renders without them
+    let related = match find_related_posts(mongoc, related_cache, &post).await {
+        EntityResult::Success(r) => PostResponseModel::all(r, authors, tags),
+        EntityResult::Error(e) => {
+            error!("Failed to find related posts: {:?}", e);
+            vec![]
+        }
+    };
+    // previews are not open for comments yet
+    let (comment_thread, comment_count) = match preview {
+        true => (vec![], 0),
+        false => match comments::find_thread(mongoc, &post._id.unwrap().to_hex()).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to find comments of post: {:?}", e);
+                (vec![], 0)
+            }
+        },
+    };
+    let series = match find_series_navigation(mongoc, &post).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find series of post: {:?}", e);
+            None
+        }
+    };
+
+    let post_markdown = match content.read(&post.body).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            error!(
+                "Post {} has no markdown body {}",
+                post._id.unwrap(),
+                post.body
+            );
+            return HttpResponse::NotFound().body("Post not found");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find post markdown: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding post markdown");
+        }
+    };
+
+    let rendered = markdown_cache.render(&post_markdown);
+    let reading = ReadingStats::new(rendered.word_count);
+
+    let canonical_url = seo_ops::absolute_url(&format!("/blogs/{}", post.permalink));
+    // ids of authors or tags deleted since are left out
+    let post_authors = post
+        .authors
+        .iter()
+        .filter_map(|id| references.author(id))
+        .collect::<Vec<_>>();
+    let post_tags = post
+        .tags
+        .iter()
+        .filter_map(|id| references.tag(id))
+        .collect::<Vec<_>>();
+    let meta = seo_ops::post_meta(&post, &post_authors, &post_tags, &canonical_url, reading);
+
+    let model = PostResponseModel::combine(post, authors, tags);
 
     render_template!(
         handlebars,
@@ -91,8 +378,112 @@ pub async fn get_post(
         json!({
             "title": model.title,
             "description":  model.description,
-            "body": string_ops::escape(&post_markdown),
+            "canonical_url": canonical_url,
+            "meta": meta,
+            "noindex": preview,
+            "preview": preview,
+            "body": rendered.html,
+            "toc": rendered.toc,
+            "reading": reading,
+            "series": series,
+            "related": related,
+            "comments": comment_thread,
+            "comment_count": comment_count,
             "model": model
         })
     )
 }
+
+#[get("/blogs/{permalink}")]
+pub async fn get_post(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    markdown_cache: web::Data<MarkdownCache>,
+    related_cache: web::Data<RelatedCache>,
+    reference_cache: web::Data<ReferenceCache>,
+    content: web::Data<dyn ContentStore>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let permalink = path.into_inner();
+
+    let mut filter = PostEntity::published();
+    filter.insert("permalink", &permalink);
+
+    let collection = Database::get_collection(&mongoc, "posts");
+    let post = match Database::find_one::<PostEntity>(collection, filter).await {
+        EntityResult::Success(r) => r,
+        // an old permalink of a post, or one set up by hand
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            return match permalinks::find_redirect(&mongoc, &permalink).await {
+                EntityResult::Success(Some(location)) => HttpResponse::MovedPermanently()
+                    .insert_header((header::LOCATION, location))
+                    .finish(),
+                EntityResult::Success(None) => HttpResponse::NotFound().body("Post not found"),
+                EntityResult::Error(e) => {
+                    error!("Failed to find redirect: {:?}", e);
+                    HttpResponse::InternalServerError().body("Error finding posts")
+                }
+            };
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    render_post(
+        &handlebars,
+        &mongoc,
+        &markdown_cache,
+        &related_cache,
+        &reference_cache,
+        content.get_ref(),
+        post,
+        false,
+    )
+    .await
+}
+
+/// drafts and scheduled posts shared through a signed, expiring link
+#[get("/preview/{id}")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_post_preview(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    markdown_cache: web::Data<MarkdownCache>,
+    related_cache: web::Data<RelatedCache>,
+    reference_cache: web::Data<ReferenceCache>,
+    content: web::Data<dyn ContentStore>,
+    path: web::Path<String>,
+    query: web::Query<PreviewQuery>,
+) -> impl Responder {
+    let post_id = path.into_inner();
+
+    if !auth::verify_preview(&post_id, query.expires, &query.signature) {
+        return HttpResponse::Forbidden().body("This preview link is invalid or has expired");
+    }
+
+    let collection = Database::get_collection(&mongoc, "posts");
+    let post = match Database::find::<PostEntity>(collection, post_id).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+            return HttpResponse::NotFound().body("Post not found");
+        }
+        EntityResult::Error(e) => {
+            error!("Failed to find post: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding post");
+        }
+    };
+
+    render_post(
+        &handlebars,
+        &mongoc,
+        &markdown_cache,
+        &related_cache,
+        &reference_cache,
+        content.get_ref(),
+        post,
+        true,
+    )
+    .await
+}
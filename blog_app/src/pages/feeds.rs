@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+
+use actix_web::{
+    get,
+    http::header::{self, EntityTag, IfNoneMatch},
+    web, HttpMessage, HttpRequest, HttpResponse, Responder,
+};
+use chrono::NaiveDateTime;
+use handlebars::Handlebars;
+use log::error;
+use mongodb::{bson::doc, options::FindOptions, Client};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    entities::{
+        blogs::{PostEntity, TagEntity},
+        result_types::EntityResult,
+    },
+    utils::{
+        date_ops, db_ops::Database, environ::Environ, hash_ops, reference_ops::ReferenceCache,
+        string_ops,
+    },
+};
+
+/// number of most recent posts in a feed
+const FEED_SIZE: i64 = 20;
+
+#[derive(Debug, Serialize)]
+struct FeedEntryModel {
+    title: String,
+    url: String,
+    summary: String,
+    content: String,
+    authors: Vec<FeedAuthorModel>,
+    categories: Vec<FeedCategoryModel>,
+    published: String,
+    updated: String,
+    published_rss: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FeedAuthorModel {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FeedCategoryModel {
+    name: String,
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SitemapEntryModel {
+    url: String,
+    lastmod: String,
+}
+
+/// responds with the body and its etag, or 304 when the client already holds the same body
+fn etag_response(req: &HttpRequest, content_type: &str, body: String) -> HttpResponse {
+    let etag = EntityTag::new_strong(hash_ops::fast_hash(&body));
+
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+
+    if unchanged {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ETag(etag))
+        .body(body)
+}
+
+fn render_xml(
+    req: &HttpRequest,
+    handlebars: &Handlebars<'_>,
+    template_name: &str,
+    content_type: &str,
+    context: serde_json::Value,
+) -> HttpResponse {
+    match handlebars.render(template_name, &context) {
+        Ok(body) => etag_response(req, content_type, body),
+        Err(e) => {
+            error!("Error rendering {} template: {:?}", template_name, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// most recent published posts, optionally of one tag, with their authors and tags resolved;
+/// posts left without an existing author or tag are left out
+async fn find_feed_entries(
+    mongoc: &Client,
+    reference_cache: &ReferenceCache,
+    tag: Option<&TagEntity>,
+) -> EntityResult<Vec<FeedEntryModel>> {
+    let site_url = Environ::default().site_url;
+
+    let references = match reference_cache.get(mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let mut filter = PostEntity::published();
+    if let Some(tag) = tag {
+        filter.insert("tags", tag._id.unwrap().to_string());
+    }
+
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let posts = match Database::find_many(
+        collection,
+        filter,
+        FindOptions::builder()
+            .sort(doc! { "publish_date": -1 })
+            .limit(FEED_SIZE)
+            .build(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let authors = references
+        .authors
+        .iter()
+        .filter_map(|author| author._id.map(|id| (id.to_string(), author)))
+        .collect::<HashMap<_, _>>();
+    let tags = references
+        .tags
+        .iter()
+        .filter_map(|tag| tag._id.map(|id| (id.to_string(), tag)))
+        .collect::<HashMap<_, _>>();
+
+    let entries = posts
+        .into_iter()
+        .filter_map(|post| {
+            let entry_authors = post
+                .authors
+                .iter()
+                .filter_map(|id| authors.get(id))
+                .map(|author| FeedAuthorModel {
+                    name: format!("{} {}", author.first_name, author.last_name),
+                })
+                .collect::<Vec<_>>();
+            let categories = post
+                .tags
+                .iter()
+                .filter_map(|id| tags.get(id))
+                .map(|tag| FeedCategoryModel {
+                    name: tag.name.clone(),
+                    url: format!("{}{}", site_url, tag.url()),
+                })
+                .collect::<Vec<_>>();
+            if entry_authors.is_empty() || categories.is_empty() {
+                return None;
+            }
+            Some(FeedEntryModel {
+                title: post.title,
+                url: format!("{}/blogs/{}", site_url, post.permalink),
+                summary: post.description,
+                content: post.tldr,
+                authors: entry_authors,
+                categories,
+                published: date_ops::to_rfc3339(post.publish_date),
+                updated: date_ops::to_rfc3339(post.modified_date),
+                published_rss: date_ops::to_rfc2822(post.publish_date),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    EntityResult::Success(entries)
+}
+
+/// a feed is as recent as its newest entry
+fn feed_updated(entries: &[FeedEntryModel]) -> String {
+    match entries.first() {
+        Some(entry) => entry.updated.clone(),
+        None => date_ops::to_rfc3339(date_ops::local_date()),
+    }
+}
+
+async fn find_tag(
+    mongoc: &Client,
+    reference_cache: &ReferenceCache,
+    slug: &str,
+) -> EntityResult<Option<TagEntity>> {
+    match reference_cache.get(mongoc).await {
+        EntityResult::Success(r) => EntityResult::Success(
+            r.tags
+                .iter()
+                .find(|tag| tag.slug() == string_ops::slugify(slug))
+                .cloned(),
+        ),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+#[get("/feed.xml")]
+pub async fn get_atom_feed(
+    req: HttpRequest,
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+) -> impl Responder {
+    let entries = match find_feed_entries(&mongoc, &reference_cache, None).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find feed entries: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error building feed");
+        }
+    };
+
+    let site_url = Environ::default().site_url;
+    render_xml(
+        &req,
+        &handlebars,
+        "atom-feed",
+        "application/atom+xml; charset=utf-8",
+        json!({
+            "title": "Computer Science Coach",
+            "site_url": site_url,
+            "feed_url": format!("{}/feed.xml", site_url),
+            "updated": feed_updated(&entries),
+            "entries": entries
+        }),
+    )
+}
+
+#[get("/rss.xml")]
+pub async fn get_rss_feed(
+    req: HttpRequest,
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+) -> impl Responder {
+    let entries = match find_feed_entries(&mongoc, &reference_cache, None).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find feed entries: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error building feed");
+        }
+    };
+
+    let site_url = Environ::default().site_url;
+    render_xml(
+        &req,
+        &handlebars,
+        "rss-feed",
+        "application/rss+xml; charset=utf-8",
+        json!({
+            "title": "Computer Science Coach",
+            "description": "Latest posts from Computer Science Coach",
+            "site_url": site_url,
+            "feed_url": format!("{}/rss.xml", site_url),
+            "entries": entries
+        }),
+    )
+}
+
+#[get("/tags/{slug}/feed.xml")]
+pub async fn get_tag_atom_feed(
+    req: HttpRequest,
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let tag = match find_tag(&mongoc, &reference_cache, &path.into_inner()).await {
+        EntityResult::Success(Some(r)) => r,
+        EntityResult::Success(None) => return HttpResponse::NotFound().body("Tag not found"),
+        EntityResult::Error(e) => {
+            error!("Failed to find tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding tags");
+        }
+    };
+
+    let entries = match find_feed_entries(&mongoc, &reference_cache, Some(&tag)).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find feed entries of tag {}: {:?}", tag.name, e);
+            return HttpResponse::InternalServerError().body("Error building feed");
+        }
+    };
+
+    let site_url = Environ::default().site_url;
+    render_xml(
+        &req,
+        &handlebars,
+        "atom-feed",
+        "application/atom+xml; charset=utf-8",
+        json!({
+            "title": format!("Computer Science Coach: {}", tag.name),
+            "site_url": format!("{}{}", site_url, tag.url()),
+            "feed_url": format!("{}{}/feed.xml", site_url, tag.url()),
+            "updated": feed_updated(&entries),
+            "entries": entries
+        }),
+    )
+}
+
+#[get("/tags/{slug}/rss.xml")]
+pub async fn get_tag_rss_feed(
+    req: HttpRequest,
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let tag = match find_tag(&mongoc, &reference_cache, &path.into_inner()).await {
+        EntityResult::Success(Some(r)) => r,
+        EntityResult::Success(None) => return HttpResponse::NotFound().body("Tag not found"),
+        EntityResult::Error(e) => {
+            error!("Failed to find tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding tags");
+        }
+    };
+
+    let entries = match find_feed_entries(&mongoc, &reference_cache, Some(&tag)).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find feed entries of tag {}: {:?}", tag.name, e);
+            return HttpResponse::InternalServerError().body("Error building feed");
+        }
+    };
+
+    let site_url = Environ::default().site_url;
+    render_xml(
+        &req,
+        &handlebars,
+        "rss-feed",
+        "application/rss+xml; charset=utf-8",
+        json!({
+            "title": format!("Computer Science Coach: {}", tag.name),
+            "description": tag.description,
+            "site_url": format!("{}{}", site_url, tag.url()),
+            "feed_url": format!("{}{}/rss.xml", site_url, tag.url()),
+            "entries": entries
+        }),
+    )
+}
+
+#[get("/sitemap.xml")]
+pub async fn get_sitemap(
+    req: HttpRequest,
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    reference_cache: web::Data<ReferenceCache>,
+) -> impl Responder {
+    let site_url = Environ::default().site_url;
+
+    let references = match reference_cache.get(&mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find authors and tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding authors and tags");
+        }
+    };
+    let (authors, tags) = (&references.authors, &references.tags);
+
+    let collection = Database::get_collection::<PostEntity>(&mongoc, "posts");
+    let posts = match Database::find_many(
+        collection,
+        PostEntity::published(),
+        FindOptions::builder()
+            .sort(doc! { "publish_date": -1 })
+            .build(),
+    )
+    .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error finding posts");
+        }
+    };
+
+    // an archive page changes whenever one of its posts does
+    let mut archive_lastmod: HashMap<String, NaiveDateTime> = HashMap::new();
+    for post in posts.iter() {
+        for key in post.authors.iter().chain(post.tags.iter()) {
+            let lastmod = archive_lastmod
+                .entry(key.clone())
+                .or_insert(post.modified_date);
+            if post.modified_date > *lastmod {
+                *lastmod = post.modified_date;
+            }
+        }
+    }
+
+    let mut entries = posts
+        .iter()
+        .map(|post| SitemapEntryModel {
+            url: format!("{}/blogs/{}", site_url, post.permalink),
+            lastmod: date_ops::to_w3c_date(post.modified_date),
+        })
+        .collect::<Vec<_>>();
+
+    for tag in tags.iter() {
+        if let Some(lastmod) = tag._id.and_then(|id| archive_lastmod.get(&id.to_string())) {
+            entries.push(SitemapEntryModel {
+                url: format!("{}{}", site_url, tag.url()),
+                lastmod: date_ops::to_w3c_date(*lastmod),
+            });
+        }
+    }
+
+    for author in authors.iter() {
+        if let Some(lastmod) = author
+            ._id
+            .and_then(|id| archive_lastmod.get(&id.to_string()))
+        {
+            entries.push(SitemapEntryModel {
+                url: format!("{}{}", site_url, author.url()),
+                lastmod: date_ops::to_w3c_date(*lastmod),
+            });
+        }
+    }
+
+    render_xml(
+        &req,
+        &handlebars,
+        "sitemap",
+        "application/xml; charset=utf-8",
+        json!({
+            "site_url": site_url,
+            "entries": entries
+        }),
+    )
+}
+
+#[get("/robots.txt")]
+pub async fn get_robots(req: HttpRequest) -> impl Responder {
+    let body = format!(
+        "User-agent: *\nDisallow: /admin/\nDisallow: /api/\nDisallow: /preview/\n\nSitemap: {}/sitemap.xml\n",
+        Environ::default().site_url
+    );
+
+    etag_response(&req, "text/plain; charset=utf-8", body)
+}
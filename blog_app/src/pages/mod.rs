@@ -1,2 +1,23 @@
+use actix_web::web;
+
 pub mod admin;
+pub mod archives;
 pub mod blogs;
+pub mod feeds;
+pub mod newsletter;
+pub mod search;
+
+/// the read-only public pages, served by the app and rendered by the static export
+pub fn configure_public(cfg: &mut web::ServiceConfig) {
+    cfg.service(blogs::get_highlight_css)
+        .service(blogs::get_posts)
+        .service(blogs::get_post)
+        .service(archives::get_tag_archive)
+        .service(archives::get_author_archive)
+        .service(feeds::get_atom_feed)
+        .service(feeds::get_rss_feed)
+        .service(feeds::get_tag_atom_feed)
+        .service(feeds::get_tag_rss_feed)
+        .service(feeds::get_sitemap)
+        .service(feeds::get_robots);
+}
@@ -0,0 +1,158 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::error;
+use mongodb::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    auth,
+    entities::result_types::EntityResult,
+    models::SubscribeRequestModel,
+    newsletter::{self, Mailer, SubscribeError},
+    utils::{hash_ops, request_ops},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeQuery {
+    pub signature: String,
+}
+
+fn render_message(handlebars: &Handlebars<'_>, title: &str, message: &str) -> HttpResponse {
+    render_template!(
+        handlebars,
+        "newsletter-message",
+        json!({
+            "title": title,
+            "description": message,
+            "noindex": true,
+            "message": message
+        })
+    )
+}
+
+#[post("/newsletter/subscribe")]
+pub async fn post_subscribe(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    mailer: web::Data<dyn Mailer>,
+    form: web::Form<SubscribeRequestModel>,
+    req: HttpRequest,
+) -> impl Responder {
+    let email = match newsletter::normalize_email(&form.email) {
+        Some(r) => r,
+        None => {
+            return HttpResponse::BadRequest().body("Please enter a valid email address");
+        }
+    };
+
+    let client_hash = hash_ops::string_hasher(&request_ops::client_address(&req));
+    match newsletter::subscribe(&mongoc, &handlebars, mailer.get_ref(), &email, &client_hash).await
+    {
+        Ok(()) => {}
+        Err(SubscribeError::RateLimited) => {
+            return HttpResponse::TooManyRequests()
+                .body("You are signing up too fast, please wait a while");
+        }
+        Err(SubscribeError::Failed(e)) => {
+            error!("{}", e);
+            return HttpResponse::InternalServerError().body("Error subscribing to the newsletter");
+        }
+    }
+
+    render_message(
+        &handlebars,
+        "Check your inbox",
+        "We sent you an email with a link to confirm your subscription.",
+    )
+}
+
+#[get("/newsletter/confirm/{id}")]
+pub async fn get_confirm_subscription(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    query: web::Query<ConfirmQuery>,
+) -> impl Responder {
+    let subscriber_id = path.into_inner();
+    if !auth::verify_subscription(&subscriber_id, query.expires, &query.signature) {
+        return render_message(
+            &handlebars,
+            "Link expired",
+            "This confirmation link is invalid or has expired, please subscribe again.",
+        );
+    }
+
+    match newsletter::confirm(&mongoc, &subscriber_id).await {
+        EntityResult::Success(true) => render_message(
+            &handlebars,
+            "You are subscribed",
+            "Thanks for confirming, the next digest is on its way to your inbox.",
+        ),
+        EntityResult::Success(false) => render_message(
+            &handlebars,
+            "Nothing to confirm",
+            "This subscription was already confirmed or has been cancelled.",
+        ),
+        EntityResult::Error(e) => {
+            error!("Failed to confirm subscriber: {:?}", e);
+            HttpResponse::InternalServerError().body("Error confirming subscription")
+        }
+    }
+}
+
+/// asks before unsubscribing, mail scanners open links but do not submit forms
+#[get("/newsletter/unsubscribe/{id}")]
+pub async fn get_unsubscribe(
+    handlebars: web::Data<Handlebars<'_>>,
+    path: web::Path<String>,
+    query: web::Query<UnsubscribeQuery>,
+) -> impl Responder {
+    let subscriber_id = path.into_inner();
+    if !auth::verify_unsubscribe(&subscriber_id, &query.signature) {
+        return HttpResponse::NotFound().body("Subscription not found");
+    }
+
+    render_template!(
+        handlebars,
+        "newsletter-unsubscribe",
+        json!({
+            "title": "Unsubscribe",
+            "description": "Stop receiving the newsletter",
+            "noindex": true,
+            "action": auth::unsubscribe_path(&subscriber_id)
+        })
+    )
+}
+
+/// the form on the page above, and the one-click unsubscribe of mail clients
+#[post("/newsletter/unsubscribe/{id}")]
+pub async fn post_unsubscribe(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    path: web::Path<String>,
+    query: web::Query<UnsubscribeQuery>,
+) -> impl Responder {
+    let subscriber_id = path.into_inner();
+    if !auth::verify_unsubscribe(&subscriber_id, &query.signature) {
+        return HttpResponse::NotFound().body("Subscription not found");
+    }
+
+    match newsletter::unsubscribe(&mongoc, &subscriber_id).await {
+        EntityResult::Success(_) => render_message(
+            &handlebars,
+            "You are unsubscribed",
+            "You will not receive the newsletter anymore. You can subscribe again at any time.",
+        ),
+        EntityResult::Error(e) => {
+            error!("Failed to unsubscribe: {:?}", e);
+            HttpResponse::InternalServerError().body("Error unsubscribing")
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use handlebars::Handlebars;
+use log::error;
+use mongodb::Client;
+use serde_json::json;
+
+use crate::{
+    entities::result_types::EntityResult,
+    models::SearchRequestModel,
+    search::{self, SearchIndex},
+    utils::reference_ops::ReferenceCache,
+};
+
+#[get("/search")]
+pub async fn get_search(
+    handlebars: web::Data<Handlebars<'_>>,
+    mongoc: web::Data<Client>,
+    search_index: web::Data<dyn SearchIndex>,
+    reference_cache: web::Data<ReferenceCache>,
+    query: web::Query<SearchRequestModel>,
+) -> impl Responder {
+    if query.page == Some(0) {
+        return HttpResponse::BadRequest().body("Pages start at 1");
+    }
+
+    let response =
+        match search::find_results(&mongoc, search_index.get_ref(), &reference_cache, &query).await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => {
+                error!("Failed to search posts: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error searching posts");
+            }
+        };
+
+    let title = match response.query.is_empty() {
+        true => String::from("Search"),
+        false => format!("Search results for {}", response.query),
+    };
+
+    // result pages are endless permutations of the same posts, keep them out of indexes
+    render_template!(
+        handlebars,
+        "search-results",
+        json!({
+            "title": title,
+            "description": "Search the posts of the blog",
+            "noindex": true,
+            "base_url": "/search",
+            "all_tags_url": search::search_url(&response.query, None),
+            "query": search::search_query_string(&response.query, response.tag.as_deref()),
+            "pagination": response.pagination,
+            "search": response
+        })
+    )
+}
@@ -0,0 +1,177 @@
+use log::error;
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::IndexOptions,
+    Client, IndexModel,
+};
+
+use crate::{
+    entities::{
+        blogs::PostEntity,
+        redirects::RedirectEntity,
+        result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+    },
+    utils::{date_ops, db_ops::Database, string_ops},
+};
+
+/// generated permalinks are cut at a word boundary past this many characters
+const MAX_GENERATED_LENGTH: usize = 80;
+
+/// a post has a permalink of its own, and an old permalink redirects to one place only; the
+/// public pages also list posts by publish date, overall and per tag
+pub async fn init(mongoc: &Client) {
+    let posts = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let indexes = [
+        IndexModel::builder()
+            .keys(doc! { "permalink": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("post_permalink".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "publish_date": -1 })
+            .options(
+                IndexOptions::builder()
+                    .name("post_publish_date".to_string())
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "tags": 1, "publish_date": -1 })
+            .options(
+                IndexOptions::builder()
+                    .name("post_tag_publish_date".to_string())
+                    .build(),
+            )
+            .build(),
+    ];
+    for index in indexes {
+        if let EntityResult::Error(e) = Database::create_index(posts.clone(), index).await {
+            error!("Failed to create a post index: {:?}", e);
+        }
+    }
+
+    let redirects = Database::get_collection::<RedirectEntity>(mongoc, "redirects");
+    let index = IndexModel::builder()
+        .keys(doc! { "permalink": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("redirect_permalink".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build();
+    if let EntityResult::Error(e) = Database::create_index(redirects, index).await {
+        error!("Failed to create the redirect index: {:?}", e);
+    }
+}
+
+/// the permalink asked for in slug form, or one made from the title when none was given
+pub fn from_request(requested: &str, title: &str) -> String {
+    let slug = string_ops::slugify(requested);
+    if !slug.is_empty() {
+        return slug;
+    }
+
+    let mut slug = string_ops::slugify(title);
+    if slug.chars().count() > MAX_GENERATED_LENGTH {
+        slug = slug.chars().take(MAX_GENERATED_LENGTH).collect();
+        if let Some(end) = slug.rfind('-') {
+            slug.truncate(end);
+        }
+    }
+    match slug.is_empty() {
+        true => String::from("post"),
+        false => slug,
+    }
+}
+
+/// the permalink, or the first of permalink-2, permalink-3, ... that no other post has
+pub async fn unique(
+    mongoc: &Client,
+    permalink: &str,
+    post_id: Option<ObjectId>,
+) -> EntityResult<String> {
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    let mut candidate = permalink.to_string();
+    let mut number = 1;
+    loop {
+        let filter = doc! { "permalink": &candidate, "_id": { "$ne": post_id } };
+        match Database::count(collection.clone(), filter).await {
+            EntityResult::Success(0) => return EntityResult::Success(candidate),
+            EntityResult::Success(_) => {
+                number += 1;
+                candidate = format!("{}-{}", permalink, number);
+            }
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        }
+    }
+}
+
+/// after a post was saved under a new permalink: the old one redirects to the post from now
+/// on, and a redirect of the new one is dropped since the post answers there itself
+pub async fn record_move(
+    mongoc: &Client,
+    post_id: &str,
+    old: Option<&str>,
+    new: &str,
+) -> EntityResult<SuccessResultType> {
+    let collection = Database::get_collection::<RedirectEntity>(mongoc, "redirects");
+    match Database::delete_one(collection.clone(), doc! { "permalink": new }).await {
+        EntityResult::Success(_) | EntityResult::Error(DatabaseErrorType::NotFound(..)) => {}
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    }
+
+    let old = match old {
+        Some(old) if old != new && !old.is_empty() && old != "not-set" => old,
+        _ => return EntityResult::Success(SuccessResultType::Updated(String::from("0"))),
+    };
+    let redirect = RedirectEntity {
+        _id: None,
+        permalink: old.to_string(),
+        post_id: Some(post_id.to_string()),
+        target: None,
+        created_date: date_ops::local_date(),
+    };
+    Database::upsert(collection, doc! { "permalink": old }, redirect).await
+}
+
+/// where an old permalink points now, none when it is unknown or its post is not published
+pub async fn find_redirect(mongoc: &Client, permalink: &str) -> EntityResult<Option<String>> {
+    let collection = Database::get_collection::<RedirectEntity>(mongoc, "redirects");
+    let redirect =
+        match Database::find_one::<RedirectEntity>(collection, doc! { "permalink": permalink })
+            .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(DatabaseErrorType::NotFound(..)) => {
+                return EntityResult::Success(None)
+            }
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+    if let Some(target) = redirect.target {
+        return EntityResult::Success(Some(target));
+    }
+
+    let object_id = match redirect
+        .post_id
+        .as_deref()
+        .and_then(|id| ObjectId::parse_str(id).ok())
+    {
+        Some(r) => r,
+        None => return EntityResult::Success(None),
+    };
+    let mut filter = PostEntity::published();
+    filter.insert("_id", object_id);
+
+    let collection = Database::get_collection::<PostEntity>(mongoc, "posts");
+    match Database::find_one::<PostEntity>(collection, filter).await {
+        EntityResult::Success(r) => EntityResult::Success(Some(format!("/blogs/{}", r.permalink))),
+        EntityResult::Error(DatabaseErrorType::NotFound(..)) => EntityResult::Success(None),
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
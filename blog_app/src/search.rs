@@ -0,0 +1,446 @@
+use std::{cmp::Reverse, collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use handlebars::html_escape;
+use log::{error, info};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::{FindOptions, IndexOptions},
+    Client, IndexModel,
+};
+use serde::Deserialize;
+
+use crate::{
+    content::ContentStore,
+    entities::{
+        blogs::PostEntity,
+        result_types::{EntityResult, SuccessResultType},
+        searches::SearchEntity,
+    },
+    models::{
+        PaginationModel, PostResponseModel, SearchFacetModel, SearchRequestModel,
+        SearchResponseModel, SearchResultModel, MAX_PAGE,
+    },
+    utils::{db_ops::Database, markdown_ops, reference_ops::ReferenceCache, string_ops},
+};
+
+/// results per page of a search
+pub const PAGE_SIZE: u64 = 10;
+/// longer queries are cut, nobody types more than this into a search box
+const MAX_QUERY_CHARS: usize = 200;
+/// characters of body text shown around the first match
+const SNIPPET_CHARS: usize = 240;
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub text: String,
+    /// id of the tag to narrow the results to
+    pub tag: Option<String>,
+    pub page: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub post_id: String,
+    pub score: f64,
+    /// html, the matched terms wrapped in <mark>
+    pub snippet: String,
+}
+
+/// how many published posts of a tag match, whatever tag is selected
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchFacet {
+    #[serde(rename = "_id")]
+    pub tag: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    /// one page of hits, best first
+    pub hits: Vec<SearchHit>,
+    pub total: u64,
+    pub facets: Vec<SearchFacet>,
+}
+
+/// a full-text index of posts; only published posts are ever returned, whatever was indexed
+#[async_trait]
+pub trait SearchIndex: Send + Sync {
+    /// adds the post or replaces what was indexed for it
+    async fn index(
+        &self,
+        post_id: &str,
+        post: &PostEntity,
+        markdown: &str,
+    ) -> EntityResult<SuccessResultType>;
+
+    async fn remove(&self, post_id: &str) -> EntityResult<SuccessResultType>;
+
+    async fn search(&self, query: &SearchQuery) -> EntityResult<SearchResults>;
+}
+
+/// a text index over the search collection, ranked by mongodb's text score
+pub struct MongoSearchIndex {
+    mongoc: Client,
+}
+
+impl MongoSearchIndex {
+    pub fn new(mongoc: Client) -> Self {
+        MongoSearchIndex { mongoc }
+    }
+
+    /// a collection has a single text index, the weights rank title matches above body ones
+    pub async fn ensure_index(&self) -> EntityResult<SuccessResultType> {
+        let collection = Database::get_collection::<SearchEntity>(&self.mongoc, "search");
+        let index = IndexModel::builder()
+            .keys(doc! {
+                "title": "text",
+                "kicker": "text",
+                "subtitle": "text",
+                "tldr": "text",
+                "text": "text",
+            })
+            .options(
+                IndexOptions::builder()
+                    .name("post_text".to_string())
+                    .default_language("english".to_string())
+                    .weights(doc! { "title": 10, "kicker": 6, "subtitle": 4, "tldr": 3, "text": 1 })
+                    .build(),
+            )
+            .build();
+        Database::create_index(collection, index).await
+    }
+}
+
+#[async_trait]
+impl SearchIndex for MongoSearchIndex {
+    async fn index(
+        &self,
+        post_id: &str,
+        post: &PostEntity,
+        markdown: &str,
+    ) -> EntityResult<SuccessResultType> {
+        let entity = SearchEntity {
+            _id: post_id.to_string(),
+            title: post.title.clone(),
+            subtitle: post.subtitle.clone(),
+            kicker: post.kicker.clone(),
+            tldr: post.tldr.clone(),
+            text: markdown_ops::plain_text(markdown),
+            tags: post.tags.clone(),
+            status: post.status,
+            publish_date: post.publish_date,
+            score: 0.0,
+        };
+        let collection = Database::get_collection(&self.mongoc, "search");
+        Database::upsert(collection, doc! { "_id": post_id }, entity).await
+    }
+
+    async fn remove(&self, post_id: &str) -> EntityResult<SuccessResultType> {
+        let collection = Database::get_collection::<SearchEntity>(&self.mongoc, "search");
+        Database::delete_one(collection, doc! { "_id": post_id }).await
+    }
+
+    async fn search(&self, query: &SearchQuery) -> EntityResult<SearchResults> {
+        let collection = Database::get_collection::<SearchEntity>(&self.mongoc, "search");
+
+        let mut filter = doc! { "$text": { "$search": &query.text } };
+        filter.extend(PostEntity::published());
+
+        // facets count every matching tag, so the reader can switch to another one
+        let facets = match Database::aggregate::<_, SearchFacet>(
+            collection.clone(),
+            vec![
+                doc! { "$match": filter.clone() },
+                doc! { "$unwind": "$tags" },
+                doc! { "$group": { "_id": "$tags", "count": { "$sum": 1 } } },
+                doc! { "$sort": { "count": -1, "_id": 1 } },
+            ],
+        )
+        .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        if let Some(tag) = &query.tag {
+            filter.insert("tags", tag);
+        }
+
+        let total = match Database::count(collection.clone(), filter.clone()).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        let score: Document = doc! { "$meta": "textScore" };
+        let entities = match Database::find_many(
+            collection,
+            filter,
+            FindOptions::builder()
+                .projection(doc! { "score": score.clone() })
+                .sort(doc! { "score": score, "publish_date": -1 })
+                .skip(query.page.saturating_sub(1).saturating_mul(PAGE_SIZE))
+                .limit(PAGE_SIZE as i64)
+                .build(),
+        )
+        .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        EntityResult::Success(SearchResults {
+            hits: entities
+                .into_iter()
+                .map(|entity| SearchHit {
+                    snippet: snippet(&entity.text, &query.text),
+                    post_id: entity._id,
+                    score: entity.score,
+                })
+                .collect(),
+            total,
+            facets,
+        })
+    }
+}
+
+/// the search backend, with its index in place
+pub async fn init(mongoc: &Client) -> Arc<dyn SearchIndex> {
+    let index = MongoSearchIndex::new(mongoc.clone());
+    if let EntityResult::Error(e) = index.ensure_index().await {
+        error!("Failed to create the search index: {:?}", e);
+    }
+    Arc::new(index)
+}
+
+/// indexes the post with its markdown body read from the content store
+pub async fn index_post(
+    search: &dyn SearchIndex,
+    content: &dyn ContentStore,
+    post_id: &str,
+    post: &PostEntity,
+) -> EntityResult<SuccessResultType> {
+    match content.read(&post.body).await {
+        EntityResult::Success(markdown) => search.index(post_id, post, &markdown).await,
+        EntityResult::Error(e) => EntityResult::Error(e),
+    }
+}
+
+/// indexes every post again, e.g. after switching backends or for posts saved before search
+pub async fn rebuild(mongoc: &Client, search: &dyn SearchIndex, content: &dyn ContentStore) {
+    let collection = Database::get_collection(mongoc, "posts");
+    let posts = match Database::find_all::<PostEntity>(collection).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => {
+            error!("Failed to find posts: {:?}", e);
+            return;
+        }
+    };
+
+    let mut indexed = 0;
+    for post in &posts {
+        let post_id = post._id.map(|id| id.to_hex()).unwrap_or_default();
+        match index_post(search, content, &post_id, post).await {
+            EntityResult::Success(_) => indexed += 1,
+            EntityResult::Error(e) => error!("Failed to index post {}: {:?}", post_id, e),
+        }
+    }
+    info!("{} of {} posts indexed", indexed, posts.len());
+}
+
+/// the words of a query worth highlighting: quotes dropped, excluded (-word) terms skipped
+fn terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|term| !term.starts_with('-'))
+        .map(|term| term.trim_matches('"').to_ascii_lowercase())
+        .filter(|term| term.chars().count() > 1)
+        .collect()
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// escapes the text, wrapping every occurrence of a term in <mark>
+fn highlight(text: &str, terms: &[String]) -> String {
+    // ascii lowercasing keeps byte offsets, so matches index the original text
+    let lower = text.to_ascii_lowercase();
+    let mut html = String::new();
+    let mut position = 0;
+    while let Some((start, length)) = terms
+        .iter()
+        .filter_map(|term| {
+            lower[position..]
+                .find(term.as_str())
+                .map(|i| (position + i, term.len()))
+        })
+        .min_by_key(|&(start, length)| (start, Reverse(length)))
+    {
+        html.push_str(&html_escape(&text[position..start]));
+        html.push_str("<mark>");
+        html.push_str(&html_escape(&text[start..start + length]));
+        html.push_str("</mark>");
+        position = start + length;
+    }
+    html.push_str(&html_escape(&text[position..]));
+    html
+}
+
+/// a short excerpt of the text around the first matched term, as html
+pub fn snippet(text: &str, query: &str) -> String {
+    let terms = terms(query);
+    let lower = text.to_ascii_lowercase();
+    let first = terms
+        .iter()
+        .filter_map(|term| lower.find(term.as_str()))
+        .min()
+        .unwrap_or(0);
+
+    // start a little before the match, at the beginning of a word
+    let mut start = floor_char_boundary(text, first.saturating_sub(SNIPPET_CHARS / 4));
+    if start > 0 {
+        if let Some(space) = text[start..first].find(' ') {
+            start += space + 1;
+        }
+    }
+    let mut end = floor_char_boundary(text, start + SNIPPET_CHARS);
+    if end < text.len() {
+        if let Some(space) = text[start..end].rfind(' ') {
+            end = start + space;
+        }
+    }
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "… " } else { "" },
+        highlight(&text[start..end], &terms),
+        if end < text.len() { " …" } else { "" }
+    )
+}
+
+/// runs the search of a /search request and fills in the posts, tags and authors of the hits
+pub async fn find_results(
+    mongoc: &Client,
+    search: &dyn SearchIndex,
+    reference_cache: &ReferenceCache,
+    request: &SearchRequestModel,
+) -> EntityResult<SearchResponseModel> {
+    let text = request
+        .q
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .take(MAX_QUERY_CHARS)
+        .collect::<String>();
+    // the handlers turn away page 0, pages past MAX_PAGE are read as the last one
+    let page = request.page.unwrap_or(1).clamp(1, MAX_PAGE);
+
+    if text.is_empty() {
+        return EntityResult::Success(SearchResponseModel {
+            query: text,
+            tag: None,
+            total: 0,
+            results: vec![],
+            facets: vec![],
+            pagination: PaginationModel::new(1, PAGE_SIZE, 0),
+        });
+    }
+
+    let references = match reference_cache.get(mongoc).await {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+    let (authors, tags) = (&references.authors, &references.tags);
+
+    // tags are picked by slug, an unknown one is ignored rather than matching nothing
+    let tag = request.tag.as_deref().and_then(|slug| {
+        tags.iter()
+            .find(|tag| tag.slug() == string_ops::slugify(slug))
+    });
+
+    let results = match search
+        .search(&SearchQuery {
+            text: text.clone(),
+            tag: tag.map(|tag| tag._id.unwrap().to_string()),
+            page,
+        })
+        .await
+    {
+        EntityResult::Success(r) => r,
+        EntityResult::Error(e) => return EntityResult::Error(e),
+    };
+
+    let ids = results
+        .hits
+        .iter()
+        .filter_map(|hit| ObjectId::parse_str(&hit.post_id).ok())
+        .collect::<Vec<_>>();
+    let mut filter = doc! { "_id": { "$in": ids } };
+    filter.extend(PostEntity::published());
+
+    let posts_collection = Database::get_collection(mongoc, "posts");
+    let posts =
+        match Database::find_many::<PostEntity>(posts_collection, filter, FindOptions::default())
+            .await
+        {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+    let mut posts = PostResponseModel::all(posts, authors, tags)
+        .into_iter()
+        .map(|post| (post.id.clone(), post))
+        .collect::<HashMap<_, _>>();
+
+    let facets = results
+        .facets
+        .iter()
+        .filter_map(|facet| {
+            let facet_tag = tags
+                .iter()
+                .find(|t| t._id.map(|id| id.to_string()).as_deref() == Some(&facet.tag))?;
+            Some(SearchFacetModel {
+                name: facet_tag.name.clone(),
+                slug: facet_tag.slug(),
+                count: facet.count,
+                selected: tag.is_some_and(|tag| tag._id == facet_tag._id),
+                url: search_url(&text, Some(&facet_tag.slug())),
+            })
+        })
+        .collect();
+
+    EntityResult::Success(SearchResponseModel {
+        tag: tag.map(|tag| tag.slug()),
+        total: results.total,
+        results: results
+            .hits
+            .into_iter()
+            .filter_map(|hit| {
+                posts
+                    .remove(&hit.post_id)
+                    .map(|post| SearchResultModel::from(post, hit.snippet, hit.score))
+            })
+            .collect(),
+        facets,
+        pagination: PaginationModel::new(page, PAGE_SIZE, results.total),
+        query: text,
+    })
+}
+
+/// the url of a search, with the query string encoded
+pub fn search_url(text: &str, tag: Option<&str>) -> String {
+    format!("/search?{}", search_query_string(text, tag))
+}
+
+pub fn search_query_string(text: &str, tag: Option<&str>) -> String {
+    let mut params = vec![("q", text)];
+    if let Some(tag) = tag {
+        params.push(("tag", tag));
+    }
+    serde_urlencoded::to_string(params).unwrap_or_default()
+}
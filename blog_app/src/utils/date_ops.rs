@@ -1,22 +1,15 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
 
 pub fn local_date() -> NaiveDateTime {
     Local::now().naive_local()
 }
 
 pub fn to_display_date(date: NaiveDateTime) -> String {
-    Local
-        .from_local_datetime(&date)
-        .unwrap()
-        .naive_local()
-        .format("%v")
-        .to_string()
+    with_offset(date).naive_local().format("%v").to_string()
 }
 
 pub fn to_input_date_from(date: NaiveDateTime) -> String {
-    Local
-        .from_local_datetime(&date)
-        .unwrap()
+    with_offset(date)
         .naive_local()
         .format("%Y-%m-%d")
         .to_string()
@@ -27,11 +20,7 @@ pub fn to_input_date() -> String {
 }
 
 pub fn to_timestamp_from(date: NaiveDateTime) -> String {
-    Local
-        .from_local_datetime(&date)
-        .unwrap()
-        .timestamp()
-        .to_string()
+    with_offset(date).timestamp().to_string()
 }
 
 pub fn from(timestamp: i64) -> NaiveDateTime {
@@ -43,3 +32,27 @@ pub fn from(timestamp: i64) -> NaiveDateTime {
 pub fn to_timestamp() -> i64 {
     Local::now().timestamp()
 }
+
+/// the local time with its offset; the earlier one when clocks went back, and read as utc
+/// when clocks went forward over it, since that local time never existed
+fn with_offset(date: NaiveDateTime) -> DateTime<FixedOffset> {
+    match Local.from_local_datetime(&date).earliest() {
+        Some(r) => r.fixed_offset(),
+        None => Utc.from_utc_datetime(&date).fixed_offset(),
+    }
+}
+
+/// e.g. 2024-09-01T10:00:00+05:30, as used by atom feeds
+pub fn to_rfc3339(date: NaiveDateTime) -> String {
+    with_offset(date).to_rfc3339()
+}
+
+/// e.g. Sun, 1 Sep 2024 10:00:00 +0530, as used by rss feeds
+pub fn to_rfc2822(date: NaiveDateTime) -> String {
+    with_offset(date).to_rfc2822()
+}
+
+/// e.g. 2024-09-01, as used by sitemaps
+pub fn to_w3c_date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
@@ -3,8 +3,10 @@ use std::str::FromStr;
 use futures::stream::TryStreamExt;
 use log::debug;
 use mongodb::{
-    bson::{self, doc, oid::ObjectId, Bson},
-    Client, Collection,
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    error::{Error, ErrorKind, WriteFailure},
+    options::{FindOptions, ReplaceOptions, UpdateModifications},
+    Client, Collection, IndexModel,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
@@ -14,6 +16,16 @@ use crate::{
     utils::environ::Environ,
 };
 
+/// server error code of a write that broke a unique index
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key(e: &Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == DUPLICATE_KEY
+    )
+}
+
 pub struct Database;
 
 impl Database {
@@ -31,6 +43,10 @@ impl Database {
             Ok(result) => {
                 EntityResult::Success(SuccessResultType::Created(result.inserted_id.to_string()))
             }
+            Err(e) if is_duplicate_key(&e) => EntityResult::Error(DatabaseErrorType::Duplicate(
+                format!("Duplicate document in {}", collection.name()),
+                e.to_string(),
+            )),
             Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
                 format!("Error creating document in {}", collection.name()),
                 e.to_string(),
@@ -66,6 +82,116 @@ impl Database {
         }
     }
 
+    /// documents matching the filter, sorted and paged through the find options
+    pub async fn find_many<T>(
+        collection: Collection<T>,
+        filter: Document,
+        options: FindOptions,
+    ) -> EntityResult<Vec<T>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        match collection.find(filter, options).await {
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(entities) => EntityResult::Success(entities),
+                Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(
+                    format!("Error getting documents from {}", collection.name()),
+                    e.to_string(),
+                )),
+            },
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(
+                format!("Error getting documents from {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    pub async fn count<T>(collection: Collection<T>, filter: Document) -> EntityResult<u64>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        match collection.count_documents(filter, None).await {
+            Ok(count) => EntityResult::Success(count),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(
+                format!("Error counting documents in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// runs an aggregation pipeline, deserialising each output document into R
+    pub async fn aggregate<T, R>(
+        collection: Collection<T>,
+        pipeline: Vec<Document>,
+    ) -> EntityResult<Vec<R>>
+    where
+        T: Unpin + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let documents = match collection.aggregate(pipeline, None).await {
+            Ok(cursor) => cursor.try_collect::<Vec<Document>>().await,
+            Err(e) => Err(e),
+        };
+        match documents {
+            Ok(documents) => documents
+                .into_iter()
+                .map(|document| bson::from_document(document))
+                .collect::<Result<Vec<R>, _>>()
+                .map_or_else(
+                    |e| {
+                        EntityResult::Error(DatabaseErrorType::QueryError(
+                            format!("Error reading aggregation of {}", collection.name()),
+                            e.to_string(),
+                        ))
+                    },
+                    EntityResult::Success,
+                ),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(
+                format!("Error aggregating documents in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// inserts the entity, or replaces the document already matching the filter
+    pub async fn upsert<T>(
+        collection: Collection<T>,
+        filter: Document,
+        entity: T,
+    ) -> EntityResult<SuccessResultType>
+    where
+        T: Serialize + Unpin + Send + Sync,
+    {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        match collection
+            .replace_one(filter.clone(), entity, options)
+            .await
+        {
+            Ok(_) => EntityResult::Success(SuccessResultType::Updated(filter.to_string())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error replacing document in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// creates the index unless an identical one exists already
+    pub async fn create_index<T>(
+        collection: Collection<T>,
+        index: IndexModel,
+    ) -> EntityResult<SuccessResultType>
+    where
+        T: Send + Sync,
+    {
+        match collection.create_index(index, None).await {
+            Ok(result) => EntityResult::Success(SuccessResultType::Created(result.index_name)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error creating index on {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
     pub async fn find_by<T, E>(collection: Collection<T>, key: String, value: E) -> EntityResult<T>
     where
         T: DeserializeOwned + Unpin + Send + Sync,
@@ -86,11 +212,44 @@ impl Database {
         }
     }
 
+    /// first document matching the filter
+    pub async fn find_one<T>(collection: Collection<T>, filter: Document) -> EntityResult<T>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        match collection.find_one(filter.clone(), None).await {
+            Ok(cursor) => match cursor {
+                Some(r) => EntityResult::Success(r),
+                None => EntityResult::Error(DatabaseErrorType::NotFound(
+                    format!(
+                        "Error finding document by {} in {}",
+                        filter,
+                        collection.name()
+                    ),
+                    "Document not found".to_string(),
+                )),
+            },
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(
+                format!("Error getting documents from {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
     pub async fn find<T>(collection: Collection<T>, id: String) -> EntityResult<T>
     where
         T: DeserializeOwned + Unpin + Send + Sync,
     {
-        let object_id = ObjectId::from_str(id.as_str()).unwrap();
+        // a malformed id, say from a stale reference or a hand-typed url, matches no document
+        let object_id = match ObjectId::from_str(id.as_str()) {
+            Ok(r) => r,
+            Err(e) => {
+                return EntityResult::Error(DatabaseErrorType::NotFound(
+                    format!("Error finding document {} in {}", id, collection.name()),
+                    e.to_string(),
+                ))
+            }
+        };
 
         match collection.find_one(doc! {"_id": object_id}, None).await {
             Ok(cursor) => match cursor {
@@ -115,7 +274,15 @@ impl Database {
     where
         T: Serialize + Unpin + Send + Sync,
     {
-        let object_id = ObjectId::from_str(id.as_str()).unwrap();
+        let object_id = match ObjectId::from_str(id.as_str()) {
+            Ok(r) => r,
+            Err(e) => {
+                return EntityResult::Error(DatabaseErrorType::MutationError(
+                    format!("No document found to update in {}", collection.name()),
+                    e.to_string(),
+                ))
+            }
+        };
 
         let filter = doc! { "_id": object_id };
         let update = doc! { "$set": bson::to_bson(&entity).unwrap() };
@@ -140,4 +307,65 @@ impl Database {
             )),
         }
     }
+
+    /// applies the update, a document such as a $set or an aggregation pipeline, to every
+    /// document matching the filter
+    pub async fn update_many<T>(
+        collection: Collection<T>,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> EntityResult<SuccessResultType>
+    where
+        T: Serialize + Unpin + Send + Sync,
+    {
+        match collection.update_many(filter, update, None).await {
+            Ok(result) => EntityResult::Success(SuccessResultType::Updated(
+                result.modified_count.to_string(),
+            )),
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error updating documents in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    pub async fn delete_one<T>(
+        collection: Collection<T>,
+        filter: Document,
+    ) -> EntityResult<SuccessResultType>
+    where
+        T: Serialize + Unpin + Send + Sync,
+    {
+        match collection.delete_one(filter.clone(), None).await {
+            Ok(result) if result.deleted_count > 0 => {
+                EntityResult::Success(SuccessResultType::Deleted(filter.to_string()))
+            }
+            Ok(_) => EntityResult::Error(DatabaseErrorType::NotFound(
+                format!("No document to delete in {}", collection.name()),
+                filter.to_string(),
+            )),
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error deleting document in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
+
+    pub async fn delete_many<T>(
+        collection: Collection<T>,
+        filter: Document,
+    ) -> EntityResult<SuccessResultType>
+    where
+        T: Serialize + Unpin + Send + Sync,
+    {
+        match collection.delete_many(filter, None).await {
+            Ok(result) => {
+                EntityResult::Success(SuccessResultType::Deleted(result.deleted_count.to_string()))
+            }
+            Err(e) => EntityResult::Error(DatabaseErrorType::MutationError(
+                format!("Error deleting documents in {}", collection.name()),
+                e.to_string(),
+            )),
+        }
+    }
 }
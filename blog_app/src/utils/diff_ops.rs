@@ -0,0 +1,54 @@
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Equal,
+    Removed,
+    Added,
+    Changed,
+}
+
+/// one row of a side-by-side diff, a side is None where the other side has no counterpart
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffRow {
+    pub kind: DiffKind,
+    pub old_number: Option<usize>,
+    pub old_line: Option<String>,
+    pub new_number: Option<usize>,
+    pub new_line: Option<String>,
+}
+
+/// line by line side-by-side diff, replaced blocks are paired up line for line
+pub fn side_by_side(old: &str, new: &str) -> Vec<DiffRow> {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let line =
+        |lines: &[&str], index: usize| lines[index].trim_end_matches(['\r', '\n']).to_string();
+
+    let mut rows = Vec::new();
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let kind = match tag {
+            DiffTag::Equal => DiffKind::Equal,
+            DiffTag::Delete => DiffKind::Removed,
+            DiffTag::Insert => DiffKind::Added,
+            DiffTag::Replace => DiffKind::Changed,
+        };
+
+        for offset in 0..old_range.len().max(new_range.len()) {
+            let old_index = old_range.clone().nth(offset);
+            let new_index = new_range.clone().nth(offset);
+            rows.push(DiffRow {
+                kind,
+                old_number: old_index.map(|i| i + 1),
+                old_line: old_index.map(|i| line(old_lines, i)),
+                new_number: new_index.map(|i| i + 1),
+                new_line: new_index.map(|i| line(new_lines, i)),
+            });
+        }
+    }
+    rows
+}
@@ -9,6 +9,25 @@ pub struct Environ {
     pub db_name: String,
     pub allowed_origin: String,
     pub web_app_port: u16,
+    /// public base url used for canonical links, without a trailing slash
+    pub site_url: String,
+    /// secret the session cookies are signed and encrypted with, at least 32 bytes
+    pub session_key: Option<String>,
+    /// also encode uploads as avif, smaller than webp but much slower to encode
+    pub image_avif: bool,
+    /// where post bodies are kept: "database" (the default) or "filesystem"
+    pub content_store: String,
+    /// smtp server for newsletter mail, MailHog on localhost:1025 by default
+    pub email_server: String,
+    pub email_port: u16,
+    pub email_username: Option<String>,
+    pub email_password: Option<String>,
+    /// use STARTTLS, needed by real mail servers but not by MailHog
+    pub email_tls: bool,
+    /// sender of newsletter mail, e.g. "Computer Science Coach <news@example.com>"
+    pub email_from: String,
+    /// addresses of reverse proxies whose X-Forwarded-For header is believed
+    pub trusted_proxies: Vec<String>,
 }
 
 impl Environ {
@@ -21,6 +40,17 @@ impl Environ {
 
         from_filename(env_file).ok();
     }
+
+    /// the email settings Default cannot report without panicking mid request
+    pub fn check_email() -> Result<(), String> {
+        Environ::init();
+        match env::var("EMAIL_PORT") {
+            Ok(port) if port.parse::<u16>().is_err() => {
+                Err(format!("EMAIL_PORT must be a number, got {}", port))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl Default for Environ {
@@ -33,11 +63,51 @@ impl Default for Environ {
             .expect("Missing WEB_APP_PORT")
             .parse::<u16>()
             .expect("WEB_APP_PORT must be a number");
+        let site_url = env::var("SITE_URL")
+            .unwrap_or_else(|_| format!("http://localhost:{}", web_app_port))
+            .trim_end_matches('/')
+            .to_string();
+        let session_key = env::var("SESSION_KEY").ok();
+        if let Some(key) = &session_key {
+            assert!(key.len() >= 32, "SESSION_KEY must be at least 32 bytes");
+        }
+        let image_avif = env::var("IMAGE_AVIF").is_ok_and(|v| v == "true" || v == "1");
+        let content_store = env::var("CONTENT_STORE").unwrap_or_else(|_| String::from("database"));
+        let email_server = env::var("EMAIL_SERVER").unwrap_or_else(|_| String::from("localhost"));
+        // read on every request, a bad port is reported once at startup by check_email instead
+        let email_port = env::var("EMAIL_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+            .unwrap_or(1025);
+        let email_username = env::var("EMAIL_USERNAME").ok().filter(|v| !v.is_empty());
+        let email_password = env::var("EMAIL_PASSWORD").ok().filter(|v| !v.is_empty());
+        let email_tls = env::var("EMAIL_TLS").is_ok_and(|v| v == "true" || v == "1");
+        let email_from = env::var("EMAIL_FROM")
+            .unwrap_or_else(|_| String::from("Computer Science Coach <newsletter@localhost>"));
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .map(|v| {
+                v.split(',')
+                    .map(|proxy| proxy.trim().to_string())
+                    .filter(|proxy| !proxy.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
         Environ {
             db_connection_string: db_cs,
             db_name,
             allowed_origin,
             web_app_port,
+            site_url,
+            session_key,
+            image_avif,
+            content_store,
+            email_server,
+            email_port,
+            email_username,
+            email_password,
+            email_tls,
+            email_from,
+            trusted_proxies,
         }
     }
 }
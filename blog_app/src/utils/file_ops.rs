@@ -18,7 +18,7 @@ pub fn read_files_from_dir(dir: &str, ext: &str) -> Vec<FileInfo> {
             if path.ends_with(ext) {
                 let name = path
                     .split('/')
-                    .last()
+                    .next_back()
                     .unwrap()
                     .split('.')
                     .next()
@@ -40,3 +40,19 @@ pub fn read_file(file: &str) -> Result<String, String> {
         Err(e) => Err(format!("Error reading file: [{:?}]", e)),
     }
 }
+
+pub fn write_file(file: &str, contents: &str) -> Result<(), String> {
+    match std::fs::write(file, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Error writing file: [{:?}]", e)),
+    }
+}
+
+/// e.g. 1.4 MB, for showing file sizes
+pub fn display_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / 1024.0 / 1024.0),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
@@ -1,6 +1,7 @@
 use bcrypt::{hash, DEFAULT_COST};
 use crc32fast::Hasher;
 use hex::encode as hex_encode;
+use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256};
 
 pub fn string_hasher(input: &str) -> String {
@@ -15,7 +16,7 @@ pub fn cook_hash(password: &str) -> Result<String, bcrypt::BcryptError> {
 }
 
 pub fn verify(text: &str, hashed: &str) -> bool {
-    bcrypt::verify(text, hashed).unwrap()
+    bcrypt::verify(text, hashed).unwrap_or(false)
 }
 
 pub fn fast_hash(input: &str) -> String {
@@ -24,3 +25,21 @@ pub fn fast_hash(input: &str) -> String {
     let checksum = hasher.finalize();
     format!("{:08x}", checksum)
 }
+
+/// hex encoded hmac-sha256 of the input
+pub fn sign(input: &str, secret: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts keys of any size");
+    mac.update(input.as_bytes());
+    hex_encode(mac.finalize().into_bytes())
+}
+
+/// checks a signature made by sign in constant time
+pub fn verify_signature(input: &str, signature: &str, secret: &[u8]) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(r) => r,
+        Err(_) => return false,
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts keys of any size");
+    mac.update(input.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
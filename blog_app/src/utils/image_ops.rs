@@ -1,9 +1,14 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, path::Path};
 
+use handlebars::{html_escape, Context, Handlebars, Helper, HelperResult, Output, RenderContext};
+use image::DynamicImage;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use std::fmt;
 
+use crate::media;
+
 pub const UPLOADS_DIR: &str = "./assets/images/uploads";
 pub const RELATIVE_PATH: &str = "/assets/images/uploads";
 
@@ -36,6 +41,17 @@ impl ImageSize {
         ]
     }
 
+    /// what the file name of a resized copy ends with, before the extension
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            ImageSize::Thumbnail => "-thumb",
+            ImageSize::Profile => "-profile",
+            ImageSize::Wide => "-wide",
+            ImageSize::Hero => "-hero",
+            ImageSize::Original => "",
+        }
+    }
+
     pub fn get_all_sizes() -> Vec<ImageSize> {
         vec![
             ImageSize::Original,
@@ -150,6 +166,41 @@ impl ImagePath {
         }
     }
 
+    /// path of a copy in another format, e.g. the webp version of the hero image
+    pub fn variant_path(&self, size: &ImageSize, extension: &str) -> String {
+        format!(
+            "{}/{}{}.{}",
+            UPLOADS_DIR,
+            self.key,
+            size.suffix(),
+            extension
+        )
+    }
+
+    pub fn variant_r_path(&self, size: &ImageSize, extension: &str) -> String {
+        format!(
+            "{}/{}{}.{}",
+            RELATIVE_PATH,
+            self.key,
+            size.suffix(),
+            extension
+        )
+    }
+
+    /// like from_string, but only for paths into the uploads and without panicking
+    pub fn parse(path: &str) -> Option<Self> {
+        let file_name = path.strip_prefix(RELATIVE_PATH)?.trim_start_matches('/');
+        let (name, extension) = file_name.rsplit_once('.')?;
+        let key = name.split('-').next().filter(|key| !key.is_empty())?;
+
+        Some(ImagePath::new(key.to_string(), extension.to_string())).filter(ImagePath::is_valid)
+    }
+
+    /// key and extension as uploads name them, nothing that could leave an html attribute
+    pub fn is_valid(&self) -> bool {
+        media::is_valid_key(&self.key) && media::is_valid_key(&self.extension)
+    }
+
     pub fn from_string(path: &str) -> Self {
         let parts: Vec<&str> = path.split('/').collect();
         let file_name: Vec<&str> = parts[parts.len() - 1].split('.').collect();
@@ -161,3 +212,150 @@ impl ImagePath {
         }
     }
 }
+
+/// the orientation tag of the exif block of a jpeg, 1 (upright) when there is none
+pub fn exif_orientation(bytes: &[u8]) -> u16 {
+    // walk the jpeg segments up to the image data looking for the APP1 exif segment
+    let mut offset = 2;
+    while offset + 4 <= bytes.len() && bytes[offset] == 0xFF {
+        let marker = bytes[offset + 1];
+        if marker == 0xDA {
+            break;
+        }
+        let length = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let segment = match bytes.get(offset + 4..offset + 2 + length) {
+            Some(r) => r,
+            None => break,
+        };
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_orientation(&segment[6..]).unwrap_or(1);
+        }
+        offset += 2 + length;
+    }
+    1
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let b = tiff.get(offset..offset + 2)?;
+        Some(match little_endian {
+            true => u16::from_le_bytes([b[0], b[1]]),
+            false => u16::from_be_bytes([b[0], b[1]]),
+        })
+    };
+    let u32_at = |offset: usize| {
+        let b = tiff.get(offset..offset + 4)?;
+        Some(match little_endian {
+            true => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            false => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        })
+    };
+
+    // the orientation lives in the first image file directory, in 12 byte entries
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|entry| u16_at(*entry) == Some(0x0112))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+/// turns the pixels upright, needed because re-encoding drops the exif orientation tag
+pub fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn srcset(image: &ImagePath, extension: &str) -> String {
+    ImageSize::get_display_sizes()
+        .iter()
+        .map(|size| {
+            format!(
+                "{} {}w",
+                image.variant_r_path(size, extension),
+                size.dimensions().0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `{{picture path alt="" class="" sizes=""}}` renders an uploaded image as a `<picture>` with
+/// avif and webp sources, when those were generated, and a srcset over the display sizes;
+/// anything that is not an upload gets a plain `<img>`
+pub fn picture_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let attribute = |name: &str| {
+        h.hash_get(name)
+            .and_then(|value| value.value().as_str())
+            .map(html_escape)
+    };
+    let alt = attribute("alt").unwrap_or_default();
+    let class = attribute("class").unwrap_or_default();
+    let sizes = attribute("sizes").unwrap_or_else(|| String::from("100vw"));
+
+    let (src, image) = match h.param(0).map(|param| param.value()) {
+        Some(Value::String(path)) => (path.clone(), ImagePath::parse(path)),
+        Some(value @ Value::Object(_)) => {
+            match serde_json::from_value::<ImagePath>(value.clone()) {
+                Ok(image) if image.is_valid() => (image.original_r_path(), Some(image)),
+                _ => (String::new(), None),
+            }
+        }
+        _ => (String::new(), None),
+    };
+
+    let image = match image {
+        Some(r) => r,
+        None => {
+            out.write(&format!(
+                r#"<img src="{}" alt="{}" class="{}" loading="lazy">"#,
+                html_escape(&src),
+                alt,
+                class
+            ))?;
+            return Ok(());
+        }
+    };
+
+    out.write("<picture>")?;
+    for (extension, mime) in [("avif", "image/avif"), ("webp", "image/webp")] {
+        if extension != image.extension
+            && Path::new(&image.variant_path(&ImageSize::Hero, extension)).exists()
+        {
+            out.write(&format!(
+                r#"<source type="{}" srcset="{}" sizes="{}">"#,
+                mime,
+                html_escape(&srcset(&image, extension)),
+                sizes
+            ))?;
+        }
+    }
+    out.write(&format!(
+        r#"<img src="{}" srcset="{}" sizes="{}" alt="{}" class="{}" loading="lazy"></picture>"#,
+        html_escape(&src),
+        html_escape(&srcset(&image, &image.extension)),
+        sizes,
+        alt,
+        class
+    ))?;
+    Ok(())
+}
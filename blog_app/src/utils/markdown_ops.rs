@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, OnceLock},
+};
+
+use aarya_utils::markdown_ops::{highlight, sanitizer, CLASS_STYLE};
+use ammonia::Builder;
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use syntect::{highlighting::ThemeSet, html::css_for_theme_with_class_style};
+
+use super::{hash_ops, string_ops};
+
+const HIGHLIGHT_THEME: &str = "base16-ocean.dark";
+/// headings deeper than this are anchored but left out of the table of contents
+const TOC_MAX_LEVEL: u8 = 3;
+/// the cache is dropped as a whole once it holds this many posts
+const CACHE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    /// words of readable text, code included, for the reading time
+    pub word_count: usize,
+}
+
+/// rendered posts keyed by the sha256 of their markdown, shared between workers
+#[derive(Default)]
+pub struct MarkdownCache {
+    entries: Mutex<HashMap<String, RenderedMarkdown>>,
+}
+
+impl MarkdownCache {
+    pub fn render(&self, input: &str) -> RenderedMarkdown {
+        let key = hash_ops::string_hasher(input);
+        if let Some(rendered) = self.entries.lock().unwrap().get(&key) {
+            return rendered.clone();
+        }
+
+        let rendered = render(input);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= CACHE_CAPACITY {
+            entries.clear();
+        }
+        entries.insert(key, rendered.clone());
+        rendered
+    }
+}
+
+/// stricter than the one for posts: no classes or ids, and links are marked as user content
+fn comment_sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder.link_rel(Some("nofollow ugc noopener noreferrer"));
+        builder
+    })
+}
+
+fn heading_id(title: &str) -> String {
+    let slug = string_ops::slugify(title);
+    if slug.is_empty() {
+        String::from("section")
+    } else {
+        slug
+    }
+}
+
+/// appends -1, -2, .. to ids already used in the post
+fn unique_id(id: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = id.clone();
+    let mut n = 1;
+    while used.contains(&candidate) {
+        candidate = format!("{}-{}", id, n);
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+struct PendingHeading<'a> {
+    level: HeadingLevel,
+    id: Option<String>,
+    title: String,
+    events: Vec<Event<'a>>,
+}
+
+/// renders markdown to sanitised html with anchored headings and highlighted code blocks,
+/// along with a table of contents built from the headings and the number of words
+pub fn render(input: &str) -> RenderedMarkdown {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    let mut events = Vec::new();
+    let mut toc = Vec::new();
+    let mut used_ids = HashSet::new();
+    let mut code_block: Option<(String, String)> = None;
+    let mut heading: Option<PendingHeading> = None;
+
+    for event in Parser::new_ext(input, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((lang, String::new()));
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, code)) = code_block.take() {
+                    events.push(Event::Html(highlight(&code, &lang).into()));
+                }
+            }
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                heading = Some(PendingHeading {
+                    level,
+                    id: id.map(|id| id.to_string()),
+                    title: String::new(),
+                    events: Vec::new(),
+                });
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(pending) = heading.take() {
+                    let id = unique_id(
+                        pending.id.unwrap_or_else(|| heading_id(&pending.title)),
+                        &mut used_ids,
+                    );
+                    let level = pending.level as u8;
+
+                    let mut inner = String::new();
+                    html::push_html(&mut inner, pending.events.into_iter());
+                    events.push(Event::Html(
+                        format!(
+                            "<h{level} id=\"{id}\">{inner}<a class=\"anchor\" href=\"#{id}\">#</a></h{level}>\n"
+                        )
+                        .into(),
+                    ));
+
+                    if level <= TOC_MAX_LEVEL {
+                        toc.push(TocEntry {
+                            level,
+                            id,
+                            title: pending.title,
+                        });
+                    }
+                }
+            }
+            event => match heading.as_mut() {
+                Some(pending) => {
+                    if let Event::Text(text) | Event::Code(text) = &event {
+                        pending.title.push_str(text);
+                    }
+                    pending.events.push(event);
+                }
+                None => events.push(event),
+            },
+        }
+    }
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+
+    RenderedMarkdown {
+        html: sanitizer().clean(&html_output).to_string(),
+        toc,
+        word_count: plain_text(input).split_whitespace().count(),
+    }
+}
+
+/// the readable text of a post, without markup, html or link targets, on a single line
+pub fn plain_text(input: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(input, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// renders a reader comment; html typed into it is shown as text, not interpreted
+pub fn render_comment(input: &str) -> String {
+    let events = Parser::new_ext(input, Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+        Event::Html(text) | Event::InlineHtml(text) => Event::Text(text),
+        event => event,
+    });
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events);
+    comment_sanitizer().clean(&html_output).to_string()
+}
+
+/// stylesheet for the classes emitted by the highlighter, built once
+pub fn highlight_css() -> &'static str {
+    static HIGHLIGHT_CSS: OnceLock<String> = OnceLock::new();
+    HIGHLIGHT_CSS.get_or_init(|| {
+        let themes = ThemeSet::load_defaults();
+        css_for_theme_with_class_style(&themes.themes[HIGHLIGHT_THEME], CLASS_STYLE)
+            .unwrap_or_default()
+    })
+}
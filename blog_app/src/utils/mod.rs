@@ -1,9 +1,15 @@
 pub mod date_ops;
 pub mod db_ops;
+pub mod diff_ops;
 pub mod environ;
 pub mod file_ops;
 pub mod hash_ops;
 pub mod image_ops;
 pub mod json_ops;
+pub mod markdown_ops;
 pub mod random_ops;
+pub mod reference_ops;
+pub mod related_ops;
+pub mod request_ops;
+pub mod seo_ops;
 pub mod string_ops;
@@ -0,0 +1,73 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mongodb::Client;
+
+use crate::entities::{
+    blogs::{AuthorEntity, TagEntity},
+    result_types::EntityResult,
+};
+
+use super::db_ops::Database;
+
+/// how long authors and tags are reused; editing either drops them right away
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// every author and tag; both are small and nearly every page resolves posts against them
+#[derive(Debug, Default)]
+pub struct References {
+    pub authors: Vec<AuthorEntity>,
+    pub tags: Vec<TagEntity>,
+}
+
+impl References {
+    pub fn author(&self, id: &str) -> Option<&AuthorEntity> {
+        self.authors
+            .iter()
+            .find(|author| author._id.is_some_and(|a| a.to_hex() == id))
+    }
+
+    pub fn tag(&self, id: &str) -> Option<&TagEntity> {
+        self.tags
+            .iter()
+            .find(|tag| tag._id.is_some_and(|t| t.to_hex() == id))
+    }
+}
+
+/// authors and tags shared between workers, read again once they expire
+#[derive(Default)]
+pub struct ReferenceCache {
+    entry: Mutex<Option<(Instant, Arc<References>)>>,
+}
+
+impl ReferenceCache {
+    pub async fn get(&self, mongoc: &Client) -> EntityResult<Arc<References>> {
+        if let Some((created, references)) = self.entry.lock().unwrap().as_ref() {
+            if created.elapsed() <= CACHE_TTL {
+                return EntityResult::Success(references.clone());
+            }
+        }
+
+        let authors_collection = Database::get_collection(mongoc, "authors");
+        let authors = match Database::find_all::<AuthorEntity>(authors_collection).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+        let tags_collection = Database::get_collection(mongoc, "tags");
+        let tags = match Database::find_all::<TagEntity>(tags_collection).await {
+            EntityResult::Success(r) => r,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        let references = Arc::new(References { authors, tags });
+        *self.entry.lock().unwrap() = Some((Instant::now(), references.clone()));
+        EntityResult::Success(references)
+    }
+
+    /// drops the cached authors and tags, after one was saved, archived or deleted
+    pub fn clear(&self) {
+        *self.entry.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::Deserialize;
+
+use crate::entities::blogs::PostEntity;
+
+/// related posts shown under a post
+pub const RELATED_SIZE: usize = 3;
+/// a shared tag outweighs a shared keyword, recency only breaks ties between similar posts
+const TAG_WEIGHT: f64 = 3.0;
+const KEYWORD_WEIGHT: f64 = 1.0;
+/// recency halves for every this many days between two posts
+const RECENCY_HALF_LIFE_DAYS: f64 = 180.0;
+/// how long a ranking is reused; saving any post drops them all
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// the fields of a post ranking looks at, read through a projection rather than whole posts
+#[derive(Debug, Deserialize)]
+pub struct RelatedCandidate {
+    pub _id: ObjectId,
+    pub tags: Vec<String>,
+    pub kicker: String,
+    pub publish_date: NaiveDateTime,
+}
+
+impl RelatedCandidate {
+    pub fn projection() -> Document {
+        doc! { "_id": 1, "tags": 1, "kicker": 1, "publish_date": 1 }
+    }
+}
+
+/// the kicker is a free list of keywords, compared as lowercase words
+fn keywords(kicker: &str) -> HashSet<String> {
+    kicker
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn score(post: &PostEntity, post_keywords: &HashSet<String>, candidate: &RelatedCandidate) -> f64 {
+    let shared_tags = candidate
+        .tags
+        .iter()
+        .filter(|tag| post.tags.contains(tag))
+        .count();
+    let shared_keywords = keywords(&candidate.kicker)
+        .intersection(post_keywords)
+        .count();
+    let days_apart = (post.publish_date - candidate.publish_date)
+        .num_days()
+        .unsigned_abs() as f64;
+
+    TAG_WEIGHT * shared_tags as f64
+        + KEYWORD_WEIGHT * shared_keywords as f64
+        + 0.5f64.powf(days_apart / RECENCY_HALF_LIFE_DAYS)
+}
+
+/// ids of the candidates most related to the post, best first; the post itself is never
+/// included
+pub fn rank(post: &PostEntity, candidates: Vec<RelatedCandidate>) -> Vec<ObjectId> {
+    let post_keywords = keywords(&post.kicker);
+    let mut scored = candidates
+        .into_iter()
+        .filter(|candidate| Some(candidate._id) != post._id)
+        .map(|candidate| (score(post, &post_keywords, &candidate), candidate._id))
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    scored
+        .into_iter()
+        .take(RELATED_SIZE)
+        .map(|(_, candidate_id)| candidate_id)
+        .collect()
+}
+
+/// ranked related posts keyed by post id, shared between workers
+#[derive(Default)]
+pub struct RelatedCache {
+    entries: Mutex<HashMap<String, (Instant, Vec<PostEntity>)>>,
+}
+
+impl RelatedCache {
+    /// the cached ranking, unless it has expired; expiring also lets scheduled posts that went
+    /// live since show up
+    pub fn get(&self, post_id: &str) -> Option<Vec<PostEntity>> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(post_id) {
+            Some((created, posts)) if created.elapsed() <= CACHE_TTL => Some(posts.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, post_id: String, posts: Vec<PostEntity>) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(post_id, (Instant::now(), posts));
+    }
+
+    /// drops every ranking, a saved post can change any of them
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
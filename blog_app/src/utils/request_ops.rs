@@ -0,0 +1,35 @@
+use actix_web::{http::header::HeaderName, HttpRequest};
+
+use super::environ::Environ;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// address of the client, for rate limits: forwarding headers are set by the client itself,
+/// so they are only followed through the proxies listed in TRUSTED_PROXIES
+pub fn client_address(req: &HttpRequest) -> String {
+    let peer = match req.peer_addr() {
+        Some(r) => r.ip().to_string(),
+        None => return String::new(),
+    };
+    let trusted = Environ::default().trusted_proxies;
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    // every proxy appends the address it got the request from, the rightmost one that is not
+    // a trusted proxy is the client
+    let forwarded = req
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<_>>();
+    forwarded
+        .into_iter()
+        .rev()
+        .find(|address| !trusted.iter().any(|proxy| proxy == address))
+        .map(str::to_string)
+        .unwrap_or(peer)
+}
@@ -0,0 +1,197 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::entities::blogs::{AuthorEntity, PostEntity, TagEntity};
+
+use super::{
+    date_ops,
+    environ::Environ,
+    image_ops::{ImagePath, ImageSize},
+};
+
+pub const SITE_NAME: &str = "Computer Science Coach";
+/// a comfortable pace for technical prose, code blocks included
+const WORDS_PER_MINUTE: usize = 220;
+
+/// an image as social cards see it, with an absolute url
+#[derive(Debug, Serialize)]
+pub struct MetaImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    pub alt: String,
+}
+
+/// what head.hbs needs, next to the canonical url, for Open Graph, Twitter cards and JSON-LD
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    pub og_type: &'static str,
+    pub twitter_card: &'static str,
+    pub image: Option<MetaImage>,
+    pub published_time: Option<String>,
+    pub modified_time: Option<String>,
+    /// absolute urls of the author pages
+    pub authors: Vec<String>,
+    pub tags: Vec<String>,
+    pub reading_time: Option<String>,
+    /// already serialised, safe to place in a script element as is
+    pub json_ld: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct ReadingStats {
+    pub word_count: usize,
+    pub minutes: usize,
+}
+
+impl ReadingStats {
+    pub fn new(word_count: usize) -> Self {
+        ReadingStats {
+            word_count,
+            minutes: word_count.div_ceil(WORDS_PER_MINUTE).max(1),
+        }
+    }
+}
+
+pub fn absolute_url(path: &str) -> String {
+    format!("{}{}", Environ::default().site_url, path)
+}
+
+/// the image in one of its resized copies, or none for posts and authors without a photo
+fn meta_image(image: &ImagePath, size: ImageSize, alt: &str) -> Option<MetaImage> {
+    if image.key == "not-set" {
+        return None;
+    }
+
+    let (width, height) = size.dimensions();
+    Some(MetaImage {
+        url: absolute_url(&image.from_r(&size)),
+        width,
+        height,
+        alt: alt.to_string(),
+    })
+}
+
+/// a closing script tag inside a string would end the element early
+fn to_json_ld(value: Value) -> String {
+    value.to_string().replace("</", "<\\/")
+}
+
+fn person(author: &AuthorEntity) -> Value {
+    json!({
+        "@type": "Person",
+        "name": format!("{} {}", author.first_name, author.last_name),
+        "url": absolute_url(&author.url()),
+    })
+}
+
+fn publisher() -> Value {
+    json!({
+        "@type": "Organization",
+        "name": SITE_NAME,
+        "url": absolute_url("/"),
+        "logo": { "@type": "ImageObject", "url": absolute_url("/assets/images/logo-ico.svg") },
+    })
+}
+
+/// metadata of a post page; authors and tags are the ones the post lists, already resolved
+pub fn post_meta(
+    post: &PostEntity,
+    authors: &[&AuthorEntity],
+    tags: &[&TagEntity],
+    canonical_url: &str,
+    reading: ReadingStats,
+) -> PageMeta {
+    let image = meta_image(&post.hero_image, ImageSize::Hero, &post.title);
+    let published_time = date_ops::to_rfc3339(post.publish_date);
+    let modified_time = date_ops::to_rfc3339(post.modified_date);
+
+    let json_ld = json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": post.title,
+        "alternativeHeadline": post.subtitle,
+        "description": post.description,
+        "image": image.as_ref().map(|image| &image.url),
+        "datePublished": published_time,
+        "dateModified": modified_time,
+        "author": authors.iter().map(|author| person(author)).collect::<Vec<_>>(),
+        "publisher": publisher(),
+        "mainEntityOfPage": { "@type": "WebPage", "@id": canonical_url },
+        "articleSection": tags.first().map(|tag| &tag.name),
+        "keywords": tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>().join(", "),
+        "wordCount": reading.word_count,
+        "timeRequired": format!("PT{}M", reading.minutes),
+    });
+
+    PageMeta {
+        og_type: "article",
+        twitter_card: if image.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        },
+        image,
+        published_time: Some(published_time),
+        modified_time: Some(modified_time),
+        authors: authors
+            .iter()
+            .map(|author| absolute_url(&author.url()))
+            .collect(),
+        tags: tags.iter().map(|tag| tag.name.clone()).collect(),
+        reading_time: Some(format!("{} min read", reading.minutes)),
+        json_ld: to_json_ld(json_ld),
+    }
+}
+
+/// metadata of an author archive, described as the profile page of a person
+pub fn author_meta(author: &AuthorEntity, canonical_url: &str) -> PageMeta {
+    let name = format!("{} {}", author.first_name, author.last_name);
+    let image = meta_image(&author.photo_url, ImageSize::Profile, &name);
+
+    let mut person = person(author);
+    person["description"] = json!(author.bio);
+    person["image"] = json!(image.as_ref().map(|image| &image.url));
+    let json_ld = json!({
+        "@context": "https://schema.org",
+        "@type": "ProfilePage",
+        "url": canonical_url,
+        "mainEntity": person,
+    });
+
+    PageMeta {
+        og_type: "profile",
+        twitter_card: "summary",
+        image,
+        published_time: None,
+        modified_time: None,
+        authors: vec![],
+        tags: vec![],
+        reading_time: None,
+        json_ld: to_json_ld(json_ld),
+    }
+}
+
+/// metadata of a tag archive, a collection of posts
+pub fn tag_meta(tag: &TagEntity, canonical_url: &str) -> PageMeta {
+    let json_ld = json!({
+        "@context": "https://schema.org",
+        "@type": "CollectionPage",
+        "name": tag.name,
+        "description": tag.description,
+        "url": canonical_url,
+        "isPartOf": { "@type": "WebSite", "name": SITE_NAME, "url": absolute_url("/") },
+    });
+
+    PageMeta {
+        og_type: "website",
+        twitter_card: "summary",
+        image: None,
+        published_time: None,
+        modified_time: None,
+        authors: vec![],
+        tags: vec![],
+        reading_time: None,
+        json_ld: to_json_ld(json_ld),
+    }
+}
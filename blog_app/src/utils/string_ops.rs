@@ -21,3 +21,16 @@ pub fn unescape(input: &str) -> String {
         .replace("\\\"", "\"")
         .replace("\\\\", "\\")
 }
+
+/// lowercase, alphanumerics kept, everything else collapsed to single dashes
+pub fn slugify(input: &str) -> String {
+    let mut slug = String::new();
+    for c in input.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
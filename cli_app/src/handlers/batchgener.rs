@@ -30,10 +30,10 @@ pub async fn run_batch(
 ) {
     let session_id = generate_timestamp();
 
-    if course_id.is_some() && chapter_id.is_none() {
+    if let (Some(course_id), None) = (course_id, chapter_id) {
         // autogen given course
         let mut course = CourseEntity::new();
-        course.course_id = course_id.unwrap();
+        course.course_id = course_id;
         let courses = match course.find_all(pool).await {
             EntityResult::Success(c) => c,
             EntityResult::Error(e) => {
@@ -45,11 +45,11 @@ pub async fn run_batch(
         save_to_file(courses, count, prompt_path, data_folder, screenshot_path).await;
     }
 
-    if chapter_id.is_some() && course_id.is_some() {
+    if let (Some(course_id), Some(chapter_id)) = (course_id, chapter_id) {
         // autogen given chapter
         let mut chapter = ChapterEntity::new();
-        chapter.course_id = course_id.unwrap();
-        chapter.chapter_id = chapter_id.unwrap();
+        chapter.course_id = course_id;
+        chapter.chapter_id = chapter_id;
         let chapters = match chapter.find_all(pool).await {
             EntityResult::Success(c) => c,
             EntityResult::Error(e) => {
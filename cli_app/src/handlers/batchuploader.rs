@@ -64,9 +64,10 @@ pub async fn run_batch_uploads(
 
         // save the questions
         println!("----Saving {} question(s)----", questions.len());
+        let reason = format!("uploaded from {}", &model.file_path);
         for question in questions {
             println!("Saving question Id: {:?}", question.question_id.unwrap());
-            match question.create(pool).await {
+            match question.create(pool, "aarya_cli", Some(&reason)).await {
                 EntityResult::Success(_) => println!("Question: {:?} saved successfully", question.question_id),
                 EntityResult::Error(e) => println!("Failed to save question: {:?}", e),
             }
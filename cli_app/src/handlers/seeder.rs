@@ -1,103 +1,284 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use aarya_utils::json_ops::{json_to_vec, JsonOpsResult};
+use aarya_utils::{
+    file_ops::{read_file_contents, FileOpsResult},
+    json_ops::{json_to_vec, JsonOpsResult},
+};
 use models::{
     blogs::{AuthorEntity, TagEntity},
     chapters::ChapterEntity,
     courses::CourseEntity,
-    result_types::EntityResult,
+    result_types::{EntityResult, SuccessResultType},
     topics::TopicEntity,
 };
-use sqlx::MySqlPool;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{MySqlConnection, MySqlPool};
 
-pub async fn run_seeder(
-    courses_file: &Option<PathBuf>,
-    chapters_file: &Option<PathBuf>,
-    topics_file: &Option<PathBuf>,
-    authors_file: &Option<PathBuf>,
-    tags_file: &Option<PathBuf>,
-    pool: &MySqlPool,
-) {
-    if courses_file.is_some() {
-        println!("Ensure validating the file contents. Now processing the courses file: {:?}", courses_file);
-        let courses = match json_to_vec::<CourseEntity>(courses_file.as_ref().unwrap().to_str().unwrap()) {
-            JsonOpsResult::Success(courses) => courses,
-            JsonOpsResult::Error(e) => {
-                println!("Failed to read courses file: {:?}", e);
-                return;
-            }
-        };
-        for course in courses {
-            match course.create(pool).await {
-                EntityResult::Success(r) => println!("Course created successfully: {:?}", r),
-                EntityResult::Error(e) => println!("Failed to create course: {:?}", e),
+/// files to seed; a seed manifest is a json file with the same keys,
+/// paths in a manifest are relative to the manifest file
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeedFiles {
+    pub courses: Option<PathBuf>,
+    pub chapters: Option<PathBuf>,
+    pub topics: Option<PathBuf>,
+    pub authors: Option<PathBuf>,
+    pub tags: Option<PathBuf>,
+}
+
+/// what the seeder needs from an entity: a natural key to report and an idempotent write
+trait Seed: Serialize + DeserializeOwned {
+    const KIND: &'static str;
+    fn natural_key(&self) -> String;
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool>;
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType>;
+}
+
+impl Seed for CourseEntity {
+    const KIND: &'static str = "course";
+    fn natural_key(&self) -> String {
+        format!("course_id={}", self.course_id)
+    }
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        self.exists(conn).await
+    }
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        self.upsert(conn).await
+    }
+}
+
+impl Seed for ChapterEntity {
+    const KIND: &'static str = "chapter";
+    fn natural_key(&self) -> String {
+        format!("chapter_id={}", self.chapter_id)
+    }
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        self.exists(conn).await
+    }
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        self.upsert(conn).await
+    }
+}
+
+impl Seed for TopicEntity {
+    const KIND: &'static str = "topic";
+    fn natural_key(&self) -> String {
+        format!("topic_id={}", self.topic_id)
+    }
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        self.exists(conn).await
+    }
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        self.upsert(conn).await
+    }
+}
+
+impl Seed for AuthorEntity {
+    const KIND: &'static str = "author";
+    fn natural_key(&self) -> String {
+        format!("author_email={}", self.author_email)
+    }
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        self.exists(conn).await
+    }
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        self.upsert(conn).await
+    }
+}
+
+impl Seed for TagEntity {
+    const KIND: &'static str = "tag";
+    fn natural_key(&self) -> String {
+        format!("tag_name={}", self.tag_name)
+    }
+    async fn seed_exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        self.exists(conn).await
+    }
+    async fn seed_upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        self.upsert(conn).await
+    }
+}
+
+fn read_manifest(manifest_file: &Path) -> Result<SeedFiles, String> {
+    let contents = match read_file_contents(manifest_file.to_str().unwrap()) {
+        FileOpsResult::Success(c) => c,
+        FileOpsResult::Error(e) => return Err(format!("Failed to read seed manifest: {:?}", e)),
+    };
+    let manifest: SeedFiles = serde_json::from_str(&contents).map_err(|e| format!("Failed to parse seed manifest: {:?}", e))?;
+
+    let base = manifest_file.parent().unwrap_or(Path::new("."));
+    let resolve = |file: Option<PathBuf>| file.map(|f| base.join(f));
+    Ok(SeedFiles {
+        courses: resolve(manifest.courses),
+        chapters: resolve(manifest.chapters),
+        topics: resolve(manifest.topics),
+        authors: resolve(manifest.authors),
+        tags: resolve(manifest.tags),
+    })
+}
+
+struct SeedRows {
+    courses: Vec<CourseEntity>,
+    chapters: Vec<ChapterEntity>,
+    topics: Vec<TopicEntity>,
+    authors: Vec<AuthorEntity>,
+    tags: Vec<TagEntity>,
+}
+
+fn load<T: Seed>(file: &Option<PathBuf>) -> Result<Vec<T>, String> {
+    match file {
+        Some(file) => {
+            println!("Reading {} file: {:?}", T::KIND, file);
+            match json_to_vec::<T>(file.to_str().unwrap()) {
+                JsonOpsResult::Success(rows) => Ok(rows),
+                JsonOpsResult::Error(e) => Err(format!("Failed to read {} file: {:?}", T::KIND, e)),
             }
         }
-    } else if chapters_file.is_some() {
-        println!("Processing chapters file");
-        println!("Ensure validating the file contents. Now processing the courses file: {:?}", chapters_file);
-        let chapters = match json_to_vec::<ChapterEntity>(chapters_file.as_ref().unwrap().to_str().unwrap()) {
-            JsonOpsResult::Success(chapters) => chapters,
-            JsonOpsResult::Error(e) => {
-                println!("Failed to read chapters file: {:?}", e);
-                return;
+        None => Ok(Vec::new()),
+    }
+}
+
+// read every file before touching the database
+fn load_all(files: &SeedFiles) -> Result<SeedRows, String> {
+    Ok(SeedRows {
+        courses: load(&files.courses)?,
+        chapters: load(&files.chapters)?,
+        topics: load(&files.topics)?,
+        authors: load(&files.authors)?,
+        tags: load(&files.tags)?,
+    })
+}
+
+async fn seed<T: Seed>(
+    rows: &[T],
+    dry_run: bool,
+    conn: &mut MySqlConnection,
+) -> Result<(), String> {
+    for row in rows {
+        if dry_run {
+            match row.seed_exists(conn).await {
+                EntityResult::Success(true) => println!("[dry-run] update {} {}", T::KIND, row.natural_key()),
+                EntityResult::Success(false) => println!("[dry-run] insert {} {}", T::KIND, row.natural_key()),
+                EntityResult::Error(e) => return Err(format!("Failed to plan {} {}: {:?}", T::KIND, row.natural_key(), e)),
             }
-        };
-        for chapter in chapters {
-            match chapter.create_chapter(pool).await {
-                EntityResult::Success(r) => println!("Chapter created successfully: {:?}", r),
-                EntityResult::Error(e) => println!("Failed to create chapter: {:?}", e),
+        } else {
+            match row.seed_upsert(conn).await {
+                EntityResult::Success(SuccessResultType::Created(..)) => println!("Inserted {} {}", T::KIND, row.natural_key()),
+                EntityResult::Success(_) => println!("Updated {} {}", T::KIND, row.natural_key()),
+                EntityResult::Error(e) => return Err(format!("Failed to seed {} {}: {:?}", T::KIND, row.natural_key(), e)),
             }
         }
-    } else if topics_file.is_some() {
-        println!("Processing topics file");
-        println!("Ensure validating the file contents. Now processing the topics file: {:?}", topics_file);
-        let topics = match json_to_vec::<TopicEntity>(topics_file.as_ref().unwrap().to_str().unwrap()) {
-            JsonOpsResult::Success(topics) => topics,
-            JsonOpsResult::Error(e) => {
-                println!("Failed to read topics file: {:?}", e);
+    }
+    Ok(())
+}
+
+/// seeds every given file in dependency order (courses, chapters, topics, authors, tags) in one transaction;
+/// rows are upserted by their natural key so the seeder can be re-run, any failure rolls back the whole run
+pub async fn run_seeder(
+    files: SeedFiles,
+    manifest_file: &Option<PathBuf>,
+    dry_run: bool,
+    pool: &MySqlPool,
+) {
+    let files = match manifest_file {
+        Some(manifest_file) => match read_manifest(manifest_file) {
+            Ok(f) => f,
+            Err(e) => {
+                println!("{e}");
                 return;
             }
-        };
-        for topic in topics {
-            match topic.create_topic(pool).await {
-                EntityResult::Success(r) => println!("Topic created successfully: {:?}", r),
-                EntityResult::Error(e) => println!("Failed to create topic: {:?}", e),
-            }
+        },
+        None => files,
+    };
+
+    let rows = match load_all(&files) {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("{e}");
+            return;
         }
-    } else if authors_file.is_some() {
-        println!("Processing authors file");
-        println!("Ensure validating the file contents. Now processing the authors file: {:?}", authors_file);
-        let authors = match json_to_vec::<AuthorEntity>(authors_file.as_ref().unwrap().to_str().unwrap()) {
-            JsonOpsResult::Success(authors) => authors,
-            JsonOpsResult::Error(e) => {
-                println!("Failed to read authors file: {:?}", e);
-                return;
-            }
-        };
-        for author in authors {
-            match author.create(pool).await {
-                EntityResult::Success(r) => println!("Author created successfully: {:?}", r),
-                EntityResult::Error(e) => println!("Failed to create author: {:?}", e),
-            }
+    };
+
+    if rows.courses.is_empty() && rows.chapters.is_empty() && rows.topics.is_empty() && rows.authors.is_empty() && rows.tags.is_empty() {
+        println!("No file provided to seed the database");
+        return;
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            println!("Failed to start transaction: {:?}", e);
+            return;
         }
-    } else if tags_file.is_some() {
-        println!("Processing tags file");
-        println!("Ensure validating the file contents. Now processing the tags file: {:?}", tags_file);
-        let tags = match json_to_vec::<TagEntity>(tags_file.as_ref().unwrap().to_str().unwrap()) {
-            JsonOpsResult::Success(tags) => tags,
-            JsonOpsResult::Error(e) => {
-                println!("Failed to read tags file: {:?}", e);
-                return;
-            }
-        };
-        for tag in tags {
-            match tag.create(pool).await {
-                EntityResult::Success(r) => println!("Tag created successfully: {:?}", r),
-                EntityResult::Error(e) => println!("Failed to create tag: {:?}", e),
-            }
+    };
+
+    let result = async {
+        seed(&rows.courses, dry_run, &mut tx).await?;
+        seed(&rows.chapters, dry_run, &mut tx).await?;
+        seed(&rows.topics, dry_run, &mut tx).await?;
+        seed(&rows.authors, dry_run, &mut tx).await?;
+        seed(&rows.tags, dry_run, &mut tx).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        println!("{e}");
+        println!("Rolled back, nothing was seeded");
+        return;
+    }
+
+    if dry_run {
+        match tx.rollback().await {
+            Ok(_) => println!("Dry run complete, nothing was written"),
+            Err(e) => println!("Failed to end dry run: {:?}", e),
         }
-    } else {
-        println!("No file provided to seed the database");
+        return;
+    }
+
+    match tx.commit().await {
+        Ok(_) => println!(
+            "Seeded {} course(s), {} chapter(s), {} topic(s), {} author(s), {} tag(s)",
+            rows.courses.len(),
+            rows.chapters.len(),
+            rows.topics.len(),
+            rows.authors.len(),
+            rows.tags.len()
+        ),
+        Err(e) => println!("Failed to commit seed: {:?}", e),
     }
 }
@@ -4,7 +4,13 @@ use aarya_utils::file_ops::{file_exists, read_file_contents, FileOpsResult};
 use models::{questions::QuestionEntity, result_types::EntityResult};
 use sqlx::MySqlPool;
 
-pub async fn run_upload(course_id: u32, chapter_id: u32, topic_id: u32, data_file: &Path, pool: &MySqlPool) {
+pub async fn run_upload(
+    course_id: u32,
+    chapter_id: u32,
+    topic_id: u32,
+    data_file: &Path,
+    pool: &MySqlPool,
+) {
     let data_file = data_file.to_str().unwrap();
     if !file_exists(data_file) {
         println!("Data file is required and does not exist");
@@ -32,8 +38,9 @@ pub async fn run_upload(course_id: u32, chapter_id: u32, topic_id: u32, data_fil
         }
     };
 
+    let reason = format!("uploaded from {data_file}");
     for question in questions {
-        match question.create(pool).await {
+        match question.create(pool, "aarya_cli", Some(&reason)).await {
             EntityResult::Success(_) => println!("Question created successfully"),
             EntityResult::Error(e) => println!("Failed to create question: {:?}", e),
         }
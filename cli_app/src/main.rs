@@ -8,7 +8,7 @@ use handlers::{
     batchgener::run_batch,
     batchuploader::run_batch_uploads,
     blogposter::run_blog_poster,
-    seeder::run_seeder,
+    seeder::{run_seeder, SeedFiles},
     uploader::run_upload,
     validator::run_validate,
 };
@@ -90,7 +90,8 @@ enum Commands {
         #[arg(long, value_name = "FILE")]
         data_file: PathBuf,
     },
-    /// seed the database with courses, chapters, topics, and questions
+    /// seed the database with courses, chapters, topics, authors, and tags
+    /// all given files are applied in one transaction, rows are upserted so the command can be re-run
     /// run `aarya_cli validate --schema-file schema.json --data-file data.json`
     /// before running this command
     Seeder {
@@ -104,6 +105,14 @@ enum Commands {
         authors_file: Option<PathBuf>,
         #[arg(long, value_name = "FILE")]
         tags_file: Option<PathBuf>,
+
+        /// json file with the keys courses, chapters, topics, authors and tags; used instead of the file arguments
+        #[arg(long, value_name = "FILE")]
+        manifest_file: Option<PathBuf>,
+
+        /// print the inserts and updates without writing them
+        #[arg(long)]
+        dry_run: bool,
     },
     /// calls `autogen` in a loop using courses, chapters, and topics from the database
     Batchgen {
@@ -193,8 +202,17 @@ async fn main() {
             topics_file,
             authors_file,
             tags_file,
+            manifest_file,
+            dry_run,
         }) => {
-            run_seeder(courses_file, chapters_file, topics_file, authors_file, tags_file, &pool).await;
+            let files = SeedFiles {
+                courses: courses_file.clone(),
+                chapters: chapters_file.clone(),
+                topics: topics_file.clone(),
+                authors: authors_file.clone(),
+                tags: tags_file.clone(),
+            };
+            run_seeder(files, manifest_file, *dry_run, &pool).await;
         }
         Some(Commands::Batchgen {
             course_id,
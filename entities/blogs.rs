@@ -1,7 +1,7 @@
 use aarya_utils::hash_ops;
 use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 
 use crate::result_types::{DatabaseErrorType, EntityResult, SuccessResultType};
 
@@ -139,6 +139,56 @@ impl TagEntity {
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error fetching tags".to_string(), e.to_string())),
         }
     }
+
+    // tags are matched on their name when seeding
+    pub async fn exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        match sqlx::query_scalar::<_, i64>("SELECT count(tag_id) FROM tags WHERE tag_name = ?")
+            .bind(&self.tag_name)
+            .fetch_one(conn)
+            .await
+        {
+            Ok(count) => EntityResult::Success(count > 0),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error finding tag".to_string(), e.to_string())),
+        }
+    }
+
+    /// inserts the tag or updates the description of the tag with the same name
+    pub async fn upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        let exists = match self.exists(&mut *conn).await {
+            EntityResult::Success(exists) => exists,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        if exists {
+            match sqlx::query("UPDATE tags SET tag_description = ? WHERE tag_name = ?")
+                .bind(&self.tag_description)
+                .bind(&self.tag_name)
+                .execute(conn)
+                .await
+            {
+                Ok(d) => EntityResult::Success(SuccessResultType::Updated(self.tag_id as u64, d.rows_affected())),
+                Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error updating tag".to_string(), e.to_string())),
+            }
+        } else {
+            let tag_hash = hash_ops::string_hasher(format!("{}-{}", &self.tag_name, self.tag_id).as_str());
+            match sqlx::query("INSERT INTO tags (tag_name, tag_description, tag_hash) VALUES (?, ?, ?)")
+                .bind(&self.tag_name)
+                .bind(&self.tag_description)
+                .bind(tag_hash)
+                .execute(conn)
+                .await
+            {
+                Ok(d) => EntityResult::Success(SuccessResultType::Created(d.last_insert_id(), d.rows_affected())),
+                Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error creating tag".to_string(), e.to_string())),
+            }
+        }
+    }
 }
 
 impl Default for TagEntity {
@@ -200,6 +250,60 @@ impl AuthorEntity {
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error fetching authors".to_string(), e.to_string())),
         }
     }
+
+    // authors are matched on their email when seeding
+    pub async fn exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        match sqlx::query_scalar::<_, i64>("SELECT count(author_id) FROM authors WHERE author_email = ?")
+            .bind(&self.author_email)
+            .fetch_one(conn)
+            .await
+        {
+            Ok(count) => EntityResult::Success(count > 0),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error finding author".to_string(), e.to_string())),
+        }
+    }
+
+    /// inserts the author or updates the author with the same email
+    pub async fn upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        let exists = match self.exists(&mut *conn).await {
+            EntityResult::Success(exists) => exists,
+            EntityResult::Error(e) => return EntityResult::Error(e),
+        };
+
+        let author_hash = hash_ops::string_hasher(format!("{}-{}", &self.author_name, self.author_email).as_str());
+        let query = if exists {
+            r#"
+            UPDATE authors SET author_name = ?, author_bio = ?, author_photo_url = ?, author_intro = ?, author_hash = ?
+            WHERE author_email = ?
+        "#
+        } else {
+            r#"
+            INSERT INTO authors (author_name, author_bio, author_photo_url, author_intro, author_hash, author_email)
+            VALUES (?, ?, ?, ?, ?, ?)
+        "#
+        };
+
+        match sqlx::query(query)
+            .bind(&self.author_name)
+            .bind(&self.author_bio)
+            .bind(&self.author_photo_url)
+            .bind(&self.author_intro)
+            .bind(author_hash)
+            .bind(&self.author_email)
+            .execute(conn)
+            .await
+        {
+            Ok(d) if exists => EntityResult::Success(SuccessResultType::Updated(self.author_id.unwrap_or_default() as u64, d.rows_affected())),
+            Ok(d) => EntityResult::Success(SuccessResultType::Created(d.last_insert_id(), d.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error upserting author".to_string(), e.to_string())),
+        }
+    }
 }
 
 impl Default for AuthorEntity {
@@ -1,10 +1,11 @@
 use aarya_utils::hash_ops::string_hasher;
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 use validator::Validate;
 
 use crate::{
     courses::CourseDetailQueryModel,
+    questions::QuestionEntity,
     result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
 };
 
@@ -14,6 +15,9 @@ pub struct ChapterEntity {
     pub course_id: u32,
     pub chapter_name: String,
     pub chapter_description: String,
+    /// position of the chapter within its course
+    #[serde(default)]
+    pub chapter_order: u32,
 }
 
 #[derive(Validate, Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
@@ -31,6 +35,7 @@ impl ChapterEntity {
             course_id: 0,
             chapter_name: "not-set".to_string(),
             chapter_description: "not-set".to_string(),
+            chapter_order: 0,
         }
     }
 
@@ -44,8 +49,8 @@ impl ChapterEntity {
         let chapter_name_hash = string_hasher(&self.chapter_name);
 
         let query = r#"
-            INSERT INTO chapters (chapter_id, course_id, chapter_name, chapter_description, chapter_name_hash)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO chapters (chapter_id, course_id, chapter_name, chapter_description, chapter_name_hash, chapter_order)
+            VALUES (?, ?, ?, ?, ?, ?)
         "#;
 
         match sqlx::query(query)
@@ -54,6 +59,7 @@ impl ChapterEntity {
             .bind(&self.chapter_name)
             .bind(&self.chapter_description)
             .bind(chapter_name_hash)
+            .bind(self.chapter_order)
             .execute(pool)
             .await
         {
@@ -77,6 +83,7 @@ impl ChapterEntity {
             FROM chapters ch
                 JOIN courses co ON ch.course_id = co.course_id
             WHERE ch.course_id = ?
+            ORDER BY ch.chapter_order, ch.chapter_id
         "#;
 
         match sqlx::query_as::<_, ChapterQueryModel>(query).bind(self.course_id).fetch_all(pool).await {
@@ -110,6 +117,161 @@ impl ChapterEntity {
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error fetching chapters".to_string(), e.to_string())),
         }
     }
+
+    // rename a chapter, change its description or its position
+    pub async fn update(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            UPDATE chapters SET chapter_name = ?, chapter_description = ?, chapter_name_hash = ?, chapter_order = ?
+            WHERE chapter_id = ?
+        "#;
+
+        match sqlx::query(query)
+            .bind(&self.chapter_name)
+            .bind(&self.chapter_description)
+            .bind(string_hasher(&self.chapter_name))
+            .bind(self.chapter_order)
+            .bind(self.chapter_id)
+            .execute(pool)
+            .await
+        {
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.chapter_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error updating chapter".to_string(), e.to_string())),
+        }
+    }
+
+    /// sets chapter_order of the chapters of a course to their position in chapter_ids
+    pub async fn reorder(
+        &self,
+        pool: &MySqlPool,
+        chapter_ids: &[u32],
+    ) -> EntityResult<SuccessResultType> {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        let mut rows_affected = 0;
+        for (order, chapter_id) in chapter_ids.iter().enumerate() {
+            match sqlx::query("UPDATE chapters SET chapter_order = ? WHERE chapter_id = ? and course_id = ?")
+                .bind(order as u32)
+                .bind(chapter_id)
+                .bind(self.course_id)
+                .execute(&mut *tx)
+                .await
+            {
+                Ok(r) => rows_affected += r.rows_affected(),
+                Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error ordering chapters".to_string(), e.to_string())),
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Updated(self.course_id as u64, rows_affected)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error ordering chapters".to_string(), e.to_string())),
+        }
+    }
+
+    /// refuses to delete a chapter used by tests, or one that has questions unless cascade is set,
+    /// in which case its questions and topics are deleted in the same transaction
+    pub async fn delete(
+        &self,
+        pool: &MySqlPool,
+        cascade: bool,
+        editor: &str,
+    ) -> EntityResult<SuccessResultType> {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        match QuestionEntity::count_tests(&mut tx, "chapter_id", self.chapter_id).await {
+            Ok(0) => {}
+            Ok(tests) => {
+                return EntityResult::Error(DatabaseErrorType::Conflict(
+                    format!("Chapter {} is used by {tests} test(s)", self.chapter_id),
+                    "tests keep their questions, so it cannot be deleted".to_string(),
+                ))
+            }
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting tests of chapter".to_string(), e.to_string())),
+        }
+
+        let count = match QuestionEntity::count_all(&mut tx, "chapter_id", self.chapter_id).await {
+            Ok(count) => count,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting questions of chapter".to_string(), e.to_string())),
+        };
+        if count > 0 && !cascade {
+            return EntityResult::Error(DatabaseErrorType::Conflict(
+                format!("Chapter {} has {count} question(s)", self.chapter_id),
+                "delete with cascade to remove them".to_string(),
+            ));
+        }
+
+        let reason = format!("chapter {} deleted", self.chapter_id);
+        if let Err(e) = QuestionEntity::delete_all(&mut tx, "chapter_id", self.chapter_id, editor, Some(&reason)).await {
+            return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting questions of chapter".to_string(), e.to_string()));
+        }
+
+        for query in ["DELETE FROM topics WHERE chapter_id = ?", "DELETE FROM chapters WHERE chapter_id = ?"] {
+            if let Err(e) = sqlx::query(query).bind(self.chapter_id).execute(&mut *tx).await {
+                return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting chapter".to_string(), e.to_string()));
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Deleted(self.chapter_id as u64, count as u64)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error deleting chapter".to_string(), e.to_string())),
+        }
+    }
+
+    // used by the seeder to plan an insert or an update
+    pub async fn exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        match sqlx::query_scalar::<_, i64>("SELECT count(chapter_id) FROM chapters WHERE chapter_id = ?")
+            .bind(self.chapter_id)
+            .fetch_one(conn)
+            .await
+        {
+            Ok(count) => EntityResult::Success(count > 0),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error finding chapter".to_string(), e.to_string())),
+        }
+    }
+
+    /// inserts the chapter or updates the one with the same chapter_id
+    pub async fn upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            INSERT INTO chapters (chapter_id, course_id, chapter_name, chapter_description, chapter_name_hash, chapter_order)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                course_id = VALUES(course_id),
+                chapter_name = VALUES(chapter_name),
+                chapter_description = VALUES(chapter_description),
+                chapter_name_hash = VALUES(chapter_name_hash),
+                chapter_order = VALUES(chapter_order)
+        "#;
+
+        match sqlx::query(query)
+            .bind(self.chapter_id)
+            .bind(self.course_id)
+            .bind(&self.chapter_name)
+            .bind(&self.chapter_description)
+            .bind(string_hasher(&self.chapter_name))
+            .bind(self.chapter_order)
+            .execute(conn)
+            .await
+        {
+            // mysql reports 1 for an insert and 2 (or 0 when nothing changed) for an update
+            Ok(r) if r.rows_affected() == 1 => EntityResult::Success(SuccessResultType::Created(self.chapter_id as u64, r.rows_affected())),
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.chapter_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error upserting chapter".to_string(), e.to_string())),
+        }
+    }
 }
 
 impl Default for ChapterEntity {
@@ -1,9 +1,12 @@
 use aarya_utils::hash_ops::string_hasher;
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 use validator::Validate;
 
-use crate::result_types::{DatabaseErrorType, EntityResult, SuccessResultType};
+use crate::{
+    questions::QuestionEntity,
+    result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+};
 
 #[derive(Validate, Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
 pub struct CourseEntity {
@@ -29,6 +32,51 @@ pub struct CourseDetailQueryModel {
     pub topic_name: String,
 }
 
+/// flat course -> chapter -> topic row with the number of questions in the topic
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
+pub struct CatalogueQueryModel {
+    pub course_id: u32,
+    pub course_name: String,
+    pub course_description: String,
+    pub chapter_id: Option<u32>,
+    pub chapter_name: Option<String>,
+    pub chapter_description: Option<String>,
+    pub chapter_order: Option<u32>,
+    pub topic_id: Option<u32>,
+    pub topic_name: Option<String>,
+    pub topic_description: Option<String>,
+    pub topic_order: Option<u32>,
+    pub question_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CatalogueCourseModel {
+    pub course_id: u32,
+    pub course_name: String,
+    pub course_description: String,
+    pub question_count: i64,
+    pub chapters: Vec<CatalogueChapterModel>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CatalogueChapterModel {
+    pub chapter_id: u32,
+    pub chapter_name: String,
+    pub chapter_description: String,
+    pub chapter_order: u32,
+    pub question_count: i64,
+    pub topics: Vec<CatalogueTopicModel>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CatalogueTopicModel {
+    pub topic_id: u32,
+    pub topic_name: String,
+    pub topic_description: String,
+    pub topic_order: u32,
+    pub question_count: i64,
+}
+
 impl CourseEntity {
     pub fn new() -> Self {
         CourseEntity {
@@ -99,6 +147,207 @@ impl CourseEntity {
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error fetching courses".to_string(), e.to_string())),
         }
     }
+
+    // rename a course or change its description
+    pub async fn update(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            UPDATE courses SET course_name = ?, course_description = ?, course_name_hash = ?
+            WHERE course_id = ?
+        "#;
+
+        match sqlx::query(query)
+            .bind(&self.course_name)
+            .bind(&self.course_description)
+            .bind(string_hasher(&self.course_name))
+            .bind(self.course_id)
+            .execute(pool)
+            .await
+        {
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.course_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error updating course".to_string(), e.to_string())),
+        }
+    }
+
+    /// refuses to delete a course used by tests, or one that has questions unless cascade is set,
+    /// in which case its questions, topics and chapters are deleted in the same transaction
+    pub async fn delete(
+        &self,
+        pool: &MySqlPool,
+        cascade: bool,
+        editor: &str,
+    ) -> EntityResult<SuccessResultType> {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        match QuestionEntity::count_tests(&mut tx, "course_id", self.course_id).await {
+            Ok(0) => {}
+            Ok(tests) => {
+                return EntityResult::Error(DatabaseErrorType::Conflict(
+                    format!("Course {} is used by {tests} test(s)", self.course_id),
+                    "tests keep their questions, so it cannot be deleted".to_string(),
+                ))
+            }
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting tests of course".to_string(), e.to_string())),
+        }
+
+        let count = match QuestionEntity::count_all(&mut tx, "course_id", self.course_id).await {
+            Ok(count) => count,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting questions of course".to_string(), e.to_string())),
+        };
+        if count > 0 && !cascade {
+            return EntityResult::Error(DatabaseErrorType::Conflict(
+                format!("Course {} has {count} question(s)", self.course_id),
+                "delete with cascade to remove them".to_string(),
+            ));
+        }
+
+        let reason = format!("course {} deleted", self.course_id);
+        if let Err(e) = QuestionEntity::delete_all(&mut tx, "course_id", self.course_id, editor, Some(&reason)).await {
+            return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting questions of course".to_string(), e.to_string()));
+        }
+
+        for query in [
+            "DELETE FROM topics WHERE course_id = ?",
+            "DELETE FROM chapters WHERE course_id = ?",
+            "DELETE FROM courses WHERE course_id = ?",
+        ] {
+            if let Err(e) = sqlx::query(query).bind(self.course_id).execute(&mut *tx).await {
+                return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting course".to_string(), e.to_string()));
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Deleted(self.course_id as u64, count as u64)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error deleting course".to_string(), e.to_string())),
+        }
+    }
+
+    // the whole course -> chapter -> topic tree with question counts per node
+    pub async fn find_catalogue(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Vec<CatalogueCourseModel>> {
+        let query = r#"
+            SELECT
+                c.course_id,
+                c.course_name,
+                c.course_description,
+                ch.chapter_id,
+                ch.chapter_name,
+                ch.chapter_description,
+                ch.chapter_order,
+                t.topic_id,
+                t.topic_name,
+                t.topic_description,
+                t.topic_order,
+                count(q.question_id) as question_count
+            FROM courses c
+                left join chapters ch
+                    on ch.course_id = c.course_id
+                left join topics t
+                    on t.chapter_id = ch.chapter_id
+                left join questions q
+                    on q.topic_id = t.topic_id
+            GROUP BY c.course_id, c.course_name, c.course_description, ch.chapter_id, ch.chapter_name, ch.chapter_description, ch.chapter_order, t.topic_id, t.topic_name, t.topic_description, t.topic_order
+            ORDER BY c.course_id, ch.chapter_order, ch.chapter_id, t.topic_order, t.topic_id
+        "#;
+
+        let rows = match sqlx::query_as::<_, CatalogueQueryModel>(query).fetch_all(pool).await {
+            Ok(rows) => rows,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error fetching catalogue".to_string(), e.to_string())),
+        };
+
+        // rows are ordered by course and chapter, so a node only has to be compared with the last one
+        let mut courses: Vec<CatalogueCourseModel> = Vec::new();
+        for row in rows {
+            if courses.last().map(|c| c.course_id) != Some(row.course_id) {
+                courses.push(CatalogueCourseModel {
+                    course_id: row.course_id,
+                    course_name: row.course_name.clone(),
+                    course_description: row.course_description.clone(),
+                    question_count: 0,
+                    chapters: Vec::new(),
+                });
+            }
+            let course = courses.last_mut().unwrap();
+            course.question_count += row.question_count;
+
+            let chapter_id = match row.chapter_id {
+                Some(id) => id,
+                None => continue,
+            };
+            if course.chapters.last().map(|c| c.chapter_id) != Some(chapter_id) {
+                course.chapters.push(CatalogueChapterModel {
+                    chapter_id,
+                    chapter_name: row.chapter_name.clone().unwrap_or_default(),
+                    chapter_description: row.chapter_description.clone().unwrap_or_default(),
+                    chapter_order: row.chapter_order.unwrap_or_default(),
+                    question_count: 0,
+                    topics: Vec::new(),
+                });
+            }
+            let chapter = course.chapters.last_mut().unwrap();
+            chapter.question_count += row.question_count;
+
+            if let Some(topic_id) = row.topic_id {
+                chapter.topics.push(CatalogueTopicModel {
+                    topic_id,
+                    topic_name: row.topic_name.unwrap_or_default(),
+                    topic_description: row.topic_description.unwrap_or_default(),
+                    topic_order: row.topic_order.unwrap_or_default(),
+                    question_count: row.question_count,
+                });
+            }
+        }
+
+        EntityResult::Success(courses)
+    }
+
+    // used by the seeder to plan an insert or an update
+    pub async fn exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        match sqlx::query_scalar::<_, i64>("SELECT count(course_id) FROM courses WHERE course_id = ?")
+            .bind(self.course_id)
+            .fetch_one(conn)
+            .await
+        {
+            Ok(count) => EntityResult::Success(count > 0),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error finding course".to_string(), e.to_string())),
+        }
+    }
+
+    /// inserts the course or updates the one with the same course_id
+    pub async fn upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            INSERT INTO courses (course_id, course_name, course_description, course_name_hash)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE course_name = VALUES(course_name), course_description = VALUES(course_description), course_name_hash = VALUES(course_name_hash)
+        "#;
+
+        match sqlx::query(query)
+            .bind(self.course_id)
+            .bind(&self.course_name)
+            .bind(&self.course_description)
+            .bind(string_hasher(&self.course_name))
+            .execute(conn)
+            .await
+        {
+            // mysql reports 1 for an insert and 2 (or 0 when nothing changed) for an update
+            Ok(r) if r.rows_affected() == 1 => EntityResult::Success(SuccessResultType::Created(self.course_id as u64, r.rows_affected())),
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.course_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error upserting course".to_string(), e.to_string())),
+        }
+    }
 }
 
 impl Default for CourseEntity {
@@ -1,6 +1,8 @@
 pub mod blogs;
 pub mod chapters;
 pub mod courses;
+pub mod question_reports;
+pub mod question_revisions;
 pub mod questions;
 pub mod result_types;
 pub mod tests;
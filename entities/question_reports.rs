@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::result_types::{DatabaseErrorType, EntityResult, SuccessResultType};
+
+/// number of students with an open report after which a question is no longer picked for new tests
+pub const REPORT_THRESHOLD: u32 = 3;
+
+/// off until reports carry the real student id; every report is filed under the same placeholder student for now
+pub const SUPPRESS_REPORTED: bool = false;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    WrongAnswer,
+    Ambiguous,
+    Typo,
+    Other,
+}
+
+impl ReportReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::WrongAnswer => "wrong_answer",
+            ReportReason::Ambiguous => "ambiguous",
+            ReportReason::Typo => "typo",
+            ReportReason::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
+pub struct QuestionReportEntity {
+    pub report_id: Option<u32>,
+    pub question_id: u32,
+    pub test_id: Option<u32>,
+    pub student_id: u32,
+    pub report_reason: String,
+    pub report_comment: Option<String>,
+    pub report_state: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct QuestionReportMutationModel {
+    pub question_id: u32,
+    pub test_id: Option<u32>,
+    pub report_reason: ReportReason,
+    pub report_comment: Option<String>,
+}
+
+/// open reports grouped by question for the admin queue
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
+pub struct QuestionReportQueueModel {
+    pub question_id: u32,
+    pub que_text: String,
+    pub course_name: String,
+    pub chapter_name: String,
+    pub topic_name: String,
+    pub report_count: i64,
+    pub student_count: i64,
+    pub report_reasons: String,
+    #[sqlx(skip)]
+    pub suppressed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
+pub struct QuestionReportQueryModel {
+    pub report_id: u32,
+    pub question_id: u32,
+    pub test_id: Option<u32>,
+    pub student_id: u32,
+    pub report_reason: String,
+    pub report_comment: Option<String>,
+    pub report_state: u8,
+    pub report_timestamp: String,
+}
+
+impl QuestionReportEntity {
+    pub fn new() -> Self {
+        QuestionReportEntity {
+            report_id: Some(0),
+            question_id: 0,
+            test_id: None,
+            student_id: 0,
+            report_reason: ReportReason::Other.as_str().to_string(),
+            report_comment: None,
+            report_state: 0,
+        }
+    }
+
+    /// a student can hold one open report per question; a second one is a conflict
+    pub async fn create(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            INSERT INTO question_reports (question_id, test_id, student_id, report_reason, report_comment, report_state)
+            SELECT ?, ?, ?, ?, ?, ? FROM dual
+            WHERE NOT EXISTS (
+                SELECT 1 FROM question_reports
+                WHERE question_id = ? and student_id = ? and report_state = 0
+            )
+        "#;
+
+        let result = sqlx::query(query)
+            .bind(self.question_id)
+            .bind(self.test_id)
+            .bind(self.student_id)
+            .bind(&self.report_reason)
+            .bind(&self.report_comment)
+            .bind(self.report_state)
+            .bind(self.question_id)
+            .bind(self.student_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) if r.rows_affected() == 0 => EntityResult::Error(DatabaseErrorType::Conflict(
+                "Failed to create question report".to_string(),
+                format!("Student {} already has an open report on question {}", self.student_id, self.question_id),
+            )),
+            Ok(r) => EntityResult::Success(SuccessResultType::Created(r.last_insert_id(), r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to create question report".to_string(), e.to_string())),
+        }
+    }
+
+    // open reports grouped by question, most reported first
+    pub async fn find_open(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Vec<QuestionReportQueueModel>> {
+        let query = sqlx::query_as::<_, QuestionReportQueueModel>(
+            r#"
+            SELECT
+                q.question_id,
+                q.que_text,
+                c.course_name,
+                ch.chapter_name,
+                t.topic_name,
+                count(qr.report_id) as report_count,
+                count(distinct qr.student_id) as student_count,
+                group_concat(distinct qr.report_reason order by qr.report_reason separator ', ') as report_reasons
+            FROM question_reports qr
+                inner join questions q
+                    on qr.question_id = q.question_id
+                inner join courses c
+                    on q.course_id = c.course_id
+                inner join chapters ch
+                    on q.chapter_id = ch.chapter_id
+                inner join topics t
+                    on q.topic_id = t.topic_id
+            WHERE qr.report_state = 0
+            GROUP BY q.question_id, q.que_text, c.course_name, ch.chapter_name, t.topic_name
+            ORDER BY report_count DESC, q.question_id
+        "#,
+        )
+        .fetch_all(pool)
+        .await;
+
+        match query {
+            Ok(result) => EntityResult::Success(
+                result
+                    .into_iter()
+                    .map(|mut report| {
+                        report.suppressed = SUPPRESS_REPORTED && report.student_count >= REPORT_THRESHOLD as i64;
+                        report
+                    })
+                    .collect(),
+            ),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to read open question reports".to_string(), e.to_string())),
+        }
+    }
+
+    // all reports of a question, open ones first
+    pub async fn find_by_question(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Vec<QuestionReportQueryModel>> {
+        let query = sqlx::query_as::<_, QuestionReportQueryModel>(
+            r#"
+            SELECT
+                report_id,
+                question_id,
+                test_id,
+                student_id,
+                report_reason,
+                report_comment,
+                report_state,
+                cast(report_timestamp as char) as report_timestamp
+            FROM question_reports
+            WHERE question_id = ?
+            ORDER BY report_state, report_timestamp DESC
+        "#,
+        )
+        .bind(self.question_id)
+        .fetch_all(pool)
+        .await;
+
+        match query {
+            Ok(result) => EntityResult::Success(result),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(format!("Failed to read reports of question: {}", self.question_id), e.to_string())),
+        }
+    }
+
+    /// state: open (0), resolved (1), dismissed (2)
+    pub fn is_valid_state(state: u8) -> bool {
+        matches!(state, 0..=2)
+    }
+
+    /// state: open (0), resolved (1), dismissed (2)
+    pub async fn update_state(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let result = sqlx::query("UPDATE question_reports SET report_state = ? WHERE report_id = ?")
+            .bind(self.report_state)
+            .bind(self.report_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(r.last_insert_id(), r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to update question report".to_string(), e.to_string())),
+        }
+    }
+
+    /// moves every open report of a question to the given state
+    pub async fn update_state_by_question(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let result = sqlx::query("UPDATE question_reports SET report_state = ? WHERE question_id = ? and report_state = 0")
+            .bind(self.report_state)
+            .bind(self.question_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(r.last_insert_id(), r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to update question reports".to_string(), e.to_string())),
+        }
+    }
+}
+
+impl Default for QuestionReportEntity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::{
+    questions::QuestionEntity,
+    result_types::{DatabaseErrorType, EntityResult},
+};
+
+/// immutable snapshot of a question, written on every insert, update and delete
+/// the content is the state of the question after the action (for deletes: the deleted content)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
+pub struct QuestionRevisionEntity {
+    pub revision_id: u32,
+    pub question_id: u32,
+    pub action_type: String,
+    pub editor: String,
+    pub revision_reason: Option<String>,
+    pub revision_timestamp: String,
+    pub course_id: u32,
+    pub chapter_id: Option<u32>,
+    pub topic_id: Option<u32>,
+    pub que_text: String,
+    pub que_description: String,
+    pub choices: Value,
+    pub answers: Value,
+    pub ans_explanation: String,
+    pub ans_hint: String,
+    pub difficulty: i8,
+    pub diff_reason: String,
+    pub que_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl RevisionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionAction::Insert => "insert",
+            RevisionAction::Update => "update",
+            RevisionAction::Delete => "delete",
+        }
+    }
+}
+
+impl QuestionRevisionEntity {
+    pub fn new() -> Self {
+        QuestionRevisionEntity {
+            revision_id: 0,
+            question_id: 0,
+            action_type: RevisionAction::Insert.as_str().to_string(),
+            editor: "not-set".to_string(),
+            revision_reason: None,
+            revision_timestamp: "not-set".to_string(),
+            course_id: 0,
+            chapter_id: Some(0),
+            topic_id: Some(0),
+            que_text: "not-set".to_string(),
+            que_description: "not-set".to_string(),
+            choices: Value::Null,
+            answers: Value::Null,
+            ans_explanation: "not-set".to_string(),
+            ans_hint: "not-set".to_string(),
+            difficulty: 0,
+            diff_reason: "not-set".to_string(),
+            que_hash: None,
+        }
+    }
+
+    /// snapshots the current row of a question; runs on the caller's connection so it shares the caller's transaction
+    /// returns the new revision_id, None when the question does not exist
+    pub(crate) async fn snapshot(
+        conn: &mut MySqlConnection,
+        question_id: u32,
+        action: RevisionAction,
+        editor: &str,
+        reason: Option<&str>,
+    ) -> Result<Option<u64>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO question_revisions (
+                question_id,
+                action_type,
+                editor,
+                revision_reason,
+                course_id,
+                chapter_id,
+                topic_id,
+                que_text,
+                que_description,
+                choices,
+                answers,
+                radio,
+                ans_explanation,
+                ans_hint,
+                difficulty,
+                diff_reason,
+                que_hash)
+            SELECT
+                question_id, ?, ?, ?,
+                course_id, chapter_id, topic_id, que_text, que_description, choices, answers, radio,
+                ans_explanation, ans_hint, difficulty, diff_reason, que_hash
+            FROM questions
+            WHERE question_id = ?
+        "#,
+        )
+        .bind(action.as_str())
+        .bind(editor)
+        .bind(reason)
+        .bind(question_id)
+        .execute(conn)
+        .await?;
+
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_id()))
+    }
+
+    /// snapshots every question whose `column` (course_id, chapter_id or topic_id) equals `id`, used before cascading deletes
+    pub(crate) async fn snapshot_all(
+        conn: &mut MySqlConnection,
+        column: &'static str,
+        id: u32,
+        editor: &str,
+        reason: Option<&str>,
+    ) -> Result<u64, sqlx::Error> {
+        let query = format!(
+            r#"
+            INSERT INTO question_revisions (
+                question_id,
+                action_type,
+                editor,
+                revision_reason,
+                course_id,
+                chapter_id,
+                topic_id,
+                que_text,
+                que_description,
+                choices,
+                answers,
+                radio,
+                ans_explanation,
+                ans_hint,
+                difficulty,
+                diff_reason,
+                que_hash)
+            SELECT
+                question_id, ?, ?, ?,
+                course_id, chapter_id, topic_id, que_text, que_description, choices, answers, radio,
+                ans_explanation, ans_hint, difficulty, diff_reason, que_hash
+            FROM questions
+            WHERE {column} = ?
+        "#
+        );
+
+        let result = sqlx::query(&query).bind(RevisionAction::Delete.as_str()).bind(editor).bind(reason).bind(id).execute(conn).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // history of a question, newest first
+    pub async fn find_by_question(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Vec<QuestionRevisionEntity>> {
+        let query = sqlx::query_as::<_, QuestionRevisionEntity>(
+            r#"
+            SELECT
+                revision_id,
+                question_id,
+                action_type,
+                editor,
+                revision_reason,
+                cast(revision_timestamp as char) as revision_timestamp,
+                course_id,
+                chapter_id,
+                topic_id,
+                que_text,
+                que_description,
+                choices,
+                answers,
+                ans_explanation,
+                ans_hint,
+                difficulty,
+                diff_reason,
+                que_hash
+            FROM question_revisions
+            WHERE question_id = ?
+            ORDER BY revision_id DESC
+        "#,
+        )
+        .bind(self.question_id)
+        .fetch_all(pool)
+        .await;
+
+        match query {
+            Ok(result) => EntityResult::Success(result),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(format!("Failed to read revisions of question: {}", self.question_id), e.to_string())),
+        }
+    }
+
+    pub async fn find_one(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Option<QuestionRevisionEntity>> {
+        let query = sqlx::query_as::<_, QuestionRevisionEntity>(
+            r#"
+            SELECT
+                revision_id,
+                question_id,
+                action_type,
+                editor,
+                revision_reason,
+                cast(revision_timestamp as char) as revision_timestamp,
+                course_id,
+                chapter_id,
+                topic_id,
+                que_text,
+                que_description,
+                choices,
+                answers,
+                ans_explanation,
+                ans_hint,
+                difficulty,
+                diff_reason,
+                que_hash
+            FROM question_revisions
+            WHERE revision_id = ? and question_id = ?
+        "#,
+        )
+        .bind(self.revision_id)
+        .bind(self.question_id)
+        .fetch_optional(pool)
+        .await;
+
+        match query {
+            Ok(result) => EntityResult::Success(result),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError(format!("Failed to read revision: {}", self.revision_id), e.to_string())),
+        }
+    }
+
+    /// the question content as it was at this revision
+    pub fn to_question(&self) -> QuestionEntity {
+        QuestionEntity {
+            question_id: Some(self.question_id),
+            course_id: self.course_id,
+            chapter_id: self.chapter_id,
+            topic_id: self.topic_id,
+            que_text: self.que_text.clone(),
+            que_description: self.que_description.clone(),
+            choices: self.choices.clone(),
+            answers: self.answers.clone(),
+            ans_explanation: self.ans_explanation.clone(),
+            ans_hint: self.ans_hint.clone(),
+            difficulty: self.difficulty,
+            diff_reason: self.diff_reason.clone(),
+            que_hash: self.que_hash.clone(),
+        }
+    }
+}
+
+impl Default for QuestionRevisionEntity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,10 +1,14 @@
 use aarya_utils::{hash_ops, random::randomize_u32s};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 use validator::{Validate, ValidateLength};
 
-use crate::result_types::{DatabaseErrorType, EntityResult, SuccessResultType};
+use crate::{
+    question_reports::{REPORT_THRESHOLD, SUPPRESS_REPORTED},
+    question_revisions::{QuestionRevisionEntity, RevisionAction},
+    result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Choice {
@@ -84,12 +88,21 @@ impl Default for QuestionEntity {
 }
 
 impl QuestionEntity {
+    /// creates the question and its first revision in one transaction
     pub async fn create(
         &self,
         pool: &MySqlPool,
+        editor: &str,
+        reason: Option<&str>,
     ) -> EntityResult<SuccessResultType> {
         let que_hash = hash_ops::string_hasher(self.que_text.to_lowercase().as_str());
         let radio = self.answers.as_array().length().unwrap() == 1; // determines showing radio buttons or checkboxes
+
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
         let res = sqlx::query(
             "INSERT INTO questions (
                     question_id,
@@ -122,10 +135,25 @@ impl QuestionEntity {
         .bind(&self.ans_explanation)
         .bind(&self.ans_hint)
         .bind(que_hash)
-        .execute(pool)
+        .execute(&mut *tx)
         .await;
-        match res {
-            Ok(result) => EntityResult::Success(SuccessResultType::Created(result.last_insert_id(), result.rows_affected())),
+        let result = match res {
+            Ok(result) => result,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to create question".to_string(), e.to_string())),
+        };
+
+        // question_id is not auto generated, fall back to the insert id only when it was not given
+        let question_id = match self.question_id {
+            Some(id) if id > 0 => id,
+            _ => result.last_insert_id() as u32,
+        };
+
+        if let Err(e) = QuestionRevisionEntity::snapshot(&mut tx, question_id, RevisionAction::Insert, editor, reason).await {
+            return EntityResult::Error(DatabaseErrorType::QueryError("Failed to save question revision".to_string(), e.to_string()));
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Created(question_id as u64, result.rows_affected())),
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to create question".to_string(), e.to_string())),
         }
     }
@@ -241,6 +269,39 @@ impl QuestionEntity {
         }
     }
 
+    // read the editable columns of a question
+    pub async fn find_entity(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<Option<QuestionEntity>> {
+        let question = sqlx::query_as::<_, QuestionEntity>(
+            r#"
+            SELECT 
+                question_id, 
+                course_id, 
+                chapter_id, 
+                topic_id,
+                que_text, 
+                que_description, 
+                choices, 
+                answers,
+                ans_explanation, 
+                ans_hint, 
+                difficulty, 
+                diff_reason,
+                que_hash
+            FROM questions
+            WHERE question_id = ?"#,
+        )
+        .bind(self.question_id)
+        .fetch_optional(pool)
+        .await;
+        match question {
+            Ok(result) => EntityResult::Success(result),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to read question by id".to_string(), e.to_string())),
+        }
+    }
+
     pub async fn find_duplicate(
         &self,
         pool: &MySqlPool,
@@ -325,12 +386,21 @@ impl QuestionEntity {
                     and q.course_id = ? 
                     and q.chapter_id = ? 
                     and q.topic_id = ?
+                    and (? or q.question_id not in (
+                        SELECT qr.question_id
+                        FROM question_reports qr
+                        WHERE qr.report_state = 0
+                        GROUP BY qr.question_id
+                        HAVING count(distinct qr.student_id) >= ?
+                    ))
             "#,
         )
         .bind(self.difficulty)
         .bind(self.course_id)
         .bind(self.chapter_id)
         .bind(self.topic_id)
+        .bind(!SUPPRESS_REPORTED)
+        .bind(REPORT_THRESHOLD)
         .fetch_all(pool)
         .await;
 
@@ -347,52 +417,145 @@ impl QuestionEntity {
         }
     }
 
+    /// updates the question and records the new content as a revision in one transaction
     pub async fn update(
         &self,
         pool: &MySqlPool,
+        editor: &str,
+        reason: Option<&str>,
     ) -> EntityResult<SuccessResultType> {
+        let que_hash = hash_ops::string_hasher(self.que_text.to_lowercase().as_str());
+        // determines showing radio buttons or checkboxes
+        let radio = match self.answers.as_array() {
+            Some(answers) => answers.len() == 1,
+            None => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to update question".to_string(), "answers must be a list".to_string())),
+        };
+
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
         let res = sqlx::query(
             r#"
                 UPDATE questions SET 
                     course_id = ?, 
                     chapter_id = ?, 
+                    topic_id = ?,
                     que_text = ?, 
                     que_description = ?, 
                     answers = ?, 
+                    radio = ?,
                     choices = ?, 
                     difficulty = ?, 
                     diff_reason = ?, 
                     ans_explanation = ?, 
-                    ans_hint = ?
+                    ans_hint = ?,
+                    que_hash = ?
                 WHERE question_id = ?"#,
         )
         .bind(self.course_id)
         .bind(self.chapter_id)
+        .bind(self.topic_id)
         .bind(&self.que_text)
         .bind(&self.que_description)
         .bind(&self.answers)
+        .bind(radio)
         .bind(&self.choices)
         .bind(self.difficulty)
         .bind(&self.diff_reason)
         .bind(&self.ans_explanation)
         .bind(&self.ans_hint)
+        .bind(que_hash)
         .bind(self.question_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await;
-        match res {
-            Ok(result) => EntityResult::Success(SuccessResultType::Updated(result.last_insert_id(), result.rows_affected())),
+        let result = match res {
+            Ok(result) => result,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to update question".to_string(), e.to_string())),
+        };
+
+        match QuestionRevisionEntity::snapshot(&mut tx, self.question_id.unwrap_or_default(), RevisionAction::Update, editor, reason).await {
+            Ok(None) => return EntityResult::Error(DatabaseErrorType::NotFound("Question not found".to_string(), format!("{:?}", self.question_id))),
+            Ok(Some(_)) => {}
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to save question revision".to_string(), e.to_string())),
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Updated(result.last_insert_id(), result.rows_affected())),
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to update question".to_string(), e.to_string())),
         }
     }
 
+    /// records the deleted content as the last revision, then deletes the question
     pub async fn delete(
         &self,
         pool: &MySqlPool,
+        editor: &str,
+        reason: Option<&str>,
     ) -> EntityResult<SuccessResultType> {
-        let res = sqlx::query("DELETE FROM questions WHERE question_id = ?").bind(self.question_id).execute(pool).await;
-        match res {
-            Ok(result) => EntityResult::Success(SuccessResultType::Deleted(result.last_insert_id(), result.rows_affected())),
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        match QuestionRevisionEntity::snapshot(&mut tx, self.question_id.unwrap_or_default(), RevisionAction::Delete, editor, reason).await {
+            Ok(None) => return EntityResult::Error(DatabaseErrorType::NotFound("Question not found".to_string(), format!("{:?}", self.question_id))),
+            Ok(Some(_)) => {}
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to save question revision".to_string(), e.to_string())),
+        }
+
+        let res = sqlx::query("DELETE FROM questions WHERE question_id = ?").bind(self.question_id).execute(&mut *tx).await;
+        let result = match res {
+            Ok(result) => result,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Failed to delete question".to_string(), e.to_string())),
+        };
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Deleted(result.last_insert_id(), result.rows_affected())),
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Failed to delete question".to_string(), e.to_string())),
         }
     }
+
+    /// number of questions whose `column` (course_id, chapter_id or topic_id) equals `id`
+    pub(crate) async fn count_all(
+        conn: &mut MySqlConnection,
+        column: &'static str,
+        id: u32,
+    ) -> Result<i64, sqlx::Error> {
+        let query = format!("SELECT count(question_id) FROM questions WHERE {column} = ?");
+        sqlx::query_scalar::<_, i64>(&query).bind(id).fetch_one(conn).await
+    }
+
+    /// number of tests taken on `column` (course_id, chapter_id or topic_id) `id` or holding one of its questions
+    pub(crate) async fn count_tests(
+        conn: &mut MySqlConnection,
+        column: &'static str,
+        id: u32,
+    ) -> Result<i64, sqlx::Error> {
+        let query = format!(
+            "SELECT count(test_id) FROM tests WHERE {column} = ? or test_id in (SELECT tq.test_id FROM test_questions tq inner join questions q on tq.question_id = q.question_id WHERE q.{column} = ?)"
+        );
+        sqlx::query_scalar::<_, i64>(&query).bind(id).bind(id).fetch_one(conn).await
+    }
+
+    /// deletes every question whose `column` equals `id` along with its reports; each deleted question gets a delete revision
+    /// callers check count_tests first, questions used in tests are kept by the test_questions foreign key
+    pub(crate) async fn delete_all(
+        conn: &mut MySqlConnection,
+        column: &'static str,
+        id: u32,
+        editor: &str,
+        reason: Option<&str>,
+    ) -> Result<u64, sqlx::Error> {
+        QuestionRevisionEntity::snapshot_all(&mut *conn, column, id, editor, reason).await?;
+
+        let query = format!("DELETE FROM question_reports WHERE question_id in (SELECT question_id FROM questions WHERE {column} = ?)");
+        sqlx::query(&query).bind(id).execute(&mut *conn).await?;
+
+        let query = format!("DELETE FROM questions WHERE {column} = ?");
+        let result = sqlx::query(&query).bind(id).execute(&mut *conn).await?;
+
+        Ok(result.rows_affected())
+    }
 }
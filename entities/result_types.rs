@@ -3,6 +3,8 @@ pub enum DatabaseErrorType {
     NotFound(String, String),
     ConnectionError(String, String),
     QueryError(String, String),
+    /// the row is still referenced, e.g. deleting a topic that has questions
+    Conflict(String, String),
 }
 
 #[derive(Debug)]
@@ -20,6 +20,8 @@ pub struct TestEntity {
 pub struct TestQuestionsEntity {
     pub test_id: u32,
     pub question_id: u32,
+    /// revision of the question shown in the test, pinned to the latest one when the test is created
+    pub revision_id: Option<u32>,
     pub question_state: u32,
 }
 
@@ -27,7 +29,9 @@ pub struct TestQuestionsEntity {
 pub struct TestQuestionModel {
     pub test_id: u32,
     pub question_id: u32,
+    pub revision_id: u32,
     pub que_text: String,
+    pub que_description: String,
     pub difficulty: i8,
     pub choices: String,
     pub radio: bool,
@@ -221,6 +225,7 @@ impl TestQuestionsEntity {
         TestQuestionsEntity {
             test_id: 0,
             question_id: 0,
+            revision_id: None,
             question_state: 0,
         }
     }
@@ -229,12 +234,22 @@ impl TestQuestionsEntity {
         &self,
         pool: &MySqlPool,
     ) -> EntityResult<SuccessResultType> {
+        // without an explicit revision the test shows the latest revision of the question
         let query = r#"
-            INSERT INTO test_questions (test_id, question_id, question_state)
-            VALUES (?, ?, ?)
+            INSERT INTO test_questions (test_id, question_id, revision_id, question_state)
+            SELECT ?, ?, coalesce(?, max(revision_id)), ?
+            FROM question_revisions
+            WHERE question_id = ?
         "#;
 
-        let result = sqlx::query(query).bind(self.test_id).bind(self.question_id).bind(self.question_state).execute(pool).await;
+        let result = sqlx::query(query)
+            .bind(self.test_id)
+            .bind(self.question_id)
+            .bind(self.revision_id)
+            .bind(self.question_state)
+            .bind(self.question_id)
+            .execute(pool)
+            .await;
 
         match result {
             Ok(r) => EntityResult::Success(SuccessResultType::Created(r.last_insert_id(), r.rows_affected())),
@@ -249,25 +264,28 @@ impl TestQuestionsEntity {
         let query = sqlx::query_as::<_, TestQuestionModel>(
             r#"
             SELECT tq.test_id, 
-                q.question_id, 
-                q.que_text, 
-                q.difficulty,
-                q.choices,
-                q.radio,
-                q.diff_reason,
+                tq.question_id, 
+                tq.revision_id,
+                qr.que_text, 
+                qr.que_description,
+                qr.difficulty,
+                qr.choices,
+                qr.radio,
+                qr.diff_reason,
                 c.course_name,
                 ch.chapter_name,
                 t.topic_name
             FROM test_questions tq
-                inner join questions q
-                    on tq.question_id = q.question_id
+                inner join question_revisions qr
+                    on tq.revision_id = qr.revision_id
                 inner join courses c
-                    on q.course_id = c.course_id
+                    on qr.course_id = c.course_id
                 inner join chapters ch
-                    on q.course_id = ch.chapter_id
+                    on qr.chapter_id = ch.chapter_id
                 inner join topics t
-                    on q.topic_id = t.topic_id
-            WHERE test_id = ?;
+                    on qr.topic_id = t.topic_id
+            WHERE tq.test_id = ?
+            ORDER BY tq.id;
         "#,
         )
         .bind(self.test_id)
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 use validator::Validate;
 
-use crate::result_types::{DatabaseErrorType, EntityResult, SuccessResultType};
+use crate::{
+    questions::QuestionEntity,
+    result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
+};
 
 #[derive(Validate, Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
 pub struct TopicEntity {
@@ -11,6 +14,9 @@ pub struct TopicEntity {
     pub course_id: u32,
     pub topic_name: String,
     pub topic_description: String,
+    /// position of the topic within its chapter
+    #[serde(default)]
+    pub topic_order: u32,
 }
 
 #[derive(Validate, Debug, Serialize, Deserialize, PartialEq, Clone, sqlx::FromRow)]
@@ -32,6 +38,7 @@ impl TopicEntity {
             course_id: 0,
             topic_name: "not-set".to_string(),
             topic_description: "not-set".to_string(),
+            topic_order: 0,
         }
     }
 
@@ -40,8 +47,8 @@ impl TopicEntity {
         pool: &MySqlPool,
     ) -> EntityResult<SuccessResultType> {
         let query = r#"
-            INSERT INTO topics (topic_id, course_id, chapter_id, topic_name, topic_description)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO topics (topic_id, course_id, chapter_id, topic_name, topic_description, topic_order)
+            VALUES (?, ?, ?, ?, ?, ?)
         "#;
 
         match sqlx::query(query)
@@ -50,6 +57,7 @@ impl TopicEntity {
             .bind(self.chapter_id)
             .bind(&self.topic_name)
             .bind(&self.topic_description)
+            .bind(self.topic_order)
             .execute(pool)
             .await
         {
@@ -78,6 +86,7 @@ impl TopicEntity {
                 JOIN courses co 
                     ON t.course_id = co.course_id
             where t.course_id = ? and t.chapter_id = ?
+            ORDER BY t.topic_order, t.topic_id
         "#;
 
         match sqlx::query_as::<_, TopicQueryModel>(query).bind(self.course_id).bind(self.chapter_id).fetch_all(pool).await {
@@ -85,6 +94,158 @@ impl TopicEntity {
             Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error fetching chapters".to_string(), e.to_string())),
         }
     }
+
+    // rename a topic, change its description or its position
+    pub async fn update(
+        &self,
+        pool: &MySqlPool,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            UPDATE topics SET topic_name = ?, topic_description = ?, topic_order = ?
+            WHERE topic_id = ?
+        "#;
+
+        match sqlx::query(query)
+            .bind(&self.topic_name)
+            .bind(&self.topic_description)
+            .bind(self.topic_order)
+            .bind(self.topic_id)
+            .execute(pool)
+            .await
+        {
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.topic_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error updating topic".to_string(), e.to_string())),
+        }
+    }
+
+    /// sets topic_order of the topics of a chapter to their position in topic_ids
+    pub async fn reorder(
+        &self,
+        pool: &MySqlPool,
+        topic_ids: &[u32],
+    ) -> EntityResult<SuccessResultType> {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        let mut rows_affected = 0;
+        for (order, topic_id) in topic_ids.iter().enumerate() {
+            match sqlx::query("UPDATE topics SET topic_order = ? WHERE topic_id = ? and chapter_id = ?")
+                .bind(order as u32)
+                .bind(topic_id)
+                .bind(self.chapter_id)
+                .execute(&mut *tx)
+                .await
+            {
+                Ok(r) => rows_affected += r.rows_affected(),
+                Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error ordering topics".to_string(), e.to_string())),
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Updated(self.chapter_id as u64, rows_affected)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error ordering topics".to_string(), e.to_string())),
+        }
+    }
+
+    /// refuses to delete a topic used by tests, or one that has questions unless cascade is set,
+    /// in which case its questions are deleted in the same transaction
+    pub async fn delete(
+        &self,
+        pool: &MySqlPool,
+        cascade: bool,
+        editor: &str,
+    ) -> EntityResult<SuccessResultType> {
+        let mut tx = match pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::ConnectionError("Failed to start transaction".to_string(), e.to_string())),
+        };
+
+        match QuestionEntity::count_tests(&mut tx, "topic_id", self.topic_id).await {
+            Ok(0) => {}
+            Ok(tests) => {
+                return EntityResult::Error(DatabaseErrorType::Conflict(
+                    format!("Topic {} is used by {tests} test(s)", self.topic_id),
+                    "tests keep their questions, so it cannot be deleted".to_string(),
+                ))
+            }
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting tests of topic".to_string(), e.to_string())),
+        }
+
+        let count = match QuestionEntity::count_all(&mut tx, "topic_id", self.topic_id).await {
+            Ok(count) => count,
+            Err(e) => return EntityResult::Error(DatabaseErrorType::QueryError("Error counting questions of topic".to_string(), e.to_string())),
+        };
+        if count > 0 && !cascade {
+            return EntityResult::Error(DatabaseErrorType::Conflict(
+                format!("Topic {} has {count} question(s)", self.topic_id),
+                "delete with cascade to remove them".to_string(),
+            ));
+        }
+
+        let reason = format!("topic {} deleted", self.topic_id);
+        if let Err(e) = QuestionEntity::delete_all(&mut tx, "topic_id", self.topic_id, editor, Some(&reason)).await {
+            return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting questions of topic".to_string(), e.to_string()));
+        }
+
+        if let Err(e) = sqlx::query("DELETE FROM topics WHERE topic_id = ?").bind(self.topic_id).execute(&mut *tx).await {
+            return EntityResult::Error(DatabaseErrorType::QueryError("Error deleting topic".to_string(), e.to_string()));
+        }
+
+        match tx.commit().await {
+            Ok(_) => EntityResult::Success(SuccessResultType::Deleted(self.topic_id as u64, count as u64)),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error deleting topic".to_string(), e.to_string())),
+        }
+    }
+
+    // used by the seeder to plan an insert or an update
+    pub async fn exists(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<bool> {
+        match sqlx::query_scalar::<_, i64>("SELECT count(topic_id) FROM topics WHERE topic_id = ?")
+            .bind(self.topic_id)
+            .fetch_one(conn)
+            .await
+        {
+            Ok(count) => EntityResult::Success(count > 0),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error finding topic".to_string(), e.to_string())),
+        }
+    }
+
+    /// inserts the topic or updates the one with the same topic_id
+    pub async fn upsert(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> EntityResult<SuccessResultType> {
+        let query = r#"
+            INSERT INTO topics (topic_id, course_id, chapter_id, topic_name, topic_description, topic_order)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                course_id = VALUES(course_id),
+                chapter_id = VALUES(chapter_id),
+                topic_name = VALUES(topic_name),
+                topic_description = VALUES(topic_description),
+                topic_order = VALUES(topic_order)
+        "#;
+
+        match sqlx::query(query)
+            .bind(self.topic_id)
+            .bind(self.course_id)
+            .bind(self.chapter_id)
+            .bind(&self.topic_name)
+            .bind(&self.topic_description)
+            .bind(self.topic_order)
+            .execute(conn)
+            .await
+        {
+            // mysql reports 1 for an insert and 2 (or 0 when nothing changed) for an update
+            Ok(r) if r.rows_affected() == 1 => EntityResult::Success(SuccessResultType::Created(self.topic_id as u64, r.rows_affected())),
+            Ok(r) => EntityResult::Success(SuccessResultType::Updated(self.topic_id as u64, r.rows_affected())),
+            Err(e) => EntityResult::Error(DatabaseErrorType::QueryError("Error upserting topic".to_string(), e.to_string())),
+        }
+    }
 }
 
 impl Default for TopicEntity {
@@ -37,7 +37,7 @@ pub fn read_files_from_dir(
         } else {
             let path = path.to_str().unwrap();
             if path.ends_with(ext) {
-                let name = path.split('/').last().unwrap().split('.').next().unwrap().to_string();
+                let name = path.split('/').next_back().unwrap().split('.').next().unwrap().to_string();
                 files.push(FileInfo { name, path: path.to_string() });
             }
         }
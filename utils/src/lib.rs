@@ -3,6 +3,7 @@ pub mod file_ops;
 pub mod hash_ops;
 pub mod image_ops;
 pub mod json_ops;
+pub mod markdown_ops;
 pub mod openai;
 pub mod random;
 pub mod timestamps;
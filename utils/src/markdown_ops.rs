@@ -0,0 +1,109 @@
+use std::sync::OnceLock;
+
+use ammonia::Builder;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+/// css classes emitted for highlighted code are prefixed to avoid clashing with page styles
+pub const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// for markdown written by editors, questions and blog posts alike; heading ids and task list checkboxes are kept
+pub fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder
+            .add_generic_attributes(["class", "id"])
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "disabled"])
+            // task list checkboxes are the only inputs markdown may contain
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("input", "type") if value != "checkbox" => None,
+                _ => Some(value.into()),
+            });
+        builder
+    })
+}
+
+/// highlights a fenced code block; unknown or missing languages are rendered as plain text
+pub fn highlight(
+    code: &str,
+    lang: &str,
+) -> String {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set.find_syntax_by_token(lang).unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator.parse_html_for_line_which_includes_newline(line).is_err() {
+            // fall back to escaped, unhighlighted code
+            return format!("<pre><code>{}</code></pre>", ammonia::clean_text(code));
+        }
+    }
+
+    format!("<pre class=\"hl-code\"><code>{}</code></pre>", generator.finalize())
+}
+
+/// renders markdown to sanitised html, fenced code blocks are syntax highlighted
+pub fn to_html(input: &str) -> String {
+    let mut events = Vec::new();
+    let mut code_block: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(input, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.split_whitespace().next().unwrap_or_default().to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((lang, String::new()));
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, code)) = code_block.take() {
+                    events.push(Event::Html(highlight(&code, &lang).into()));
+                }
+            }
+            _ => events.push(event),
+        }
+    }
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+
+    sanitizer().clean(&html_output).to_string()
+}
+
+/// same as to_html but drops the paragraph wrapper of single line inputs, e.g. choice texts
+pub fn to_inline_html(input: &str) -> String {
+    let output = to_html(input);
+    let trimmed = output.trim();
+    match trimmed.strip_prefix("<p>").and_then(|s| s.strip_suffix("</p>")) {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// stylesheet for the classes emitted by the highlighter, built once
+pub fn highlight_css() -> &'static str {
+    static HIGHLIGHT_CSS: OnceLock<String> = OnceLock::new();
+    HIGHLIGHT_CSS.get_or_init(|| {
+        let themes = ThemeSet::load_defaults();
+        css_for_theme_with_class_style(&themes.themes[HIGHLIGHT_THEME], CLASS_STYLE).unwrap_or_default()
+    })
+}
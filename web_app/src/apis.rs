@@ -1,12 +1,17 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use aarya_utils::markdown_ops;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
 use models::{
     chapters::ChapterEntity,
+    courses::CourseEntity,
+    question_reports::{QuestionReportEntity, QuestionReportMutationModel},
+    question_revisions::QuestionRevisionEntity,
     questions::QuestionEntity,
-    result_types::{EntityResult, SuccessResultType},
+    result_types::{DatabaseErrorType, EntityResult, SuccessResultType},
     tests::{TestEntity, TestMutationModel, TestQuestionModel, TestQuestionsEntity},
     topics::TopicEntity,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::MySqlPool;
 
 #[derive(Debug, Serialize)]
@@ -17,6 +22,32 @@ pub struct QuestionLoadModel {
     eof: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReportStateModel {
+    report_state: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuestionUpdateModel {
+    #[serde(flatten)]
+    question: QuestionEntity,
+    revision_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackModel {
+    revision_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteQueryModel {
+    #[serde(default)]
+    cascade: bool,
+}
+
+// TODO: hard coded; replace with the signed in editor
+const EDITOR: &str = "admin";
+
 // get chapters -> GET /chapters/{course_id}
 #[get("/api/chapters/{course_id}")]
 pub async fn chapters_by_course(
@@ -102,6 +133,7 @@ pub async fn configure_test(
                 let test_question = TestQuestionsEntity {
                     test_id: test_id as u32,
                     question_id: question,
+                    revision_id: None,
                     question_state: 0,
                 };
                 match test_question.create(&pool).await {
@@ -132,7 +164,7 @@ pub async fn load_question_by_index(
     match test_questions.find_all(&pool).await {
         EntityResult::Success(result) => HttpResponse::Ok().json(QuestionLoadModel {
             total: result.len(),
-            current: result[index].clone(),
+            current: render_markdown(result[index].clone()),
             index: index + 1,
             eof: index == result.len() - 1,
         }),
@@ -141,3 +173,332 @@ pub async fn load_question_by_index(
 }
 
 // post start-test -> POST /start-test
+
+/// question fields are authored in markdown (often with java code blocks)
+/// render them to sanitised html before they are sent to the test page
+fn render_markdown(mut question: TestQuestionModel) -> TestQuestionModel {
+    question.que_text = markdown_ops::to_html(&question.que_text);
+    question.que_description = markdown_ops::to_html(&question.que_description);
+
+    // choices are stored as [{ "id": "", "text": "" }]
+    if let Ok(mut choices) = serde_json::from_str::<Vec<Value>>(&question.choices) {
+        for choice in choices.iter_mut() {
+            if let Some(text) = choice.get("text").and_then(Value::as_str) {
+                choice["text"] = Value::String(markdown_ops::to_inline_html(text));
+            }
+        }
+        question.choices = serde_json::to_string(&choices).unwrap_or(question.choices);
+    }
+
+    question
+}
+
+/// a student flags the question shown in a test
+/// one open report per student and question; suppression from tests is gated by SUPPRESS_REPORTED
+#[post("/api/report-question")]
+pub async fn report_question(
+    pool: web::Data<MySqlPool>,
+    model: web::Json<QuestionReportMutationModel>,
+) -> impl Responder {
+    let model = model.into_inner();
+    let report = QuestionReportEntity {
+        report_id: None,
+        question_id: model.question_id,
+        test_id: model.test_id,
+        // TODO: hard coded; replace with actual student id
+        student_id: 10001,
+        report_reason: model.report_reason.as_str().to_string(),
+        report_comment: model.report_comment.filter(|c| !c.trim().is_empty()),
+        report_state: 0,
+    };
+
+    match report.create(&pool).await {
+        EntityResult::Success(r) => match r {
+            SuccessResultType::Created(id, _) => HttpResponse::Ok().json(id),
+            _ => HttpResponse::Ok().finish(),
+        },
+        EntityResult::Error(DatabaseErrorType::Conflict(_, m)) => HttpResponse::Conflict().body(m),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error saving report: [{e:?}]")),
+    }
+}
+
+/// state: open (0), resolved (1), dismissed (2)
+#[post("/api/report/{report_id}/state")]
+pub async fn update_report_state(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<ReportStateModel>,
+) -> impl Responder {
+    if !QuestionReportEntity::is_valid_state(model.report_state) {
+        return HttpResponse::BadRequest().body("Report state must be 0 (open), 1 (resolved) or 2 (dismissed)");
+    }
+
+    let mut report = QuestionReportEntity::new();
+    report.report_id = Some(path.into_inner());
+    report.report_state = model.report_state;
+
+    match report.update_state(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating report: [{e:?}]")),
+    }
+}
+
+/// resolves or dismisses every open report of a question at once
+#[post("/api/question/{question_id}/reports/state")]
+pub async fn update_question_reports_state(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<ReportStateModel>,
+) -> impl Responder {
+    if !QuestionReportEntity::is_valid_state(model.report_state) {
+        return HttpResponse::BadRequest().body("Report state must be 0 (open), 1 (resolved) or 2 (dismissed)");
+    }
+
+    let mut report = QuestionReportEntity::new();
+    report.question_id = path.into_inner();
+    report.report_state = model.report_state;
+
+    match report.update_state_by_question(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating reports: [{e:?}]")),
+    }
+}
+
+#[post("/api/question/{question_id}")]
+pub async fn update_question(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<QuestionUpdateModel>,
+) -> impl Responder {
+    let model = model.into_inner();
+    let mut question = model.question;
+    question.question_id = Some(path.into_inner());
+    if !question.answers.is_array() {
+        return HttpResponse::BadRequest().body("Answers must be a list");
+    }
+
+    match question.update(&pool, EDITOR, model.revision_reason.as_deref()).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating question: [{e:?}]")),
+    }
+}
+
+#[get("/api/question/{question_id}/revisions")]
+pub async fn question_revisions(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+) -> impl Responder {
+    let mut revision = QuestionRevisionEntity::new();
+    revision.question_id = path.into_inner();
+
+    match revision.find_by_question(&pool).await {
+        EntityResult::Success(revisions) => HttpResponse::Ok().json(revisions),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error getting revisions: [{e:?}]")),
+    }
+}
+
+/// restores the content of a revision as a new revision, the history itself is never rewritten
+/// a deleted question is re-created with its original id
+#[post("/api/question/{question_id}/rollback/{revision_id}")]
+pub async fn rollback_question(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(u32, u32)>,
+    model: web::Json<RollbackModel>,
+) -> impl Responder {
+    let (question_id, revision_id) = path.into_inner();
+
+    let mut revision = QuestionRevisionEntity::new();
+    revision.question_id = question_id;
+    revision.revision_id = revision_id;
+    let revision = match revision.find_one(&pool).await {
+        EntityResult::Success(Some(r)) => r,
+        EntityResult::Success(None) => return HttpResponse::NotFound().body(format!("Revision {revision_id} of question {question_id} not found")),
+        EntityResult::Error(e) => return HttpResponse::InternalServerError().body(format!("Error getting revision: [{e:?}]")),
+    };
+
+    let reason = match model.into_inner().revision_reason.filter(|r| !r.trim().is_empty()) {
+        Some(r) => format!("rollback to revision {revision_id}: {r}"),
+        None => format!("rollback to revision {revision_id}"),
+    };
+
+    let question = revision.to_question();
+    let exists = match question.find_entity(&pool).await {
+        EntityResult::Success(q) => q.is_some(),
+        EntityResult::Error(e) => return HttpResponse::InternalServerError().body(format!("Error getting question: [{e:?}]")),
+    };
+
+    let result = if exists {
+        question.update(&pool, EDITOR, Some(&reason)).await
+    } else {
+        question.create(&pool, EDITOR, Some(&reason)).await
+    };
+
+    match result {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error rolling back question: [{e:?}]")),
+    }
+}
+
+/// deletes refused because of existing questions or tests are reported as 409 with the reason
+fn delete_response(result: EntityResult<SuccessResultType>) -> HttpResponse {
+    match result {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(DatabaseErrorType::Conflict(message, hint)) => HttpResponse::Conflict().body(format!("{message}, {hint}")),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error deleting: [{e:?}]")),
+    }
+}
+
+// get the course -> chapter -> topic tree with question counts -> GET /api/catalogue
+#[get("/api/catalogue")]
+pub async fn catalogue(pool: web::Data<MySqlPool>) -> impl Responder {
+    match CourseEntity::new().find_catalogue(&pool).await {
+        EntityResult::Success(courses) => HttpResponse::Ok().json(courses),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error getting catalogue: [{e:?}]")),
+    }
+}
+
+#[post("/api/course")]
+pub async fn create_course(
+    pool: web::Data<MySqlPool>,
+    model: web::Json<CourseEntity>,
+) -> impl Responder {
+    match model.into_inner().create(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error creating course: [{e:?}]")),
+    }
+}
+
+#[post("/api/course/{course_id}")]
+pub async fn update_course(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<CourseEntity>,
+) -> impl Responder {
+    let mut course = model.into_inner();
+    course.course_id = path.into_inner();
+
+    match course.update(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating course: [{e:?}]")),
+    }
+}
+
+#[delete("/api/course/{course_id}")]
+pub async fn delete_course(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    query: web::Query<DeleteQueryModel>,
+) -> impl Responder {
+    let mut course = CourseEntity::new();
+    course.course_id = path.into_inner();
+
+    delete_response(course.delete(&pool, query.cascade, EDITOR).await)
+}
+
+/// body: chapter ids of the course in their new order
+#[post("/api/course/{course_id}/chapters/order")]
+pub async fn order_chapters(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<Vec<u32>>,
+) -> impl Responder {
+    let mut chapter = ChapterEntity::new();
+    chapter.course_id = path.into_inner();
+
+    match chapter.reorder(&pool, &model).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error ordering chapters: [{e:?}]")),
+    }
+}
+
+#[post("/api/chapter")]
+pub async fn create_chapter(
+    pool: web::Data<MySqlPool>,
+    model: web::Json<ChapterEntity>,
+) -> impl Responder {
+    match model.into_inner().create_chapter(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error creating chapter: [{e:?}]")),
+    }
+}
+
+#[post("/api/chapter/{chapter_id}")]
+pub async fn update_chapter(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<ChapterEntity>,
+) -> impl Responder {
+    let mut chapter = model.into_inner();
+    chapter.chapter_id = path.into_inner();
+
+    match chapter.update(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating chapter: [{e:?}]")),
+    }
+}
+
+#[delete("/api/chapter/{chapter_id}")]
+pub async fn delete_chapter(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    query: web::Query<DeleteQueryModel>,
+) -> impl Responder {
+    let mut chapter = ChapterEntity::new();
+    chapter.chapter_id = path.into_inner();
+
+    delete_response(chapter.delete(&pool, query.cascade, EDITOR).await)
+}
+
+/// body: topic ids of the chapter in their new order
+#[post("/api/chapter/{chapter_id}/topics/order")]
+pub async fn order_topics(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<Vec<u32>>,
+) -> impl Responder {
+    let mut topic = TopicEntity::new();
+    topic.chapter_id = path.into_inner();
+
+    match topic.reorder(&pool, &model).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error ordering topics: [{e:?}]")),
+    }
+}
+
+#[post("/api/topic")]
+pub async fn create_topic(
+    pool: web::Data<MySqlPool>,
+    model: web::Json<TopicEntity>,
+) -> impl Responder {
+    match model.into_inner().create_topic(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error creating topic: [{e:?}]")),
+    }
+}
+
+#[post("/api/topic/{topic_id}")]
+pub async fn update_topic(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    model: web::Json<TopicEntity>,
+) -> impl Responder {
+    let mut topic = model.into_inner();
+    topic.topic_id = path.into_inner();
+
+    match topic.update(&pool).await {
+        EntityResult::Success(_) => HttpResponse::Ok().finish(),
+        EntityResult::Error(e) => HttpResponse::InternalServerError().body(format!("Error updating topic: [{e:?}]")),
+    }
+}
+
+#[delete("/api/topic/{topic_id}")]
+pub async fn delete_topic(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+    query: web::Query<DeleteQueryModel>,
+) -> impl Responder {
+    let mut topic = TopicEntity::new();
+    topic.topic_id = path.into_inner();
+
+    delete_response(topic.delete(&pool, query.cascade, EDITOR).await)
+}
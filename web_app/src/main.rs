@@ -6,8 +6,11 @@ use handlebars::Handlebars;
 use sqlx::MySqlPool;
 
 use crate::{
-    apis::{chapters_by_course, configure_test, load_question_by_index, topics_by},
-    pages::{home_page, test_config_page, test_start_page},
+    apis::{
+        catalogue, chapters_by_course, configure_test, create_chapter, create_course, create_topic, delete_chapter, delete_course, delete_topic, load_question_by_index, order_chapters, order_topics,
+        question_revisions, report_question, rollback_question, topics_by, update_chapter, update_course, update_question, update_question_reports_state, update_report_state, update_topic,
+    },
+    pages::{catalogue_page, highlight_css, home_page, question_edit_page, reports_page, test_config_page, test_start_page},
 };
 
 #[macro_use]
@@ -65,6 +68,28 @@ async fn main() -> std::io::Result<()> {
             .service(topics_by)
             .service(configure_test)
             .service(load_question_by_index)
+            .service(report_question)
+            .service(reports_page)
+            .service(question_edit_page)
+            .service(update_report_state)
+            .service(update_question_reports_state)
+            .service(update_question)
+            .service(question_revisions)
+            .service(rollback_question)
+            .service(catalogue)
+            .service(catalogue_page)
+            .service(create_course)
+            .service(update_course)
+            .service(delete_course)
+            .service(order_chapters)
+            .service(create_chapter)
+            .service(update_chapter)
+            .service(delete_chapter)
+            .service(order_topics)
+            .service(create_topic)
+            .service(update_topic)
+            .service(delete_topic)
+            .service(highlight_css)
     })
     .bind((ip, port))?
     .run()
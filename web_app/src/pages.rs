@@ -1,6 +1,7 @@
+use aarya_utils::markdown_ops;
 use actix_web::{get, web, HttpResponse, Responder};
 use handlebars::Handlebars;
-use models::{courses::CourseEntity, result_types::EntityResult};
+use models::{courses::CourseEntity, question_reports::QuestionReportEntity, question_revisions::QuestionRevisionEntity, questions::QuestionEntity, result_types::EntityResult};
 use serde_json::json;
 use sqlx::MySqlPool;
 
@@ -32,3 +33,89 @@ pub async fn test_start_page(
     let test_id = path.into_inner();
     render_template!(handlebars, "start-test", json!({"title": "Start a new test", "test_id":test_id}))
 }
+
+#[get("/styles/highlight.css")]
+pub async fn highlight_css() -> impl Responder {
+    HttpResponse::Ok().content_type("text/css").body(markdown_ops::highlight_css())
+}
+
+#[get("/admin/catalogue")]
+pub async fn catalogue_page(
+    handlebars: web::Data<Handlebars<'_>>,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let courses = match CourseEntity::new().find_catalogue(&pool).await {
+        EntityResult::Success(courses) => courses,
+        EntityResult::Error(_) => {
+            return HttpResponse::InternalServerError().body("Error getting catalogue");
+        }
+    };
+    render_template!(handlebars, "admin-catalogue", json!({"title": "Course catalogue", "courses": courses}))
+}
+
+#[get("/admin/reports")]
+pub async fn reports_page(
+    handlebars: web::Data<Handlebars<'_>>,
+    pool: web::Data<MySqlPool>,
+) -> impl Responder {
+    let report = QuestionReportEntity::new();
+    let reports = match report.find_open(&pool).await {
+        EntityResult::Success(reports) => reports,
+        EntityResult::Error(_) => {
+            return HttpResponse::InternalServerError().body("Error getting question reports");
+        }
+    };
+    render_template!(handlebars, "admin-reports", json!({"title": "Reported questions", "reports": reports}))
+}
+
+#[get("/admin/question/{question_id}")]
+pub async fn question_edit_page(
+    handlebars: web::Data<Handlebars<'_>>,
+    pool: web::Data<MySqlPool>,
+    path: web::Path<u32>,
+) -> impl Responder {
+    let question_id = path.into_inner();
+
+    let mut question = QuestionEntity::new();
+    question.question_id = Some(question_id);
+    let question = match question.find_entity(&pool).await {
+        EntityResult::Success(Some(question)) => question,
+        EntityResult::Success(None) => {
+            return HttpResponse::NotFound().body("Question not found");
+        }
+        EntityResult::Error(_) => {
+            return HttpResponse::InternalServerError().body("Error getting question");
+        }
+    };
+
+    let mut report = QuestionReportEntity::new();
+    report.question_id = question_id;
+    let reports = match report.find_by_question(&pool).await {
+        EntityResult::Success(reports) => reports,
+        EntityResult::Error(_) => {
+            return HttpResponse::InternalServerError().body("Error getting question reports");
+        }
+    };
+
+    let mut revision = QuestionRevisionEntity::new();
+    revision.question_id = question_id;
+    let revisions = match revision.find_by_question(&pool).await {
+        EntityResult::Success(revisions) => revisions,
+        EntityResult::Error(_) => {
+            return HttpResponse::InternalServerError().body("Error getting question revisions");
+        }
+    };
+
+    render_template!(
+        handlebars,
+        "question-edit",
+        json!({
+            "title": format!("Edit question {question_id}"),
+            "question": question,
+            "choices": serde_json::to_string_pretty(&question.choices).unwrap_or_default(),
+            "answers": serde_json::to_string_pretty(&question.answers).unwrap_or_default(),
+            "reports": reports,
+            "revisions": revisions
+        })
+    )
+}